@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS fexit_attachments;
+DROP TABLE IF EXISTS fentry_attachments;
+DROP TABLE IF EXISTS tracepoint_attachments;
+DROP TABLE IF EXISTS uprobe_attachments;
+DROP TABLE IF EXISTS kprobe_attachments;
+DROP TABLE IF EXISTS tcx_attachments;
+DROP TABLE IF EXISTS tc_attachments;
+DROP TABLE IF EXISTS xdp_attachments;
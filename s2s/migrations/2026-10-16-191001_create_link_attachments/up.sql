@@ -0,0 +1,84 @@
+-- Per-kind attachment details.
+--
+-- A row in bpf_links records that a program is (or will be) attached
+-- somewhere, but the parameters needed to perform that attachment
+-- differ by program kind. Each kind therefore gets its own detail
+-- table holding exactly one row per link, keyed by the link's ID.
+-- Deleting the link (or its program) removes the details with it.
+
+-- XDP attachments.
+CREATE TABLE xdp_attachments (
+    link_id INTEGER PRIMARY KEY NOT NULL REFERENCES bpf_links(id) ON DELETE CASCADE,
+    iface TEXT NOT NULL,              -- Interface name
+    if_index INTEGER,                 -- Interface index (resolved at attach time)
+    priority INTEGER NOT NULL,        -- Position within the XDP dispatcher
+    proceed_on TEXT NOT NULL DEFAULT '[]',  -- JSON array of XDP actions
+    current_position INTEGER,         -- Slot in the dispatcher, once attached
+    attached BOOLEAN NOT NULL DEFAULT FALSE,
+    nsid BIGINT                       -- Network namespace inode number
+);
+
+-- TC attachments (via the TC dispatcher).
+CREATE TABLE tc_attachments (
+    link_id INTEGER PRIMARY KEY NOT NULL REFERENCES bpf_links(id) ON DELETE CASCADE,
+    iface TEXT NOT NULL,
+    if_index INTEGER,
+    direction TEXT NOT NULL
+        CHECK(direction IN ('ingress', 'egress')),
+    priority INTEGER NOT NULL,
+    proceed_on TEXT NOT NULL DEFAULT '[]',  -- JSON array of TC actions
+    current_position INTEGER,
+    attached BOOLEAN NOT NULL DEFAULT FALSE,
+    nsid BIGINT
+);
+
+-- TCX attachments. TCX links are ordered by the kernel, so there is
+-- no dispatcher, proceed-on list or attached flag.
+CREATE TABLE tcx_attachments (
+    link_id INTEGER PRIMARY KEY NOT NULL REFERENCES bpf_links(id) ON DELETE CASCADE,
+    iface TEXT NOT NULL,
+    if_index INTEGER,
+    direction TEXT NOT NULL
+        CHECK(direction IN ('ingress', 'egress')),
+    priority INTEGER NOT NULL,
+    current_position INTEGER,
+    nsid BIGINT
+);
+
+-- Kprobe and kretprobe attachments.
+CREATE TABLE kprobe_attachments (
+    link_id INTEGER PRIMARY KEY NOT NULL REFERENCES bpf_links(id) ON DELETE CASCADE,
+    fn_name TEXT NOT NULL,            -- Kernel function to probe
+    fn_offset BIGINT NOT NULL DEFAULT 0,  -- Offset within the function
+    retprobe BOOLEAN NOT NULL,
+    container_pid INTEGER             -- Set when attaching inside a container
+);
+
+-- Uprobe and uretprobe attachments.
+CREATE TABLE uprobe_attachments (
+    link_id INTEGER PRIMARY KEY NOT NULL REFERENCES bpf_links(id) ON DELETE CASCADE,
+    target TEXT NOT NULL,             -- Binary or library path
+    fn_name TEXT,                     -- Optional symbol within the target
+    fn_offset BIGINT NOT NULL DEFAULT 0,
+    retprobe BOOLEAN NOT NULL,
+    pid INTEGER,                      -- Restrict to a single process
+    container_pid INTEGER
+);
+
+-- Tracepoint attachments.
+CREATE TABLE tracepoint_attachments (
+    link_id INTEGER PRIMARY KEY NOT NULL REFERENCES bpf_links(id) ON DELETE CASCADE,
+    tracepoint TEXT NOT NULL          -- "<category>/<name>", e.g. syscalls/sys_enter_kill
+);
+
+-- Fentry attachments.
+CREATE TABLE fentry_attachments (
+    link_id INTEGER PRIMARY KEY NOT NULL REFERENCES bpf_links(id) ON DELETE CASCADE,
+    fn_name TEXT NOT NULL
+);
+
+-- Fexit attachments.
+CREATE TABLE fexit_attachments (
+    link_id INTEGER PRIMARY KEY NOT NULL REFERENCES bpf_links(id) ON DELETE CASCADE,
+    fn_name TEXT NOT NULL
+);
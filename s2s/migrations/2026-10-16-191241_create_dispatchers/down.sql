@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS xdp_dispatchers;
+DROP TABLE IF EXISTS tc_dispatchers;
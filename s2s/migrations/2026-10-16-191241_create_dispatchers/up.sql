@@ -0,0 +1,64 @@
+-- Tables for TC and XDP dispatchers.
+--
+-- bpfman attaches multiple TC or XDP programs to a single interface
+-- by loading a dispatcher program and attaching each user program to
+-- one of its extension slots. There is at most one live dispatcher
+-- per network namespace, interface and (for TC) direction; when the
+-- set of extensions changes bpfman builds a replacement with the next
+-- revision number and removes the old one. The natural key is
+-- therefore the attach point, with the revision stored alongside.
+--
+-- The programs occupying a dispatcher's slots are the attached rows
+-- in tc_attachments / xdp_attachments with the same nsid and
+-- if_index, ordered by current_position.
+
+CREATE TABLE tc_dispatchers (
+    nsid BIGINT NOT NULL,            -- Network namespace inode number
+    if_index INTEGER NOT NULL,       -- Interface index
+    direction TEXT NOT NULL
+        CHECK(direction IN ('ingress', 'egress')),
+    revision INTEGER NOT NULL,       -- Incremented on every rebuild
+    if_name TEXT NOT NULL,           -- Interface name
+    priority INTEGER NOT NULL,       -- TC filter priority of the dispatcher
+    handle INTEGER,                  -- TC filter handle, once attached
+    num_extensions INTEGER NOT NULL DEFAULT 0,
+    program_name TEXT,               -- Dispatcher program name
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (nsid, if_index, direction)
+);
+
+CREATE TABLE xdp_dispatchers (
+    nsid BIGINT NOT NULL,
+    if_index INTEGER NOT NULL,
+    revision INTEGER NOT NULL,
+    if_name TEXT NOT NULL,
+    mode INTEGER NOT NULL,           -- bpfman's XDP attach mode discriminant
+    num_extensions INTEGER NOT NULL DEFAULT 0,
+    program_name TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (nsid, if_index)
+);
+
+-- Trigger for tc_dispatchers.
+CREATE TRIGGER update_tc_dispatchers_updated_at
+AFTER UPDATE ON tc_dispatchers
+FOR EACH ROW
+WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE tc_dispatchers
+  SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+  WHERE nsid = NEW.nsid AND if_index = NEW.if_index AND direction = NEW.direction;
+END;
+
+-- Trigger for xdp_dispatchers.
+CREATE TRIGGER update_xdp_dispatchers_updated_at
+AFTER UPDATE ON xdp_dispatchers
+FOR EACH ROW
+WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE xdp_dispatchers
+  SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+  WHERE nsid = NEW.nsid AND if_index = NEW.if_index;
+END;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS image_maps;
+DROP TABLE IF EXISTS image_programs;
+DROP TABLE IF EXISTS images;
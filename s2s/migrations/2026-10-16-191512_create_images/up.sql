@@ -0,0 +1,37 @@
+-- Tables for the bytecode image cache.
+--
+-- bpfman pulls bytecode from OCI images and caches the manifest,
+-- config and bytecode layer locally. Each image is identified by the
+-- URL it was pulled from, which is what bpf_programs.image_url holds;
+-- the digest is the image's config digest, so re-pulling a mutable
+-- tag (e.g. :latest) may change it.
+CREATE TABLE images (
+    url TEXT PRIMARY KEY NOT NULL,   -- e.g. quay.io/bpfman-bytecode/go-xdp-counter:latest
+    digest TEXT NOT NULL,            -- Config digest, e.g. sha256:24c2...
+    tag TEXT,                        -- Tag parsed from the URL, if any
+    pulled_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    manifest TEXT NOT NULL,          -- Raw OCI manifest JSON
+    config TEXT NOT NULL,            -- Raw OCI image config JSON
+    layer BLOB                       -- Compressed bytecode layer
+);
+
+CREATE INDEX images_digest ON images(digest);
+
+-- Programs declared by an image's io.ebpf.programs label, which maps
+-- each program name to its bpfman program type (e.g. "xdp", "tc",
+-- "probe").
+CREATE TABLE image_programs (
+    image_url TEXT NOT NULL REFERENCES images(url) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    PRIMARY KEY (image_url, name)
+);
+
+-- Maps declared by an image's io.ebpf.maps label, which maps each map
+-- name to its type (e.g. "per_cpu_array").
+CREATE TABLE image_maps (
+    image_url TEXT NOT NULL REFERENCES images(url) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    map_type TEXT NOT NULL,
+    PRIMARY KEY (image_url, name)
+);
@@ -0,0 +1,22 @@
+-- Registry credentials no longer live in the database. They are kept
+-- in a secret store (see s2s::secrets) and bpf_programs records only
+-- the key under which they are stored, so database dumps never
+-- contain registry passwords.
+--
+-- SQL cannot reach the secret store, so this migration refuses to run
+-- while any program still has a username or password, rather than
+-- discard them. Copy them into the secret store and set both columns
+-- to NULL first.
+CREATE TEMP TABLE credentials_guard (id INTEGER);
+CREATE TEMP TRIGGER credentials_guard_abort
+BEFORE INSERT ON credentials_guard
+BEGIN
+  SELECT RAISE(ABORT, 'bpf_programs has registry credentials in username or password; move them to the secret store and set both columns to NULL before applying this migration');
+END;
+INSERT INTO credentials_guard
+SELECT id FROM bpf_programs WHERE username IS NOT NULL OR password IS NOT NULL;
+DROP TABLE credentials_guard;
+
+ALTER TABLE bpf_programs ADD COLUMN credentials_ref TEXT;
+ALTER TABLE bpf_programs DROP COLUMN username;
+ALTER TABLE bpf_programs DROP COLUMN password;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS bpf_programs_map_owner_delete;
+DROP TRIGGER IF EXISTS bpf_programs_map_owner_update;
+DROP TRIGGER IF EXISTS bpf_programs_map_owner_insert;
+DROP INDEX IF EXISTS bpf_programs_map_owner_id;
@@ -0,0 +1,69 @@
+-- Map sharing rules.
+--
+-- A program either owns its maps (map_owner_id IS NULL) or uses the
+-- maps of another program, its map owner. Ownership is one level
+-- deep: an owner must itself own its maps, so a program that has
+-- dependants cannot become a dependant. These rules are enforced with
+-- triggers rather than a foreign key because SQLite cannot add a
+-- constraint to an existing table, and because triggers hold whether
+-- or not foreign key enforcement is enabled on the connection.
+--
+-- Triggers only see later writes, so existing programs are checked
+-- explicitly: one whose map owner does not exist, or is itself a
+-- dependant, makes the migration fail.
+
+CREATE TEMP TABLE map_owner_guard (id INTEGER);
+CREATE TEMP TRIGGER map_owner_guard_abort
+BEFORE INSERT ON map_owner_guard
+BEGIN
+  SELECT RAISE(ABORT, 'map owner does not exist or does not own its maps');
+END;
+INSERT INTO map_owner_guard
+SELECT id FROM bpf_programs AS dependant
+WHERE map_owner_id IS NOT NULL AND NOT EXISTS (
+  SELECT 1 FROM bpf_programs AS owner
+  WHERE owner.id = dependant.map_owner_id AND owner.map_owner_id IS NULL
+);
+DROP TABLE map_owner_guard;
+
+CREATE INDEX bpf_programs_map_owner_id ON bpf_programs(map_owner_id);
+
+CREATE TRIGGER bpf_programs_map_owner_insert
+BEFORE INSERT ON bpf_programs
+FOR EACH ROW
+WHEN NEW.map_owner_id IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'map owner does not exist or does not own its maps')
+  WHERE NOT EXISTS (
+    SELECT 1 FROM bpf_programs
+    WHERE id = NEW.map_owner_id AND map_owner_id IS NULL
+  );
+END;
+
+CREATE TRIGGER bpf_programs_map_owner_update
+BEFORE UPDATE OF map_owner_id ON bpf_programs
+FOR EACH ROW
+WHEN NEW.map_owner_id IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'a program cannot own its own maps via map_owner_id')
+  WHERE NEW.map_owner_id = NEW.id;
+  SELECT RAISE(ABORT, 'map owner does not exist or does not own its maps')
+  WHERE NOT EXISTS (
+    SELECT 1 FROM bpf_programs
+    WHERE id = NEW.map_owner_id AND map_owner_id IS NULL
+  );
+  SELECT RAISE(ABORT, 'program owns maps used by other programs')
+  WHERE EXISTS (
+    SELECT 1 FROM bpf_programs WHERE map_owner_id = OLD.id
+  );
+END;
+
+CREATE TRIGGER bpf_programs_map_owner_delete
+BEFORE DELETE ON bpf_programs
+FOR EACH ROW
+BEGIN
+  SELECT RAISE(ABORT, 'program owns maps used by other programs')
+  WHERE EXISTS (
+    SELECT 1 FROM bpf_programs WHERE map_owner_id = OLD.id
+  );
+END;
@@ -0,0 +1,199 @@
+-- Kernel IDs and sizes are u32, but Diesel reads an SQLite column
+-- declared INTEGER as a 32-bit signed value, so anything above
+-- i32::MAX would be silently truncated on read. Redeclare the u32
+-- columns of bpf_programs as BIGINT, which Diesel reads as 64 bits,
+-- and constrain them to the u32 range. The program, map and link ID
+-- columns of bpf_links, bpf_maps and bpf_program_maps, which are
+-- already read as 64 bits, get the same constraint; the attachment
+-- tables' link_id columns reference bpf_links(id), so are covered by
+-- it.
+--
+-- SQLite cannot change a column's type or add a constraint in place,
+-- so the tables are rebuilt. Foreign key enforcement must be off
+-- while this runs, as it is by default.
+
+CREATE TABLE bpf_programs_new (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id BETWEEN 0 AND 4294967295),
+    name TEXT NOT NULL,
+    description TEXT,
+    kind TEXT NOT NULL
+        CHECK(kind IN ('xdp', 'tc', 'tcx', 'tracepoint', 'kprobe', 'uprobe', 'fentry', 'fexit')),
+    state TEXT NOT NULL
+        CHECK(state IN ('pre_load', 'loaded')),
+    location_type TEXT NOT NULL
+        CHECK(location_type IN ('file', 'image')),
+    file_path TEXT,
+    image_url TEXT,
+    image_pull_policy TEXT,
+    map_pin_path TEXT NOT NULL,
+    map_owner_id BIGINT CHECK (map_owner_id BETWEEN 0 AND 4294967295),
+    program_bytes BLOB NOT NULL,
+    metadata TEXT NOT NULL DEFAULT '{}',
+    global_data TEXT NOT NULL DEFAULT '{}',
+    retprobe BOOLEAN,
+    fn_name TEXT,
+    kernel_name TEXT,
+    kernel_program_type INTEGER,
+    kernel_loaded_at TEXT,
+    kernel_tag TEXT,
+    kernel_gpl_compatible BOOLEAN,
+    kernel_btf_id BIGINT CHECK (kernel_btf_id BETWEEN 0 AND 4294967295),
+    kernel_bytes_xlated BIGINT CHECK (kernel_bytes_xlated BETWEEN 0 AND 4294967295),
+    kernel_jited BOOLEAN,
+    kernel_bytes_jited BIGINT CHECK (kernel_bytes_jited BETWEEN 0 AND 4294967295),
+    kernel_verified_insns INTEGER,
+    kernel_map_ids TEXT NOT NULL DEFAULT '[]',
+    kernel_bytes_memlock BIGINT CHECK (kernel_bytes_memlock BETWEEN 0 AND 4294967295),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    credentials_ref TEXT,
+    CHECK (
+      (location_type = 'file' AND file_path IS NOT NULL)
+      OR (location_type = 'image' AND image_url IS NOT NULL)
+    ),
+    CHECK (
+      (kind IN ('fentry', 'fexit') AND fn_name IS NOT NULL)
+      OR (kind NOT IN ('fentry', 'fexit'))
+    ),
+    CHECK (
+      (kind IN ('kprobe', 'uprobe') AND retprobe IS NOT NULL)
+      OR (kind NOT IN ('kprobe', 'uprobe'))
+    )
+);
+
+INSERT INTO bpf_programs_new SELECT
+    id, name, description, kind, state, location_type, file_path,
+    image_url, image_pull_policy, map_pin_path, map_owner_id,
+    program_bytes, metadata, global_data, retprobe, fn_name,
+    kernel_name, kernel_program_type, kernel_loaded_at, kernel_tag,
+    kernel_gpl_compatible, kernel_btf_id, kernel_bytes_xlated,
+    kernel_jited, kernel_bytes_jited, kernel_verified_insns,
+    kernel_map_ids, kernel_bytes_memlock, created_at, updated_at,
+    credentials_ref
+FROM bpf_programs;
+
+DROP TABLE bpf_programs;
+ALTER TABLE bpf_programs_new RENAME TO bpf_programs;
+
+-- Dropping the table dropped its triggers and indexes.
+CREATE TRIGGER update_bpf_programs_updated_at
+AFTER UPDATE ON bpf_programs
+FOR EACH ROW
+WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE bpf_programs
+  SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+  WHERE id = NEW.id;
+END;
+
+CREATE INDEX bpf_programs_map_owner_id ON bpf_programs(map_owner_id);
+
+CREATE TRIGGER bpf_programs_map_owner_insert
+BEFORE INSERT ON bpf_programs
+FOR EACH ROW
+WHEN NEW.map_owner_id IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'map owner does not exist or does not own its maps')
+  WHERE NOT EXISTS (
+    SELECT 1 FROM bpf_programs
+    WHERE id = NEW.map_owner_id AND map_owner_id IS NULL
+  );
+END;
+
+CREATE TRIGGER bpf_programs_map_owner_update
+BEFORE UPDATE OF map_owner_id ON bpf_programs
+FOR EACH ROW
+WHEN NEW.map_owner_id IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'a program cannot own its own maps via map_owner_id')
+  WHERE NEW.map_owner_id = NEW.id;
+  SELECT RAISE(ABORT, 'map owner does not exist or does not own its maps')
+  WHERE NOT EXISTS (
+    SELECT 1 FROM bpf_programs
+    WHERE id = NEW.map_owner_id AND map_owner_id IS NULL
+  );
+  SELECT RAISE(ABORT, 'program owns maps used by other programs')
+  WHERE EXISTS (
+    SELECT 1 FROM bpf_programs WHERE map_owner_id = OLD.id
+  );
+END;
+
+CREATE TRIGGER bpf_programs_map_owner_delete
+BEFORE DELETE ON bpf_programs
+FOR EACH ROW
+BEGIN
+  SELECT RAISE(ABORT, 'program owns maps used by other programs')
+  WHERE EXISTS (
+    SELECT 1 FROM bpf_programs WHERE map_owner_id = OLD.id
+  );
+END;
+
+CREATE TABLE bpf_links_new (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id BETWEEN 0 AND 4294967295),
+    program_id BIGINT NOT NULL REFERENCES bpf_programs(id) ON DELETE CASCADE
+        CHECK (program_id BETWEEN 0 AND 4294967295),
+    link_type TEXT,
+    target TEXT,
+    state TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO bpf_links_new SELECT
+    id, program_id, link_type, target, state, created_at, updated_at
+FROM bpf_links;
+
+DROP TABLE bpf_links;
+ALTER TABLE bpf_links_new RENAME TO bpf_links;
+
+CREATE TABLE bpf_maps_new (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id BETWEEN 0 AND 4294967295),
+    name TEXT NOT NULL,
+    map_type TEXT,
+    key_size INTEGER,
+    value_size INTEGER,
+    max_entries INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO bpf_maps_new SELECT
+    id, name, map_type, key_size, value_size, max_entries, created_at,
+    updated_at
+FROM bpf_maps;
+
+DROP TABLE bpf_maps;
+ALTER TABLE bpf_maps_new RENAME TO bpf_maps;
+
+CREATE TABLE bpf_program_maps_new (
+    program_id BIGINT NOT NULL REFERENCES bpf_programs(id) ON DELETE CASCADE
+        CHECK (program_id BETWEEN 0 AND 4294967295),
+    map_id BIGINT NOT NULL REFERENCES bpf_maps(id) ON DELETE CASCADE
+        CHECK (map_id BETWEEN 0 AND 4294967295),
+    PRIMARY KEY (program_id, map_id)
+);
+
+INSERT INTO bpf_program_maps_new SELECT program_id, map_id FROM bpf_program_maps;
+
+DROP TABLE bpf_program_maps;
+ALTER TABLE bpf_program_maps_new RENAME TO bpf_program_maps;
+
+CREATE TRIGGER update_bpf_links_updated_at
+AFTER UPDATE ON bpf_links
+FOR EACH ROW
+WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE bpf_links
+  SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+  WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER update_bpf_maps_updated_at
+AFTER UPDATE ON bpf_maps
+FOR EACH ROW
+WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE bpf_maps
+  SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+  WHERE id = NEW.id;
+END;
@@ -0,0 +1,59 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER bpf_programs_kind_update;
+
+CREATE TABLE bpf_links_new (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id BETWEEN 0 AND 4294967295),
+    program_id BIGINT NOT NULL REFERENCES bpf_programs(id) ON DELETE CASCADE
+        CHECK (program_id BETWEEN 0 AND 4294967295),
+    link_type TEXT,
+    target TEXT,
+    state TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO bpf_links_new SELECT
+    id, program_id, link_type, target, state, created_at, updated_at
+FROM bpf_links;
+
+DROP TABLE bpf_links;
+ALTER TABLE bpf_links_new RENAME TO bpf_links;
+
+CREATE TABLE bpf_maps_new (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id BETWEEN 0 AND 4294967295),
+    name TEXT NOT NULL,
+    map_type TEXT,
+    key_size INTEGER,
+    value_size INTEGER,
+    max_entries INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO bpf_maps_new SELECT
+    id, name, map_type, key_size, value_size, max_entries, created_at,
+    updated_at
+FROM bpf_maps;
+
+DROP TABLE bpf_maps;
+ALTER TABLE bpf_maps_new RENAME TO bpf_maps;
+
+CREATE TRIGGER update_bpf_links_updated_at
+AFTER UPDATE ON bpf_links
+FOR EACH ROW
+WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE bpf_links
+  SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+  WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER update_bpf_maps_updated_at
+AFTER UPDATE ON bpf_maps
+FOR EACH ROW
+WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE bpf_maps
+  SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+  WHERE id = NEW.id;
+END;
@@ -0,0 +1,139 @@
+-- Constrain the bpf_links and bpf_maps discriminators as the initial
+-- schema does for bpf_programs.
+--
+-- bpf_links.state must be a known link state, and link_type, when
+-- set, must name the kind of the program the link attaches. The
+-- latter spans two tables, so it is enforced with triggers on both;
+-- a program's kind cannot change while it has links of the old kind.
+--
+-- bpf_maps.map_type, when set, must be a known map type. A NULL type
+-- is still allowed for maps recorded from a program's kernel map IDs
+-- before their details are known.
+--
+-- SQLite cannot add a CHECK constraint to an existing table, so both
+-- tables are rebuilt. Existing rows that violate the new constraints
+-- make the migration fail. The link_type triggers only see later
+-- writes, so existing links are checked against their programs
+-- explicitly. Foreign key enforcement must be off while this runs, as
+-- it is by default.
+
+CREATE TABLE bpf_links_new (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id BETWEEN 0 AND 4294967295),
+    program_id BIGINT NOT NULL REFERENCES bpf_programs(id) ON DELETE CASCADE
+        CHECK (program_id BETWEEN 0 AND 4294967295),
+    link_type TEXT
+        CHECK(link_type IN ('xdp', 'tc', 'tcx', 'tracepoint', 'kprobe', 'uprobe', 'fentry', 'fexit')),
+    target TEXT,
+    state TEXT NOT NULL
+        CHECK(state IN ('pre_attach', 'attached')),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO bpf_links_new SELECT
+    id, program_id, link_type, target, state, created_at, updated_at
+FROM bpf_links;
+
+DROP TABLE bpf_links;
+ALTER TABLE bpf_links_new RENAME TO bpf_links;
+
+CREATE TEMP TABLE link_type_guard (id INTEGER);
+CREATE TEMP TRIGGER link_type_guard_abort
+BEFORE INSERT ON link_type_guard
+BEGIN
+  SELECT RAISE(ABORT, 'link type does not match the kind of its program');
+END;
+INSERT INTO link_type_guard
+SELECT id FROM bpf_links
+WHERE link_type IS NOT NULL AND NOT EXISTS (
+  SELECT 1 FROM bpf_programs
+  WHERE bpf_programs.id = bpf_links.program_id AND kind = bpf_links.link_type
+);
+DROP TABLE link_type_guard;
+
+CREATE TABLE bpf_maps_new (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id BETWEEN 0 AND 4294967295),
+    name TEXT NOT NULL,
+    map_type TEXT
+        CHECK(map_type IN (
+            'hash', 'array', 'program_array', 'perf_event_array',
+            'per_cpu_hash', 'per_cpu_array', 'stack_trace', 'cgroup_array',
+            'lru_hash', 'lru_per_cpu_hash', 'lpm_trie', 'array_of_maps',
+            'hash_of_maps', 'dev_map', 'sock_map', 'cpu_map', 'xsk_map',
+            'sock_hash', 'cgroup_storage', 'reuseport_sock_array',
+            'per_cpu_cgroup_storage', 'queue', 'stack', 'sk_storage',
+            'dev_map_hash', 'struct_ops', 'ring_buf', 'inode_storage',
+            'task_storage', 'bloom_filter', 'user_ring_buf', 'cgrp_storage',
+            'arena'
+        )),
+    key_size INTEGER,
+    value_size INTEGER,
+    max_entries INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO bpf_maps_new SELECT
+    id, name, map_type, key_size, value_size, max_entries, created_at,
+    updated_at
+FROM bpf_maps;
+
+DROP TABLE bpf_maps;
+ALTER TABLE bpf_maps_new RENAME TO bpf_maps;
+
+-- Dropping the tables dropped their triggers.
+CREATE TRIGGER update_bpf_links_updated_at
+AFTER UPDATE ON bpf_links
+FOR EACH ROW
+WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE bpf_links
+  SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+  WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER update_bpf_maps_updated_at
+AFTER UPDATE ON bpf_maps
+FOR EACH ROW
+WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE bpf_maps
+  SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+  WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER bpf_links_link_type_insert
+BEFORE INSERT ON bpf_links
+FOR EACH ROW
+WHEN NEW.link_type IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'link type does not match the kind of its program')
+  WHERE NOT EXISTS (
+    SELECT 1 FROM bpf_programs
+    WHERE id = NEW.program_id AND kind = NEW.link_type
+  );
+END;
+
+CREATE TRIGGER bpf_links_link_type_update
+BEFORE UPDATE OF program_id, link_type ON bpf_links
+FOR EACH ROW
+WHEN NEW.link_type IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'link type does not match the kind of its program')
+  WHERE NOT EXISTS (
+    SELECT 1 FROM bpf_programs
+    WHERE id = NEW.program_id AND kind = NEW.link_type
+  );
+END;
+
+CREATE TRIGGER bpf_programs_kind_update
+BEFORE UPDATE OF kind ON bpf_programs
+FOR EACH ROW
+WHEN NEW.kind != OLD.kind
+BEGIN
+  SELECT RAISE(ABORT, 'program kind does not match the type of its links')
+  WHERE EXISTS (
+    SELECT 1 FROM bpf_links
+    WHERE program_id = OLD.id AND link_type IS NOT NULL
+  );
+END;
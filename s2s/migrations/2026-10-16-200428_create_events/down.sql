@@ -0,0 +1,11 @@
+DROP TRIGGER bpf_maps_events_delete;
+DROP TRIGGER bpf_maps_events_update;
+DROP TRIGGER bpf_maps_events_insert;
+DROP TRIGGER bpf_links_events_delete;
+DROP TRIGGER bpf_links_events_update;
+DROP TRIGGER bpf_links_events_insert;
+DROP TRIGGER bpf_programs_events_delete;
+DROP TRIGGER bpf_programs_events_update;
+DROP TRIGGER bpf_programs_events_insert;
+DROP TABLE event_actor;
+DROP TABLE events;
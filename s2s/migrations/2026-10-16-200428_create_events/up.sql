@@ -0,0 +1,289 @@
+-- An append-only history of changes to programs, links and maps.
+--
+-- Triggers on bpf_programs, bpf_links and bpf_maps append an event for
+-- every insert, update and delete, however the change is made. Create
+-- events carry the new row and delete events the old row as JSON
+-- objects; update events carry only the columns that changed, before
+-- and after. created_at and updated_at are left out, so an update that
+-- changes nothing else records no event, and program_bytes is
+-- recorded by its length. An update that changes a program's or
+-- link's state is recorded as a state_change.
+--
+-- Each event records the actor named in event_actor, which holds at
+-- most one row and is written only inside the writing transaction, so
+-- no other connection ever sees it. Events have no foreign keys: they
+-- outlive what they describe, and as they are never deleted their IDs
+-- are never reused and give the order they happened in. Link events
+-- record the link's program so that a program's history includes its
+-- links.
+
+CREATE TABLE events (
+    id INTEGER PRIMARY KEY NOT NULL,
+    occurred_at TIMESTAMP NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now')),
+    actor TEXT,
+    entity_type TEXT NOT NULL
+        CHECK(entity_type IN ('program', 'link', 'map')),
+    entity_id BIGINT NOT NULL,
+    program_id BIGINT,
+    action TEXT NOT NULL
+        CHECK(action IN ('create', 'update', 'state_change', 'delete')),
+    old_values TEXT,
+    new_values TEXT
+);
+
+CREATE INDEX events_entity ON events(entity_type, entity_id);
+CREATE INDEX events_program_id ON events(program_id);
+
+CREATE TRIGGER events_no_update
+BEFORE UPDATE ON events
+BEGIN
+  SELECT RAISE(ABORT, 'events are append-only');
+END;
+
+CREATE TRIGGER events_no_delete
+BEFORE DELETE ON events
+BEGIN
+  SELECT RAISE(ABORT, 'events are append-only');
+END;
+
+CREATE TABLE event_actor (
+    id INTEGER PRIMARY KEY NOT NULL CHECK(id = 1),
+    actor TEXT NOT NULL
+);
+
+CREATE TRIGGER bpf_programs_events_insert
+AFTER INSERT ON bpf_programs
+FOR EACH ROW
+BEGIN
+  INSERT INTO events (actor, entity_type, entity_id, program_id, action, new_values)
+  VALUES (
+    (SELECT actor FROM event_actor), 'program', NEW.id, NEW.id, 'create',
+    json_object(
+      'id', NEW.id,
+      'name', NEW.name,
+      'description', NEW.description,
+      'kind', NEW.kind,
+      'state', NEW.state,
+      'location_type', NEW.location_type,
+      'file_path', NEW.file_path,
+      'image_url', NEW.image_url,
+      'image_pull_policy', NEW.image_pull_policy,
+      'map_pin_path', NEW.map_pin_path,
+      'map_owner_id', NEW.map_owner_id,
+      'program_bytes', length(NEW.program_bytes),
+      'metadata', NEW.metadata,
+      'global_data', NEW.global_data,
+      'retprobe', NEW.retprobe,
+      'fn_name', NEW.fn_name,
+      'kernel_name', NEW.kernel_name,
+      'kernel_program_type', NEW.kernel_program_type,
+      'kernel_loaded_at', NEW.kernel_loaded_at,
+      'kernel_tag', NEW.kernel_tag,
+      'kernel_gpl_compatible', NEW.kernel_gpl_compatible,
+      'kernel_btf_id', NEW.kernel_btf_id,
+      'kernel_bytes_xlated', NEW.kernel_bytes_xlated,
+      'kernel_jited', NEW.kernel_jited,
+      'kernel_bytes_jited', NEW.kernel_bytes_jited,
+      'kernel_verified_insns', NEW.kernel_verified_insns,
+      'kernel_map_ids', NEW.kernel_map_ids,
+      'kernel_bytes_memlock', NEW.kernel_bytes_memlock,
+      'credentials_ref', NEW.credentials_ref
+    )
+  );
+END;
+
+CREATE TRIGGER bpf_programs_events_update
+AFTER UPDATE ON bpf_programs
+FOR EACH ROW
+BEGIN
+  INSERT INTO events (actor, entity_type, entity_id, program_id, action, old_values, new_values)
+  SELECT
+    (SELECT actor FROM event_actor), 'program', NEW.id, NEW.id,
+    CASE WHEN OLD.state IS NOT NEW.state THEN 'state_change' ELSE 'update' END,
+    json_group_object(name, old), json_group_object(name, new)
+  FROM (
+    SELECT 'id' AS name, OLD.id AS old, NEW.id AS new, OLD.id IS NOT NEW.id AS changed
+    UNION ALL SELECT 'name', OLD.name, NEW.name, OLD.name IS NOT NEW.name
+    UNION ALL SELECT 'description', OLD.description, NEW.description, OLD.description IS NOT NEW.description
+    UNION ALL SELECT 'kind', OLD.kind, NEW.kind, OLD.kind IS NOT NEW.kind
+    UNION ALL SELECT 'state', OLD.state, NEW.state, OLD.state IS NOT NEW.state
+    UNION ALL SELECT 'location_type', OLD.location_type, NEW.location_type, OLD.location_type IS NOT NEW.location_type
+    UNION ALL SELECT 'file_path', OLD.file_path, NEW.file_path, OLD.file_path IS NOT NEW.file_path
+    UNION ALL SELECT 'image_url', OLD.image_url, NEW.image_url, OLD.image_url IS NOT NEW.image_url
+    UNION ALL SELECT 'image_pull_policy', OLD.image_pull_policy, NEW.image_pull_policy, OLD.image_pull_policy IS NOT NEW.image_pull_policy
+    UNION ALL SELECT 'map_pin_path', OLD.map_pin_path, NEW.map_pin_path, OLD.map_pin_path IS NOT NEW.map_pin_path
+    UNION ALL SELECT 'map_owner_id', OLD.map_owner_id, NEW.map_owner_id, OLD.map_owner_id IS NOT NEW.map_owner_id
+    UNION ALL SELECT 'program_bytes', length(OLD.program_bytes), length(NEW.program_bytes), OLD.program_bytes IS NOT NEW.program_bytes
+    UNION ALL SELECT 'metadata', OLD.metadata, NEW.metadata, OLD.metadata IS NOT NEW.metadata
+    UNION ALL SELECT 'global_data', OLD.global_data, NEW.global_data, OLD.global_data IS NOT NEW.global_data
+    UNION ALL SELECT 'retprobe', OLD.retprobe, NEW.retprobe, OLD.retprobe IS NOT NEW.retprobe
+    UNION ALL SELECT 'fn_name', OLD.fn_name, NEW.fn_name, OLD.fn_name IS NOT NEW.fn_name
+    UNION ALL SELECT 'kernel_name', OLD.kernel_name, NEW.kernel_name, OLD.kernel_name IS NOT NEW.kernel_name
+    UNION ALL SELECT 'kernel_program_type', OLD.kernel_program_type, NEW.kernel_program_type, OLD.kernel_program_type IS NOT NEW.kernel_program_type
+    UNION ALL SELECT 'kernel_loaded_at', OLD.kernel_loaded_at, NEW.kernel_loaded_at, OLD.kernel_loaded_at IS NOT NEW.kernel_loaded_at
+    UNION ALL SELECT 'kernel_tag', OLD.kernel_tag, NEW.kernel_tag, OLD.kernel_tag IS NOT NEW.kernel_tag
+    UNION ALL SELECT 'kernel_gpl_compatible', OLD.kernel_gpl_compatible, NEW.kernel_gpl_compatible, OLD.kernel_gpl_compatible IS NOT NEW.kernel_gpl_compatible
+    UNION ALL SELECT 'kernel_btf_id', OLD.kernel_btf_id, NEW.kernel_btf_id, OLD.kernel_btf_id IS NOT NEW.kernel_btf_id
+    UNION ALL SELECT 'kernel_bytes_xlated', OLD.kernel_bytes_xlated, NEW.kernel_bytes_xlated, OLD.kernel_bytes_xlated IS NOT NEW.kernel_bytes_xlated
+    UNION ALL SELECT 'kernel_jited', OLD.kernel_jited, NEW.kernel_jited, OLD.kernel_jited IS NOT NEW.kernel_jited
+    UNION ALL SELECT 'kernel_bytes_jited', OLD.kernel_bytes_jited, NEW.kernel_bytes_jited, OLD.kernel_bytes_jited IS NOT NEW.kernel_bytes_jited
+    UNION ALL SELECT 'kernel_verified_insns', OLD.kernel_verified_insns, NEW.kernel_verified_insns, OLD.kernel_verified_insns IS NOT NEW.kernel_verified_insns
+    UNION ALL SELECT 'kernel_map_ids', OLD.kernel_map_ids, NEW.kernel_map_ids, OLD.kernel_map_ids IS NOT NEW.kernel_map_ids
+    UNION ALL SELECT 'kernel_bytes_memlock', OLD.kernel_bytes_memlock, NEW.kernel_bytes_memlock, OLD.kernel_bytes_memlock IS NOT NEW.kernel_bytes_memlock
+    UNION ALL SELECT 'credentials_ref', OLD.credentials_ref, NEW.credentials_ref, OLD.credentials_ref IS NOT NEW.credentials_ref
+  )
+  WHERE changed
+  HAVING count(*) > 0;
+END;
+
+CREATE TRIGGER bpf_programs_events_delete
+AFTER DELETE ON bpf_programs
+FOR EACH ROW
+BEGIN
+  INSERT INTO events (actor, entity_type, entity_id, program_id, action, old_values)
+  VALUES (
+    (SELECT actor FROM event_actor), 'program', OLD.id, OLD.id, 'delete',
+    json_object(
+      'id', OLD.id,
+      'name', OLD.name,
+      'description', OLD.description,
+      'kind', OLD.kind,
+      'state', OLD.state,
+      'location_type', OLD.location_type,
+      'file_path', OLD.file_path,
+      'image_url', OLD.image_url,
+      'image_pull_policy', OLD.image_pull_policy,
+      'map_pin_path', OLD.map_pin_path,
+      'map_owner_id', OLD.map_owner_id,
+      'program_bytes', length(OLD.program_bytes),
+      'metadata', OLD.metadata,
+      'global_data', OLD.global_data,
+      'retprobe', OLD.retprobe,
+      'fn_name', OLD.fn_name,
+      'kernel_name', OLD.kernel_name,
+      'kernel_program_type', OLD.kernel_program_type,
+      'kernel_loaded_at', OLD.kernel_loaded_at,
+      'kernel_tag', OLD.kernel_tag,
+      'kernel_gpl_compatible', OLD.kernel_gpl_compatible,
+      'kernel_btf_id', OLD.kernel_btf_id,
+      'kernel_bytes_xlated', OLD.kernel_bytes_xlated,
+      'kernel_jited', OLD.kernel_jited,
+      'kernel_bytes_jited', OLD.kernel_bytes_jited,
+      'kernel_verified_insns', OLD.kernel_verified_insns,
+      'kernel_map_ids', OLD.kernel_map_ids,
+      'kernel_bytes_memlock', OLD.kernel_bytes_memlock,
+      'credentials_ref', OLD.credentials_ref
+    )
+  );
+END;
+
+CREATE TRIGGER bpf_links_events_insert
+AFTER INSERT ON bpf_links
+FOR EACH ROW
+BEGIN
+  INSERT INTO events (actor, entity_type, entity_id, program_id, action, new_values)
+  VALUES (
+    (SELECT actor FROM event_actor), 'link', NEW.id, NEW.program_id, 'create',
+    json_object(
+      'id', NEW.id,
+      'program_id', NEW.program_id,
+      'link_type', NEW.link_type,
+      'target', NEW.target,
+      'state', NEW.state
+    )
+  );
+END;
+
+CREATE TRIGGER bpf_links_events_update
+AFTER UPDATE ON bpf_links
+FOR EACH ROW
+BEGIN
+  INSERT INTO events (actor, entity_type, entity_id, program_id, action, old_values, new_values)
+  SELECT
+    (SELECT actor FROM event_actor), 'link', NEW.id, NEW.program_id,
+    CASE WHEN OLD.state IS NOT NEW.state THEN 'state_change' ELSE 'update' END,
+    json_group_object(name, old), json_group_object(name, new)
+  FROM (
+    SELECT 'id' AS name, OLD.id AS old, NEW.id AS new, OLD.id IS NOT NEW.id AS changed
+    UNION ALL SELECT 'program_id', OLD.program_id, NEW.program_id, OLD.program_id IS NOT NEW.program_id
+    UNION ALL SELECT 'link_type', OLD.link_type, NEW.link_type, OLD.link_type IS NOT NEW.link_type
+    UNION ALL SELECT 'target', OLD.target, NEW.target, OLD.target IS NOT NEW.target
+    UNION ALL SELECT 'state', OLD.state, NEW.state, OLD.state IS NOT NEW.state
+  )
+  WHERE changed
+  HAVING count(*) > 0;
+END;
+
+CREATE TRIGGER bpf_links_events_delete
+AFTER DELETE ON bpf_links
+FOR EACH ROW
+BEGIN
+  INSERT INTO events (actor, entity_type, entity_id, program_id, action, old_values)
+  VALUES (
+    (SELECT actor FROM event_actor), 'link', OLD.id, OLD.program_id, 'delete',
+    json_object(
+      'id', OLD.id,
+      'program_id', OLD.program_id,
+      'link_type', OLD.link_type,
+      'target', OLD.target,
+      'state', OLD.state
+    )
+  );
+END;
+
+CREATE TRIGGER bpf_maps_events_insert
+AFTER INSERT ON bpf_maps
+FOR EACH ROW
+BEGIN
+  INSERT INTO events (actor, entity_type, entity_id, program_id, action, new_values)
+  VALUES (
+    (SELECT actor FROM event_actor), 'map', NEW.id, NULL, 'create',
+    json_object(
+      'id', NEW.id,
+      'name', NEW.name,
+      'map_type', NEW.map_type,
+      'key_size', NEW.key_size,
+      'value_size', NEW.value_size,
+      'max_entries', NEW.max_entries
+    )
+  );
+END;
+
+CREATE TRIGGER bpf_maps_events_update
+AFTER UPDATE ON bpf_maps
+FOR EACH ROW
+BEGIN
+  INSERT INTO events (actor, entity_type, entity_id, program_id, action, old_values, new_values)
+  SELECT
+    (SELECT actor FROM event_actor), 'map', NEW.id, NULL,
+    'update',
+    json_group_object(name, old), json_group_object(name, new)
+  FROM (
+    SELECT 'id' AS name, OLD.id AS old, NEW.id AS new, OLD.id IS NOT NEW.id AS changed
+    UNION ALL SELECT 'name', OLD.name, NEW.name, OLD.name IS NOT NEW.name
+    UNION ALL SELECT 'map_type', OLD.map_type, NEW.map_type, OLD.map_type IS NOT NEW.map_type
+    UNION ALL SELECT 'key_size', OLD.key_size, NEW.key_size, OLD.key_size IS NOT NEW.key_size
+    UNION ALL SELECT 'value_size', OLD.value_size, NEW.value_size, OLD.value_size IS NOT NEW.value_size
+    UNION ALL SELECT 'max_entries', OLD.max_entries, NEW.max_entries, OLD.max_entries IS NOT NEW.max_entries
+  )
+  WHERE changed
+  HAVING count(*) > 0;
+END;
+
+CREATE TRIGGER bpf_maps_events_delete
+AFTER DELETE ON bpf_maps
+FOR EACH ROW
+BEGIN
+  INSERT INTO events (actor, entity_type, entity_id, program_id, action, old_values)
+  VALUES (
+    (SELECT actor FROM event_actor), 'map', OLD.id, NULL, 'delete',
+    json_object(
+      'id', OLD.id,
+      'name', OLD.name,
+      'map_type', OLD.map_type,
+      'key_size', OLD.key_size,
+      'value_size', OLD.value_size,
+      'max_entries', OLD.max_entries
+    )
+  );
+END;
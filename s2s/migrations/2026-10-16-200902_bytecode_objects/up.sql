@@ -0,0 +1,338 @@
+-- Store program bytecode once per distinct object.
+--
+-- bytecode_objects holds each distinct program_bytes value keyed by
+-- its SHA-256, as lowercase hex, and bpf_programs references it by
+-- that digest instead of holding its own copy. Programs loaded from
+-- the same image share one object. A program with no bytecode, which
+-- was an empty program_bytes, now has a NULL bytecode_sha256.
+--
+-- Objects are not deleted with the programs that reference them;
+-- s2s::models::BytecodeObject::collect_garbage deletes those no
+-- program references.
+--
+-- The digests are computed with sha256_hex(), which s2s registers on
+-- each connection it opens, so this migration must be applied by s2s;
+-- s2s::migrations::run_pending refuses to apply it on any other
+-- connection.
+-- bpf_programs is rebuilt to keep the column in place, which drops
+-- its triggers and indexes; they are recreated unchanged, apart from
+-- the event triggers now recording the digest. Foreign key
+-- enforcement must be off while this runs, as it is by default.
+
+CREATE TABLE bytecode_objects (
+    sha256 TEXT PRIMARY KEY NOT NULL
+        CHECK(length(sha256) = 64 AND sha256 NOT GLOB '*[^0-9a-f]*'),
+    bytes BLOB NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT OR IGNORE INTO bytecode_objects (sha256, bytes)
+SELECT sha256_hex(program_bytes), program_bytes
+FROM bpf_programs
+WHERE length(program_bytes) > 0;
+
+CREATE TABLE bpf_programs_new (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id BETWEEN 0 AND 4294967295),
+    name TEXT NOT NULL,
+    description TEXT,
+    kind TEXT NOT NULL
+        CHECK(kind IN ('xdp', 'tc', 'tcx', 'tracepoint', 'kprobe', 'uprobe', 'fentry', 'fexit')),
+    state TEXT NOT NULL
+        CHECK(state IN ('pre_load', 'loaded')),
+    location_type TEXT NOT NULL
+        CHECK(location_type IN ('file', 'image')),
+    file_path TEXT,
+    image_url TEXT,
+    image_pull_policy TEXT,
+    map_pin_path TEXT NOT NULL,
+    map_owner_id BIGINT CHECK (map_owner_id BETWEEN 0 AND 4294967295),
+    bytecode_sha256 TEXT REFERENCES bytecode_objects(sha256),
+    metadata TEXT NOT NULL DEFAULT '{}',
+    global_data TEXT NOT NULL DEFAULT '{}',
+    retprobe BOOLEAN,
+    fn_name TEXT,
+    kernel_name TEXT,
+    kernel_program_type INTEGER,
+    kernel_loaded_at TEXT,
+    kernel_tag TEXT,
+    kernel_gpl_compatible BOOLEAN,
+    kernel_btf_id BIGINT CHECK (kernel_btf_id BETWEEN 0 AND 4294967295),
+    kernel_bytes_xlated BIGINT CHECK (kernel_bytes_xlated BETWEEN 0 AND 4294967295),
+    kernel_jited BOOLEAN,
+    kernel_bytes_jited BIGINT CHECK (kernel_bytes_jited BETWEEN 0 AND 4294967295),
+    kernel_verified_insns INTEGER,
+    kernel_map_ids TEXT NOT NULL DEFAULT '[]',
+    kernel_bytes_memlock BIGINT CHECK (kernel_bytes_memlock BETWEEN 0 AND 4294967295),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    credentials_ref TEXT,
+    CHECK (
+      (location_type = 'file' AND file_path IS NOT NULL)
+      OR (location_type = 'image' AND image_url IS NOT NULL)
+    ),
+    CHECK (
+      (kind IN ('fentry', 'fexit') AND fn_name IS NOT NULL)
+      OR (kind NOT IN ('fentry', 'fexit'))
+    ),
+    CHECK (
+      (kind IN ('kprobe', 'uprobe') AND retprobe IS NOT NULL)
+      OR (kind NOT IN ('kprobe', 'uprobe'))
+    )
+);
+
+INSERT INTO bpf_programs_new SELECT
+    id,
+    name,
+    description,
+    kind,
+    state,
+    location_type,
+    file_path,
+    image_url,
+    image_pull_policy,
+    map_pin_path,
+    map_owner_id,
+    CASE WHEN length(program_bytes) > 0 THEN sha256_hex(program_bytes) END,
+    metadata,
+    global_data,
+    retprobe,
+    fn_name,
+    kernel_name,
+    kernel_program_type,
+    kernel_loaded_at,
+    kernel_tag,
+    kernel_gpl_compatible,
+    kernel_btf_id,
+    kernel_bytes_xlated,
+    kernel_jited,
+    kernel_bytes_jited,
+    kernel_verified_insns,
+    kernel_map_ids,
+    kernel_bytes_memlock,
+    created_at,
+    updated_at,
+    credentials_ref
+FROM bpf_programs;
+
+-- The bpf_links triggers that read bpf_programs would stop the rename.
+DROP TRIGGER bpf_links_link_type_insert;
+DROP TRIGGER bpf_links_link_type_update;
+
+DROP TABLE bpf_programs;
+ALTER TABLE bpf_programs_new RENAME TO bpf_programs;
+
+CREATE TRIGGER bpf_links_link_type_insert
+BEFORE INSERT ON bpf_links
+FOR EACH ROW
+WHEN NEW.link_type IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'link type does not match the kind of its program')
+  WHERE NOT EXISTS (
+    SELECT 1 FROM bpf_programs
+    WHERE id = NEW.program_id AND kind = NEW.link_type
+  );
+END;
+
+CREATE TRIGGER bpf_links_link_type_update
+BEFORE UPDATE OF program_id, link_type ON bpf_links
+FOR EACH ROW
+WHEN NEW.link_type IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'link type does not match the kind of its program')
+  WHERE NOT EXISTS (
+    SELECT 1 FROM bpf_programs
+    WHERE id = NEW.program_id AND kind = NEW.link_type
+  );
+END;
+
+CREATE INDEX bpf_programs_bytecode_sha256 ON bpf_programs(bytecode_sha256);
+
+CREATE TRIGGER update_bpf_programs_updated_at
+AFTER UPDATE ON bpf_programs
+FOR EACH ROW
+WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE bpf_programs
+  SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+  WHERE id = NEW.id;
+END;
+
+CREATE INDEX bpf_programs_map_owner_id ON bpf_programs(map_owner_id);
+
+CREATE TRIGGER bpf_programs_map_owner_insert
+BEFORE INSERT ON bpf_programs
+FOR EACH ROW
+WHEN NEW.map_owner_id IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'map owner does not exist or does not own its maps')
+  WHERE NOT EXISTS (
+    SELECT 1 FROM bpf_programs
+    WHERE id = NEW.map_owner_id AND map_owner_id IS NULL
+  );
+END;
+
+CREATE TRIGGER bpf_programs_map_owner_update
+BEFORE UPDATE OF map_owner_id ON bpf_programs
+FOR EACH ROW
+WHEN NEW.map_owner_id IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'a program cannot own its own maps via map_owner_id')
+  WHERE NEW.map_owner_id = NEW.id;
+  SELECT RAISE(ABORT, 'map owner does not exist or does not own its maps')
+  WHERE NOT EXISTS (
+    SELECT 1 FROM bpf_programs
+    WHERE id = NEW.map_owner_id AND map_owner_id IS NULL
+  );
+  SELECT RAISE(ABORT, 'program owns maps used by other programs')
+  WHERE EXISTS (
+    SELECT 1 FROM bpf_programs WHERE map_owner_id = OLD.id
+  );
+END;
+
+CREATE TRIGGER bpf_programs_map_owner_delete
+BEFORE DELETE ON bpf_programs
+FOR EACH ROW
+BEGIN
+  SELECT RAISE(ABORT, 'program owns maps used by other programs')
+  WHERE EXISTS (
+    SELECT 1 FROM bpf_programs WHERE map_owner_id = OLD.id
+  );
+END;
+
+CREATE TRIGGER bpf_programs_kind_update
+BEFORE UPDATE OF kind ON bpf_programs
+FOR EACH ROW
+WHEN NEW.kind != OLD.kind
+BEGIN
+  SELECT RAISE(ABORT, 'program kind does not match the type of its links')
+  WHERE EXISTS (
+    SELECT 1 FROM bpf_links
+    WHERE program_id = OLD.id AND link_type IS NOT NULL
+  );
+END;
+
+CREATE TRIGGER bpf_programs_events_insert
+AFTER INSERT ON bpf_programs
+FOR EACH ROW
+BEGIN
+  INSERT INTO events (actor, entity_type, entity_id, program_id, action, new_values)
+  VALUES (
+    (SELECT actor FROM event_actor), 'program', NEW.id, NEW.id, 'create',
+    json_object(
+      'id', NEW.id,
+      'name', NEW.name,
+      'description', NEW.description,
+      'kind', NEW.kind,
+      'state', NEW.state,
+      'location_type', NEW.location_type,
+      'file_path', NEW.file_path,
+      'image_url', NEW.image_url,
+      'image_pull_policy', NEW.image_pull_policy,
+      'map_pin_path', NEW.map_pin_path,
+      'map_owner_id', NEW.map_owner_id,
+      'bytecode_sha256', NEW.bytecode_sha256,
+      'metadata', NEW.metadata,
+      'global_data', NEW.global_data,
+      'retprobe', NEW.retprobe,
+      'fn_name', NEW.fn_name,
+      'kernel_name', NEW.kernel_name,
+      'kernel_program_type', NEW.kernel_program_type,
+      'kernel_loaded_at', NEW.kernel_loaded_at,
+      'kernel_tag', NEW.kernel_tag,
+      'kernel_gpl_compatible', NEW.kernel_gpl_compatible,
+      'kernel_btf_id', NEW.kernel_btf_id,
+      'kernel_bytes_xlated', NEW.kernel_bytes_xlated,
+      'kernel_jited', NEW.kernel_jited,
+      'kernel_bytes_jited', NEW.kernel_bytes_jited,
+      'kernel_verified_insns', NEW.kernel_verified_insns,
+      'kernel_map_ids', NEW.kernel_map_ids,
+      'kernel_bytes_memlock', NEW.kernel_bytes_memlock,
+      'credentials_ref', NEW.credentials_ref
+    )
+  );
+END;
+
+CREATE TRIGGER bpf_programs_events_update
+AFTER UPDATE ON bpf_programs
+FOR EACH ROW
+BEGIN
+  INSERT INTO events (actor, entity_type, entity_id, program_id, action, old_values, new_values)
+  SELECT
+    (SELECT actor FROM event_actor), 'program', NEW.id, NEW.id,
+    CASE WHEN OLD.state IS NOT NEW.state THEN 'state_change' ELSE 'update' END,
+    json_group_object(name, old), json_group_object(name, new)
+  FROM (
+    SELECT 'id' AS name, OLD.id AS old, NEW.id AS new, OLD.id IS NOT NEW.id AS changed
+    UNION ALL SELECT 'name', OLD.name, NEW.name, OLD.name IS NOT NEW.name
+    UNION ALL SELECT 'description', OLD.description, NEW.description, OLD.description IS NOT NEW.description
+    UNION ALL SELECT 'kind', OLD.kind, NEW.kind, OLD.kind IS NOT NEW.kind
+    UNION ALL SELECT 'state', OLD.state, NEW.state, OLD.state IS NOT NEW.state
+    UNION ALL SELECT 'location_type', OLD.location_type, NEW.location_type, OLD.location_type IS NOT NEW.location_type
+    UNION ALL SELECT 'file_path', OLD.file_path, NEW.file_path, OLD.file_path IS NOT NEW.file_path
+    UNION ALL SELECT 'image_url', OLD.image_url, NEW.image_url, OLD.image_url IS NOT NEW.image_url
+    UNION ALL SELECT 'image_pull_policy', OLD.image_pull_policy, NEW.image_pull_policy, OLD.image_pull_policy IS NOT NEW.image_pull_policy
+    UNION ALL SELECT 'map_pin_path', OLD.map_pin_path, NEW.map_pin_path, OLD.map_pin_path IS NOT NEW.map_pin_path
+    UNION ALL SELECT 'map_owner_id', OLD.map_owner_id, NEW.map_owner_id, OLD.map_owner_id IS NOT NEW.map_owner_id
+    UNION ALL SELECT 'bytecode_sha256', OLD.bytecode_sha256, NEW.bytecode_sha256, OLD.bytecode_sha256 IS NOT NEW.bytecode_sha256
+    UNION ALL SELECT 'metadata', OLD.metadata, NEW.metadata, OLD.metadata IS NOT NEW.metadata
+    UNION ALL SELECT 'global_data', OLD.global_data, NEW.global_data, OLD.global_data IS NOT NEW.global_data
+    UNION ALL SELECT 'retprobe', OLD.retprobe, NEW.retprobe, OLD.retprobe IS NOT NEW.retprobe
+    UNION ALL SELECT 'fn_name', OLD.fn_name, NEW.fn_name, OLD.fn_name IS NOT NEW.fn_name
+    UNION ALL SELECT 'kernel_name', OLD.kernel_name, NEW.kernel_name, OLD.kernel_name IS NOT NEW.kernel_name
+    UNION ALL SELECT 'kernel_program_type', OLD.kernel_program_type, NEW.kernel_program_type, OLD.kernel_program_type IS NOT NEW.kernel_program_type
+    UNION ALL SELECT 'kernel_loaded_at', OLD.kernel_loaded_at, NEW.kernel_loaded_at, OLD.kernel_loaded_at IS NOT NEW.kernel_loaded_at
+    UNION ALL SELECT 'kernel_tag', OLD.kernel_tag, NEW.kernel_tag, OLD.kernel_tag IS NOT NEW.kernel_tag
+    UNION ALL SELECT 'kernel_gpl_compatible', OLD.kernel_gpl_compatible, NEW.kernel_gpl_compatible, OLD.kernel_gpl_compatible IS NOT NEW.kernel_gpl_compatible
+    UNION ALL SELECT 'kernel_btf_id', OLD.kernel_btf_id, NEW.kernel_btf_id, OLD.kernel_btf_id IS NOT NEW.kernel_btf_id
+    UNION ALL SELECT 'kernel_bytes_xlated', OLD.kernel_bytes_xlated, NEW.kernel_bytes_xlated, OLD.kernel_bytes_xlated IS NOT NEW.kernel_bytes_xlated
+    UNION ALL SELECT 'kernel_jited', OLD.kernel_jited, NEW.kernel_jited, OLD.kernel_jited IS NOT NEW.kernel_jited
+    UNION ALL SELECT 'kernel_bytes_jited', OLD.kernel_bytes_jited, NEW.kernel_bytes_jited, OLD.kernel_bytes_jited IS NOT NEW.kernel_bytes_jited
+    UNION ALL SELECT 'kernel_verified_insns', OLD.kernel_verified_insns, NEW.kernel_verified_insns, OLD.kernel_verified_insns IS NOT NEW.kernel_verified_insns
+    UNION ALL SELECT 'kernel_map_ids', OLD.kernel_map_ids, NEW.kernel_map_ids, OLD.kernel_map_ids IS NOT NEW.kernel_map_ids
+    UNION ALL SELECT 'kernel_bytes_memlock', OLD.kernel_bytes_memlock, NEW.kernel_bytes_memlock, OLD.kernel_bytes_memlock IS NOT NEW.kernel_bytes_memlock
+    UNION ALL SELECT 'credentials_ref', OLD.credentials_ref, NEW.credentials_ref, OLD.credentials_ref IS NOT NEW.credentials_ref
+  )
+  WHERE changed
+  HAVING count(*) > 0;
+END;
+
+CREATE TRIGGER bpf_programs_events_delete
+AFTER DELETE ON bpf_programs
+FOR EACH ROW
+BEGIN
+  INSERT INTO events (actor, entity_type, entity_id, program_id, action, old_values)
+  VALUES (
+    (SELECT actor FROM event_actor), 'program', OLD.id, OLD.id, 'delete',
+    json_object(
+      'id', OLD.id,
+      'name', OLD.name,
+      'description', OLD.description,
+      'kind', OLD.kind,
+      'state', OLD.state,
+      'location_type', OLD.location_type,
+      'file_path', OLD.file_path,
+      'image_url', OLD.image_url,
+      'image_pull_policy', OLD.image_pull_policy,
+      'map_pin_path', OLD.map_pin_path,
+      'map_owner_id', OLD.map_owner_id,
+      'bytecode_sha256', OLD.bytecode_sha256,
+      'metadata', OLD.metadata,
+      'global_data', OLD.global_data,
+      'retprobe', OLD.retprobe,
+      'fn_name', OLD.fn_name,
+      'kernel_name', OLD.kernel_name,
+      'kernel_program_type', OLD.kernel_program_type,
+      'kernel_loaded_at', OLD.kernel_loaded_at,
+      'kernel_tag', OLD.kernel_tag,
+      'kernel_gpl_compatible', OLD.kernel_gpl_compatible,
+      'kernel_btf_id', OLD.kernel_btf_id,
+      'kernel_bytes_xlated', OLD.kernel_bytes_xlated,
+      'kernel_jited', OLD.kernel_jited,
+      'kernel_bytes_jited', OLD.kernel_bytes_jited,
+      'kernel_verified_insns', OLD.kernel_verified_insns,
+      'kernel_map_ids', OLD.kernel_map_ids,
+      'kernel_bytes_memlock', OLD.kernel_bytes_memlock,
+      'credentials_ref', OLD.credentials_ref
+    )
+  );
+END;
@@ -0,0 +1,2 @@
+DROP TRIGGER bpf_programs_kernel_info_update;
+DROP TRIGGER bpf_programs_kernel_info_insert;
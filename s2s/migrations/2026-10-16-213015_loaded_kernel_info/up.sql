@@ -0,0 +1,56 @@
+-- Tie a program's state to its kernel information.
+--
+-- A loaded program must have a kernel_name, which bpfman records for
+-- every program the kernel loads, and a pre-load program must have
+-- none of the kernel_* columns set. The lifecycle transitions write
+-- the state and those columns together; these triggers reject any
+-- other write that would separate them.
+--
+-- Triggers only see later writes, so existing programs are checked
+-- explicitly, and any that break the rule make the migration fail.
+
+CREATE TEMP TABLE kernel_info_guard (id INTEGER);
+CREATE TEMP TRIGGER kernel_info_guard_abort
+BEFORE INSERT ON kernel_info_guard
+BEGIN
+  SELECT RAISE(ABORT, 'program state does not match its kernel information');
+END;
+INSERT INTO kernel_info_guard
+SELECT id FROM bpf_programs
+WHERE (state = 'loaded' AND kernel_name IS NULL)
+   OR (state = 'pre_load' AND COALESCE(
+     kernel_name, kernel_program_type, kernel_loaded_at, kernel_tag,
+     kernel_gpl_compatible, kernel_btf_id, kernel_bytes_xlated, kernel_jited,
+     kernel_bytes_jited, kernel_verified_insns, kernel_bytes_memlock
+   ) IS NOT NULL);
+DROP TABLE kernel_info_guard;
+
+CREATE TRIGGER bpf_programs_kernel_info_insert
+BEFORE INSERT ON bpf_programs
+FOR EACH ROW
+BEGIN
+  SELECT RAISE(ABORT, 'program state does not match its kernel information')
+  WHERE (NEW.state = 'loaded' AND NEW.kernel_name IS NULL)
+     OR (NEW.state = 'pre_load' AND COALESCE(
+       NEW.kernel_name, NEW.kernel_program_type, NEW.kernel_loaded_at, NEW.kernel_tag,
+       NEW.kernel_gpl_compatible, NEW.kernel_btf_id, NEW.kernel_bytes_xlated, NEW.kernel_jited,
+       NEW.kernel_bytes_jited, NEW.kernel_verified_insns, NEW.kernel_bytes_memlock
+     ) IS NOT NULL);
+END;
+
+CREATE TRIGGER bpf_programs_kernel_info_update
+BEFORE UPDATE OF
+  state, kernel_name, kernel_program_type, kernel_loaded_at, kernel_tag,
+  kernel_gpl_compatible, kernel_btf_id, kernel_bytes_xlated, kernel_jited,
+  kernel_bytes_jited, kernel_verified_insns, kernel_bytes_memlock
+ON bpf_programs
+FOR EACH ROW
+BEGIN
+  SELECT RAISE(ABORT, 'program state does not match its kernel information')
+  WHERE (NEW.state = 'loaded' AND NEW.kernel_name IS NULL)
+     OR (NEW.state = 'pre_load' AND COALESCE(
+       NEW.kernel_name, NEW.kernel_program_type, NEW.kernel_loaded_at, NEW.kernel_tag,
+       NEW.kernel_gpl_compatible, NEW.kernel_btf_id, NEW.kernel_bytes_xlated, NEW.kernel_jited,
+       NEW.kernel_bytes_jited, NEW.kernel_verified_insns, NEW.kernel_bytes_memlock
+     ) IS NOT NULL);
+END;
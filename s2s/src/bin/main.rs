@@ -1,8 +1,266 @@
-use anyhow::Error;
-use s2s::establish_connection;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::{Context, Error, bail, ensure};
+use clap::{Parser, Subcommand};
+use diesel::sqlite::SqliteConnection;
+use s2s::{
+    migrations, models, open_connection, secrets::FileSecretStore, sled_import, snapshot::Snapshot,
+};
+
+#[derive(Parser)]
+#[command(name = "s2s", about = "bpfman sled to SQLite migration tool")]
+struct Cli {
+    /// Path to the SQLite database.
+    #[arg(
+        short,
+        long,
+        env = "DATABASE_URL",
+        default_value = "bpf.db",
+        global = true
+    )]
+    database: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Migrate a bpfman sled database into the SQLite database.
+    SledImport {
+        /// Path to the bpfman sled database (e.g. /var/lib/bpfman/db).
+        sled_path: PathBuf,
+
+        /// Directory in which to store image registry credentials.
+        #[arg(long, env = "S2S_SECRETS_DIR", default_value = "secrets")]
+        secrets_dir: PathBuf,
+    },
+
+    /// Check the database for inconsistencies left by older versions,
+    /// and programs and maps that disagree with their bytecode.
+    Check,
+
+    /// Show the sections, functions, maps and license declared by a
+    /// program's bytecode.
+    Inspect {
+        /// Kernel ID of the program.
+        program_id: u32,
+    },
+
+    /// Write every table to a JSON snapshot, with bytecode
+    /// base64-encoded.
+    Export {
+        /// File to write the snapshot to, instead of standard output.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Load a JSON snapshot written by `export` into an empty
+    /// database.
+    Import {
+        /// Snapshot to load.
+        snapshot: PathBuf,
+    },
+
+    /// Inspect, apply or roll back schema migrations.
+    ///
+    /// The other commands apply pending migrations themselves.
+    Migrate {
+        #[command(subcommand)]
+        command: MigrateCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateCommand {
+    /// List the migrations and whether each is applied.
+    Status,
+
+    /// Apply all pending migrations.
+    Up,
+
+    /// Revert the migrations applied after a version.
+    Down {
+        /// Version to roll back to, e.g. 20250212140102 or
+        /// 2025-02-12-140102_create_initial_schema. It stays applied.
+        #[arg(long)]
+        to: String,
+    },
+
+    /// Revert and reapply the most recently applied migration.
+    Redo,
+}
+
+fn migrate(conn: &mut SqliteConnection, command: MigrateCommand) -> Result<(), Error> {
+    match command {
+        MigrateCommand::Status => {
+            for migration in migrations::status(conn)? {
+                let state = if migration.applied {
+                    "applied"
+                } else {
+                    "pending"
+                };
+                println!("{state:8} {}", migration.name);
+            }
+        }
+        MigrateCommand::Up => {
+            let applied = migrations::run_pending(conn)?;
+            if applied.is_empty() {
+                println!("No pending migrations.");
+            }
+            for name in applied {
+                println!("Applied {name}");
+            }
+        }
+        MigrateCommand::Down { to } => {
+            let reverted = migrations::revert_to(conn, &to)?;
+            if reverted.is_empty() {
+                println!("Nothing to revert.");
+            }
+            for name in reverted {
+                println!("Reverted {name}");
+            }
+        }
+        MigrateCommand::Redo => {
+            let name = migrations::redo(conn)?;
+            println!("Redid {name}");
+        }
+    }
+
+    Ok(())
+}
+
+fn inspect(conn: &mut SqliteConnection, program_id: u32) -> Result<(), Error> {
+    let program = models::BpfProgram::find_record(conn, program_id.into())
+        .with_context(|| format!("finding program {program_id}"))?;
+    let Some(object) = program.inspect_bytecode(conn)? else {
+        bail!("program {program_id} has no bytecode");
+    };
+    let or_dash = |value: Option<u32>| value.map_or("-".to_string(), |v| v.to_string());
+
+    println!("Sections:");
+    for section in &object.sections {
+        let code = if section.executable { " (code)" } else { "" };
+        println!("  {:32} {:>8} bytes{code}", section.name, section.size);
+    }
+    println!("Functions:");
+    for function in &object.functions {
+        let kind = function.kind.map_or("-".to_string(), |k| k.to_string());
+        println!(
+            "  {:32} {:10} {:>6} insns  {}",
+            function.name,
+            kind,
+            function.size / 8,
+            function.section
+        );
+    }
+    println!("Maps:");
+    for map in &object.maps {
+        let map_type = map.map_type.map_or("-".to_string(), |t| t.to_string());
+        println!(
+            "  {:32} {:22} key {:>4} value {:>6} max_entries {:>8}  {}",
+            map.name,
+            map_type,
+            or_dash(map.key_size),
+            or_dash(map.value_size),
+            or_dash(map.max_entries),
+            map.section
+        );
+    }
+    println!("License: {}", object.license.as_deref().unwrap_or("-"));
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+    println!(
+        "BTF: {}, BTF.ext: {}",
+        yes_no(object.btf),
+        yes_no(object.btf_ext)
+    );
+
+    Ok(())
+}
 
 fn main() -> Result<(), Error> {
-    let _ = establish_connection(":memory:")?;
-    println!("connection to SQLite established");
+    let cli = Cli::parse();
+    let mut conn = open_connection(&cli.database)?;
+    if !matches!(cli.command, Command::Migrate { .. }) {
+        for name in migrations::run_pending(&mut conn)? {
+            eprintln!("Applied migration {name}");
+        }
+    }
+
+    match cli.command {
+        Command::SledImport {
+            sled_path,
+            secrets_dir,
+        } => {
+            // sled::open() would happily create an empty database.
+            ensure!(sled_path.exists(), "{} does not exist", sled_path.display());
+            let mut secrets = FileSecretStore::open(&secrets_dir)
+                .with_context(|| format!("opening {}", secrets_dir.display()))?;
+            let report = sled_import::migrate_path(&sled_path, &mut conn, &mut secrets)
+                .with_context(|| format!("migrating {}", sled_path.display()))?;
+            println!("{report}");
+        }
+        Command::Check => {
+            let mismatches = models::check_kernel_maps(&mut conn)?;
+            for mismatch in &mismatches {
+                println!("{mismatch}");
+            }
+            let problems = models::verify_bytecode(&mut conn)?;
+            for problem in &problems {
+                println!("{problem}");
+            }
+            let declarations = models::check_declarations(&mut conn)?;
+            for mismatch in &declarations {
+                println!("{mismatch}");
+            }
+            if !mismatches.is_empty() || !problems.is_empty() || !declarations.is_empty() {
+                bail!(
+                    "{} programs have kernel_map_ids that disagree with bpf_program_maps, \
+                     {} bytecode objects are corrupt or missing, \
+                     {} mismatches with program bytecode",
+                    mismatches.len(),
+                    problems.len(),
+                    declarations.len()
+                );
+            }
+            println!("No inconsistencies found.");
+        }
+        Command::Inspect { program_id } => inspect(&mut conn, program_id)?,
+        Command::Export { output } => {
+            let snapshot = Snapshot::export(&mut conn)?;
+            match output {
+                Some(path) => {
+                    let file = File::create(&path)
+                        .with_context(|| format!("creating {}", path.display()))?;
+                    let mut writer = BufWriter::new(file);
+                    serde_json::to_writer_pretty(&mut writer, &snapshot)?;
+                    writer.write_all(b"\n")?;
+                    writer.flush()?;
+                }
+                None => println!("{}", serde_json::to_string_pretty(&snapshot)?),
+            }
+        }
+        Command::Import { snapshot: path } => {
+            let file = File::open(&path).with_context(|| format!("opening {}", path.display()))?;
+            let snapshot: Snapshot = serde_json::from_reader(BufReader::new(file))
+                .with_context(|| format!("reading {}", path.display()))?;
+            snapshot
+                .import(&mut conn)
+                .with_context(|| format!("importing {}", path.display()))?;
+            println!(
+                "Imported {} programs, {} maps, {} links and {} events.",
+                snapshot.programs.len(),
+                snapshot.maps.len(),
+                snapshot.links.len(),
+                snapshot.events.len()
+            );
+        }
+        Command::Migrate { command } => migrate(&mut conn, command)?,
+    }
+
     Ok(())
 }
@@ -0,0 +1,213 @@
+//! Opening and configuring database connections.
+//!
+//! SQLite enables neither foreign key enforcement nor write-ahead
+//! logging by default, and fails at once with `SQLITE_BUSY` when
+//! another connection holds a lock. Without the first, the schema's
+//! `ON DELETE CASCADE` clauses do nothing, and without the others
+//! bpfman and its agents cannot share a database. [`ConnectionOptions`]
+//! sets all three on every connection it opens.
+
+use std::time::Duration;
+
+use diesel::{connection::SimpleConnection, prelude::*, sqlite::SqliteConnection};
+use thiserror::Error;
+
+use crate::{
+    migrations::{self, MigrationError},
+    models, uintblob,
+};
+
+#[derive(Debug, Error)]
+pub enum ConnectionError {
+    #[error("Database connection error: {0}")]
+    Connection(#[from] diesel::ConnectionError),
+
+    #[error("Migration error: {0}")]
+    Migration(#[from] MigrationError),
+
+    #[error("Connection pool error: {0}")]
+    Pool(#[from] diesel::r2d2::PoolError),
+}
+
+/// Settings applied to each connection as it is opened.
+///
+/// The defaults enable foreign keys and WAL, and wait up to five
+/// seconds for a lock.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use s2s::ConnectionOptions;
+/// let conn = ConnectionOptions::new()
+///     .busy_timeout(Some(Duration::from_secs(30)))
+///     .establish("/var/lib/bpfman/bpf.db")?;
+/// # Ok::<(), s2s::ConnectionError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionOptions {
+    foreign_keys: bool,
+    wal: bool,
+    busy_timeout: Option<Duration>,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        ConnectionOptions {
+            foreign_keys: true,
+            wal: true,
+            busy_timeout: Some(Duration::from_secs(5)),
+        }
+    }
+}
+
+impl ConnectionOptions {
+    /// Returns the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether foreign key constraints, including cascading
+    /// deletes, are enforced.
+    pub fn foreign_keys(mut self, enabled: bool) -> Self {
+        self.foreign_keys = enabled;
+        self
+    }
+
+    /// Sets whether to use write-ahead logging, which lets readers
+    /// proceed while another connection writes. The journal mode is
+    /// stored in the database file, so this has no effect on an
+    /// in-memory database and disabling it reverts a file to the
+    /// default rollback journal.
+    pub fn wal(mut self, enabled: bool) -> Self {
+        self.wal = enabled;
+        self
+    }
+
+    /// Sets how long to wait for another connection's lock before
+    /// failing with "database is locked". `None` fails at once.
+    pub fn busy_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.busy_timeout = timeout;
+        self
+    }
+
+    /// Applies the options to an open connection.
+    ///
+    /// Foreign key enforcement cannot change inside a transaction, so
+    /// this must be called outside one.
+    pub fn apply(&self, conn: &mut SqliteConnection) -> QueryResult<()> {
+        let busy_timeout = self.busy_timeout.map_or(0, |t| t.as_millis());
+        let journal_mode = if self.wal { "WAL" } else { "DELETE" };
+        let foreign_keys = if self.foreign_keys { "ON" } else { "OFF" };
+        conn.batch_execute(&format!(
+            "PRAGMA busy_timeout = {busy_timeout};
+             PRAGMA journal_mode = {journal_mode};
+             PRAGMA foreign_keys = {foreign_keys};"
+        ))
+    }
+
+    /// Registers the SQL functions the crate provides and applies the
+    /// options, as every new connection needs.
+    pub(crate) fn configure(&self, conn: &mut SqliteConnection) -> QueryResult<()> {
+        uintblob::register_functions(conn)?;
+        models::register_functions(conn)?;
+        self.apply(conn)
+    }
+
+    /// Opens a connection and applies the options, without touching
+    /// the schema.
+    pub fn open(&self, database_url: &str) -> Result<SqliteConnection, ConnectionError> {
+        let mut connection = SqliteConnection::establish(database_url)?;
+        self.configure(&mut connection)
+            .map_err(diesel::ConnectionError::CouldntSetupConfiguration)?;
+        Ok(connection)
+    }
+
+    /// Opens a connection, applies the options and applies any pending
+    /// migrations.
+    pub fn establish(&self, database_url: &str) -> Result<SqliteConnection, ConnectionError> {
+        let mut connection = self.open(database_url)?;
+        migrations::run_pending(&mut connection)?;
+        Ok(connection)
+    }
+}
+
+/// Opens a connection with the default [`ConnectionOptions`] without
+/// touching the schema, for managing migrations with [`migrations`].
+pub fn open_connection(database_url: &str) -> Result<SqliteConnection, ConnectionError> {
+    ConnectionOptions::default().open(database_url)
+}
+
+/// Opens a connection with the default [`ConnectionOptions`] and
+/// applies any pending migrations.
+pub fn establish_connection(database_url: &str) -> Result<SqliteConnection, ConnectionError> {
+    ConnectionOptions::default().establish(database_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::sql_types::{BigInt, Text};
+
+    use super::*;
+    use crate::test_utils::TempDatabase;
+
+    #[derive(QueryableByName)]
+    struct Pragmas {
+        #[diesel(sql_type = BigInt)]
+        foreign_keys: i64,
+        #[diesel(sql_type = Text)]
+        journal_mode: String,
+        #[diesel(sql_type = BigInt)]
+        busy_timeout: i64,
+    }
+
+    fn pragmas(conn: &mut SqliteConnection) -> Pragmas {
+        diesel::sql_query(
+            "SELECT f.foreign_keys, j.journal_mode, b.timeout AS busy_timeout \
+             FROM pragma_foreign_keys f, pragma_journal_mode j, pragma_busy_timeout b",
+        )
+        .get_result(conn)
+        .unwrap()
+    }
+
+    #[test]
+    fn test_default_options_are_applied() {
+        let db = TempDatabase::new("connection");
+        let url = db.url();
+
+        let mut conn = establish_connection(url).unwrap();
+        let applied = pragmas(&mut conn);
+        assert_eq!(applied.foreign_keys, 1);
+        assert_eq!(applied.journal_mode, "wal");
+        assert_eq!(applied.busy_timeout, 5000);
+        drop(conn);
+
+        let mut conn = ConnectionOptions::new()
+            .foreign_keys(false)
+            .wal(false)
+            .busy_timeout(None)
+            .open(url)
+            .unwrap();
+        let applied = pragmas(&mut conn);
+        assert_eq!(applied.foreign_keys, 0);
+        assert_eq!(applied.journal_mode, "delete");
+        assert_eq!(applied.busy_timeout, 0);
+    }
+
+    #[test]
+    /// Tests that a second connection waits for the busy timeout
+    /// before reporting a lock held by the first.
+    fn test_busy_timeout_waits_for_lock() {
+        let db = TempDatabase::new("busy");
+        let url = db.url();
+        let mut holder = establish_connection(url).unwrap();
+        let options = ConnectionOptions::new().busy_timeout(Some(Duration::from_millis(200)));
+        let mut waiter = options.open(url).unwrap();
+
+        holder.batch_execute("BEGIN IMMEDIATE").unwrap();
+        let start = std::time::Instant::now();
+        let err = waiter.batch_execute("BEGIN IMMEDIATE").unwrap_err();
+        assert!(err.to_string().contains("database is locked"), "{err}");
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        holder.batch_execute("COMMIT").unwrap();
+        waiter.batch_execute("BEGIN IMMEDIATE; COMMIT").unwrap();
+    }
+}
@@ -0,0 +1,951 @@
+//! Inspection of eBPF ELF objects.
+//!
+//! A program's bytecode is the object file it was loaded from, stored
+//! as opaque bytes. [`ElfObject::parse`] reads what that object
+//! declares: its sections, its functions and the program kinds their
+//! section names imply, its maps, its license, and whether it carries
+//! BTF. That is enough to cross-check the database against the object
+//! without shelling out to `llvm-objdump` or `bpftool`.
+//!
+//! Maps are read from both places compilers put them: the legacy
+//! `maps` section of `struct bpf_map_def` values, and the BTF-defined
+//! `.maps` section, whose attributes are encoded in the BTF types of
+//! its variables.
+
+use object::{Architecture, Object, ObjectSection, ObjectSymbol, SymbolKind};
+use thiserror::Error;
+
+use crate::models::{MapType, ProgramKind};
+
+/// Kernel map names are truncated to this many bytes
+/// (`BPF_OBJ_NAME_LEN` less the terminating NUL).
+pub const KERNEL_NAME_LEN: usize = 15;
+
+#[derive(Debug, Error)]
+pub enum ElfError {
+    #[error("not an ELF object: {0}")]
+    Parse(#[from] object::read::Error),
+
+    #[error("not an eBPF object: architecture is {0}")]
+    Architecture(String),
+
+    #[error("section {section}: {reason}")]
+    Malformed { section: String, reason: String },
+}
+
+/// What an eBPF object declares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfObject {
+    /// Every named section, in file order.
+    pub sections: Vec<Section>,
+
+    /// Every function, ordered by section and then offset. Programs
+    /// are the functions in sections whose name implies a kind;
+    /// subprograms they call live in `.text`.
+    pub functions: Vec<Function>,
+
+    /// The declared maps, legacy ones first, each group in the order
+    /// it is declared in.
+    pub maps: Vec<DeclaredMap>,
+
+    /// The contents of the `license` section, if any.
+    pub license: Option<String>,
+
+    /// Whether the object has a `.BTF` section.
+    pub btf: bool,
+
+    /// Whether the object has a `.BTF.ext` section, with the line and
+    /// CO-RE relocation information.
+    pub btf_ext: bool,
+}
+
+/// A section of an object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+
+    /// Size in bytes.
+    pub size: u64,
+
+    /// Whether the section holds instructions.
+    pub executable: bool,
+}
+
+/// A function defined in an object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+
+    /// The section the function is in, e.g. `xdp` or
+    /// `tracepoint/syscalls/sys_enter_openat`.
+    pub section: String,
+
+    /// The program kind the section name implies, or `None` for
+    /// subprograms and sections bpfman has no kind for.
+    pub kind: Option<ProgramKind>,
+
+    /// Size in bytes; each instruction is eight.
+    pub size: u64,
+}
+
+/// A map declared by an object. Attributes the declaration leaves
+/// out, such as the key of a ring buffer, are `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeclaredMap {
+    pub name: String,
+
+    /// `maps` for a legacy definition, `.maps` for a BTF-defined one.
+    pub section: String,
+
+    /// Map type, or `None` if it is not declared or not one the
+    /// kernel defines.
+    pub map_type: Option<MapType>,
+
+    pub key_size: Option<u32>,
+    pub value_size: Option<u32>,
+    pub max_entries: Option<u32>,
+}
+
+impl ElfObject {
+    /// Parses an eBPF ELF object.
+    pub fn parse(bytes: &[u8]) -> Result<ElfObject, ElfError> {
+        let file = object::File::parse(bytes)?;
+        if file.architecture() != Architecture::Bpf {
+            return Err(ElfError::Architecture(format!("{:?}", file.architecture())));
+        }
+        let little_endian = file.is_little_endian();
+
+        let mut sections = Vec::new();
+        let mut license = None;
+        let mut btf = None;
+        let mut btf_ext = false;
+        for section in file.sections() {
+            let name = section.name()?;
+            if name.is_empty() {
+                continue;
+            }
+            match name {
+                "license" => {
+                    let data = section.data()?;
+                    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+                    license = Some(String::from_utf8_lossy(&data[..end]).into_owned());
+                }
+                ".BTF" => btf = Some(section.data()?),
+                ".BTF.ext" => btf_ext = true,
+                _ => {}
+            }
+            sections.push(Section {
+                name: name.to_string(),
+                size: section.size(),
+                executable: section.kind() == object::SectionKind::Text,
+            });
+        }
+
+        let mut functions = Vec::new();
+        let mut legacy_maps = Vec::new();
+        for symbol in file.symbols() {
+            let Some(index) = symbol.section_index() else {
+                continue;
+            };
+            let section = file.section_by_index(index)?;
+            let section_name = section.name()?;
+            if symbol.kind() == SymbolKind::Text && section.kind() == object::SectionKind::Text {
+                functions.push((
+                    index.0,
+                    symbol.address(),
+                    Function {
+                        name: symbol.name()?.to_string(),
+                        section: section_name.to_string(),
+                        kind: section_kind(section_name),
+                        size: symbol.size(),
+                    },
+                ));
+            } else if is_legacy_maps_section(section_name) && !symbol.name()?.is_empty() {
+                let map = legacy_map(
+                    symbol.name()?,
+                    section_name,
+                    section.data()?,
+                    symbol.address(),
+                    little_endian,
+                )?;
+                legacy_maps.push((index.0, symbol.address(), map));
+            }
+        }
+        functions.sort_by_key(|(index, address, _)| (*index, *address));
+        legacy_maps.sort_by_key(|(index, address, _)| (*index, *address));
+
+        let mut maps: Vec<DeclaredMap> = legacy_maps.into_iter().map(|(_, _, m)| m).collect();
+        if let Some(data) = btf {
+            let btf = Btf::parse(data, little_endian).map_err(|reason| ElfError::Malformed {
+                section: ".BTF".to_string(),
+                reason,
+            })?;
+            maps.extend(btf.maps().map_err(|reason| ElfError::Malformed {
+                section: ".maps".to_string(),
+                reason,
+            })?);
+        }
+
+        Ok(ElfObject {
+            sections,
+            functions: functions.into_iter().map(|(_, _, f)| f).collect(),
+            maps,
+            license,
+            btf: btf.is_some(),
+            btf_ext,
+        })
+    }
+
+    /// Returns the function named `name`.
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+
+    /// Returns the map the kernel would name `name`, which is the
+    /// declared name truncated to [`KERNEL_NAME_LEN`] bytes.
+    pub fn map(&self, name: &str) -> Option<&DeclaredMap> {
+        self.maps.iter().find(|m| m.kernel_name() == name)
+    }
+}
+
+impl Function {
+    /// Returns whether a program of `kind` can be loaded from this
+    /// function. `tc` and `classifier` sections hold both TC and TCX
+    /// programs; functions whose section implies no kind match any.
+    pub fn matches_kind(&self, kind: ProgramKind) -> bool {
+        match self.kind {
+            None => true,
+            Some(ProgramKind::Tc) => matches!(kind, ProgramKind::Tc | ProgramKind::Tcx),
+            Some(declared) => declared == kind,
+        }
+    }
+}
+
+impl DeclaredMap {
+    /// Returns the name the kernel gives the map.
+    pub fn kernel_name(&self) -> &str {
+        let mut end = self.name.len().min(KERNEL_NAME_LEN);
+        while !self.name.is_char_boundary(end) {
+            end -= 1;
+        }
+        &self.name[..end]
+    }
+}
+
+/// Returns the program kind a section name implies, following the
+/// libbpf and aya naming conventions.
+fn section_kind(section: &str) -> Option<ProgramKind> {
+    let prefix = section.split(['/', '.']).next().unwrap_or(section);
+    match prefix {
+        "xdp" => Some(ProgramKind::Xdp),
+        "classifier" => Some(ProgramKind::Tc),
+        "tc" if section == "tc" => Some(ProgramKind::Tc),
+        "tc" | "tcx" => Some(ProgramKind::Tcx),
+        "tracepoint" | "tp" => Some(ProgramKind::Tracepoint),
+        "kprobe" | "kretprobe" => Some(ProgramKind::Kprobe),
+        "uprobe" | "uretprobe" => Some(ProgramKind::Uprobe),
+        "fentry" => Some(ProgramKind::Fentry),
+        "fexit" => Some(ProgramKind::Fexit),
+        _ => None,
+    }
+}
+
+fn is_legacy_maps_section(name: &str) -> bool {
+    name == "maps" || name.starts_with("maps/")
+}
+
+/// Reads the `struct bpf_map_def` at `offset`. Only the first four
+/// fields are read; later ones vary between loaders.
+fn legacy_map(
+    name: &str,
+    section: &str,
+    data: &[u8],
+    offset: u64,
+    little_endian: bool,
+) -> Result<DeclaredMap, ElfError> {
+    let malformed = |reason: String| ElfError::Malformed {
+        section: section.to_string(),
+        reason,
+    };
+    let offset = usize::try_from(offset).map_err(|e| malformed(e.to_string()))?;
+    let mut reader = Reader::new(data, little_endian);
+    reader.pos = offset;
+    let mut field = || {
+        reader
+            .u32()
+            .map_err(|e| malformed(format!("map {name}: {e}")))
+    };
+    Ok(DeclaredMap {
+        name: name.to_string(),
+        section: section.to_string(),
+        map_type: MapType::from_kernel(field()?),
+        key_size: Some(field()?),
+        value_size: Some(field()?),
+        max_entries: Some(field()?),
+    })
+}
+
+/// Reads integers in an object's byte order.
+struct Reader<'data> {
+    data: &'data [u8],
+    pos: usize,
+    little_endian: bool,
+}
+
+impl<'data> Reader<'data> {
+    fn new(data: &'data [u8], little_endian: bool) -> Self {
+        Reader {
+            data,
+            pos: 0,
+            little_endian,
+        }
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let bytes = self
+            .pos
+            .checked_add(N)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| format!("truncated at offset {}", self.pos))?;
+        self.pos += N;
+        Ok(bytes.try_into().expect("slice has N bytes"))
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.bytes()?;
+        Ok(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.bytes()?;
+        Ok(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+}
+
+const BTF_MAGIC: u16 = 0xEB9F;
+
+/// The BTF types a map definition can refer to. Everything else is
+/// kept only as its size, if it has one.
+#[derive(Debug)]
+enum BtfType {
+    Void,
+    Sized(u32),
+    Ptr(u32),
+    Array { elem: u32, nelems: u32 },
+    Struct { size: u32, members: Vec<(u32, u32)> },
+    Alias(u32),
+    Var { name: u32, target: u32 },
+    Datasec { name: u32, vars: Vec<u32> },
+    Other,
+}
+
+/// The type and string sections of a `.BTF` section.
+struct Btf<'data> {
+    types: Vec<BtfType>,
+    strings: &'data [u8],
+}
+
+impl<'data> Btf<'data> {
+    fn parse(data: &'data [u8], little_endian: bool) -> Result<Self, String> {
+        let mut reader = Reader::new(data, little_endian);
+        let magic = reader.u16()?;
+        if magic != BTF_MAGIC {
+            return Err(format!("bad magic {magic:#06x}"));
+        }
+        reader.bytes::<2>()?; // version, flags
+        let header_len = reader.u32()? as usize;
+        let type_off = reader.u32()? as usize;
+        let type_len = reader.u32()? as usize;
+        let str_off = reader.u32()? as usize;
+        let str_len = reader.u32()? as usize;
+
+        let slice = |off: usize, len: usize| {
+            header_len
+                .checked_add(off)
+                .and_then(|start| Some(start..start.checked_add(len)?))
+                .and_then(|range| data.get(range))
+                .ok_or_else(|| "section offsets are out of bounds".to_string())
+        };
+        let strings = slice(str_off, str_len)?;
+        let mut reader = Reader::new(slice(type_off, type_len)?, little_endian);
+
+        let mut types = vec![BtfType::Void];
+        while reader.pos < reader.data.len() {
+            types.push(Self::parse_type(&mut reader)?);
+        }
+        Ok(Btf { types, strings })
+    }
+
+    fn parse_type(reader: &mut Reader<'_>) -> Result<BtfType, String> {
+        let name = reader.u32()?;
+        let info = reader.u32()?;
+        let size_or_type = reader.u32()?;
+        let vlen = info & 0xFFFF;
+        let kind = (info >> 24) & 0x1F;
+
+        let mut skip = |count: u32, words: u32| -> Result<(), String> {
+            for _ in 0..count * words {
+                reader.u32()?;
+            }
+            Ok(())
+        };
+        Ok(match kind {
+            // INT, FLOAT
+            1 | 16 => {
+                if kind == 1 {
+                    skip(1, 1)?;
+                }
+                BtfType::Sized(size_or_type)
+            }
+            // PTR
+            2 => BtfType::Ptr(size_or_type),
+            // ARRAY
+            3 => {
+                let elem = reader.u32()?;
+                reader.u32()?; // index type
+                let nelems = reader.u32()?;
+                BtfType::Array { elem, nelems }
+            }
+            // STRUCT, UNION
+            4 | 5 => {
+                let mut members = Vec::with_capacity(vlen as usize);
+                for _ in 0..vlen {
+                    let name = reader.u32()?;
+                    let target = reader.u32()?;
+                    reader.u32()?; // offset
+                    members.push((name, target));
+                }
+                BtfType::Struct {
+                    size: size_or_type,
+                    members,
+                }
+            }
+            // ENUM, ENUM64
+            6 | 19 => {
+                skip(vlen, if kind == 6 { 2 } else { 3 })?;
+                BtfType::Sized(size_or_type)
+            }
+            // FWD, FUNC
+            7 | 12 => BtfType::Other,
+            // TYPEDEF, VOLATILE, CONST, RESTRICT, TYPE_TAG
+            8..=11 | 18 => BtfType::Alias(size_or_type),
+            // FUNC_PROTO
+            13 => {
+                skip(vlen, 2)?;
+                BtfType::Other
+            }
+            // VAR
+            14 => {
+                skip(1, 1)?; // linkage
+                BtfType::Var {
+                    name,
+                    target: size_or_type,
+                }
+            }
+            // DATASEC
+            15 => {
+                let mut vars = Vec::with_capacity(vlen as usize);
+                for _ in 0..vlen {
+                    vars.push(reader.u32()?);
+                    reader.u32()?; // offset
+                    reader.u32()?; // size
+                }
+                BtfType::Datasec { name, vars }
+            }
+            // DECL_TAG
+            17 => {
+                skip(1, 1)?;
+                BtfType::Other
+            }
+            _ => return Err(format!("unknown BTF kind {kind}")),
+        })
+    }
+
+    fn string(&self, offset: u32) -> Result<&'data str, String> {
+        let bytes = self
+            .strings
+            .get(offset as usize..)
+            .ok_or_else(|| format!("string offset {offset} is out of bounds"))?;
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        std::str::from_utf8(&bytes[..end]).map_err(|e| e.to_string())
+    }
+
+    fn get(&self, id: u32) -> Result<&BtfType, String> {
+        self.types
+            .get(id as usize)
+            .ok_or_else(|| format!("type {id} does not exist"))
+    }
+
+    /// Follows typedefs and qualifiers to the type they name.
+    fn resolve(&self, mut id: u32) -> Result<&BtfType, String> {
+        // A well-formed chain is far shorter than the type table.
+        for _ in 0..self.types.len() {
+            match self.get(id)? {
+                BtfType::Alias(target) => id = *target,
+                other => return Ok(other),
+            }
+        }
+        Err(format!("type {id} is a cycle of aliases"))
+    }
+
+    /// Returns the size in bytes of type `id`.
+    fn size_of(&self, id: u32) -> Result<u32, String> {
+        // Arrays of arrays, outermost first, with their lengths. Like a
+        // chain of aliases, well-formed nesting is far shorter than the
+        // type table.
+        let mut arrays = Vec::new();
+        let mut elem = id;
+        let mut size = None;
+        for _ in 0..self.types.len() {
+            match self.resolve(elem)? {
+                BtfType::Sized(n) | BtfType::Struct { size: n, .. } => size = Some(*n),
+                BtfType::Ptr(_) => size = Some(8),
+                BtfType::Array {
+                    elem: inner,
+                    nelems,
+                } => {
+                    arrays.push((elem, *nelems));
+                    elem = *inner;
+                    continue;
+                }
+                _ => return Err(format!("type {elem} has no size")),
+            }
+            break;
+        }
+        let size = size.ok_or_else(|| format!("type {id} is a cycle"))?;
+        arrays
+            .iter()
+            .rev()
+            .try_fold(size, |size, &(array, nelems)| {
+                size.checked_mul(nelems)
+                    .ok_or_else(|| format!("array type {array} overflows"))
+            })
+    }
+
+    /// Returns the maps defined in the `.maps` data section.
+    ///
+    /// Each is a variable whose type is a struct of pointer members,
+    /// as the `__uint` and `__type` macros generate: `__uint(name, N)`
+    /// is a pointer to an array of `N` ints and `__type(name, T)` is a
+    /// pointer to `T`.
+    fn maps(&self) -> Result<Vec<DeclaredMap>, String> {
+        let mut maps = Vec::new();
+        for ty in &self.types {
+            let BtfType::Datasec { name, vars } = ty else {
+                continue;
+            };
+            if self.string(*name)? != ".maps" {
+                continue;
+            }
+            for &var in vars {
+                let BtfType::Var { name, target } = self.get(var)? else {
+                    return Err(format!("type {var} is not a variable"));
+                };
+                maps.push(self.map(self.string(*name)?, *target)?);
+            }
+        }
+        Ok(maps)
+    }
+
+    fn map(&self, name: &str, definition: u32) -> Result<DeclaredMap, String> {
+        let BtfType::Struct { members, .. } = self.resolve(definition)? else {
+            return Err(format!("map {name} is not a struct"));
+        };
+        let mut map = DeclaredMap {
+            name: name.to_string(),
+            section: ".maps".to_string(),
+            map_type: None,
+            key_size: None,
+            value_size: None,
+            max_entries: None,
+        };
+        for &(member, target) in members {
+            let BtfType::Ptr(pointee) = self.resolve(target)? else {
+                continue;
+            };
+            let uint = || match self.resolve(*pointee)? {
+                BtfType::Array { nelems, .. } => Ok(*nelems),
+                _ => Err(format!(
+                    "map {name}: {} is not an __uint",
+                    self.string(member)?
+                )),
+            };
+            match self.string(member)? {
+                "type" => map.map_type = MapType::from_kernel(uint()?),
+                "key_size" => map.key_size = Some(uint()?),
+                "value_size" => map.value_size = Some(uint()?),
+                "max_entries" => map.max_entries = Some(uint()?),
+                "key" => map.key_size = Some(self.size_of(*pointee)?),
+                "value" => map.value_size = Some(self.size_of(*pointee)?),
+                _ => {}
+            }
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use object::{
+        BinaryFormat, Endianness, SectionKind, SymbolFlags, SymbolScope,
+        write::{Object as WriteObject, Symbol, SymbolSection},
+    };
+
+    use super::*;
+
+    /// `r0 = XDP_PASS; exit`.
+    const XDP_PASS: [u8; 16] = [
+        0xB7, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x95, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00,
+    ];
+
+    /// Encodes a `.BTF` section a type at a time.
+    #[derive(Default)]
+    struct BtfBuilder {
+        types: Vec<u8>,
+        strings: Vec<u8>,
+        count: u32,
+    }
+
+    impl BtfBuilder {
+        fn new() -> Self {
+            BtfBuilder {
+                strings: vec![0],
+                ..Default::default()
+            }
+        }
+
+        fn add(
+            &mut self,
+            name: &str,
+            kind: u32,
+            vlen: u32,
+            size_or_type: u32,
+            extra: &[u32],
+        ) -> u32 {
+            let name = if name.is_empty() { 0 } else { self.name(name) };
+            for word in [name, (kind << 24) | vlen, size_or_type]
+                .iter()
+                .chain(extra)
+            {
+                self.types.extend_from_slice(&word.to_le_bytes());
+            }
+            self.count += 1;
+            self.count
+        }
+
+        fn name(&mut self, name: &str) -> u32 {
+            let offset = self.strings.len() as u32;
+            self.strings.extend_from_slice(name.as_bytes());
+            self.strings.push(0);
+            offset
+        }
+
+        /// Adds `int (*)[n]`, as `__uint` declares.
+        fn uint(&mut self, int: u32, n: u32) -> u32 {
+            let array = self.add("", 3, 0, 0, &[int, int, n]);
+            self.add("", 2, 0, array, &[])
+        }
+
+        fn finish(self) -> Vec<u8> {
+            let mut btf = Vec::new();
+            btf.extend_from_slice(&BTF_MAGIC.to_le_bytes());
+            btf.extend_from_slice(&[1, 0]);
+            for word in [
+                24,
+                0,
+                self.types.len() as u32,
+                self.types.len() as u32,
+                self.strings.len() as u32,
+            ] {
+                btf.extend_from_slice(&word.to_le_bytes());
+            }
+            btf.extend(self.types);
+            btf.extend(self.strings);
+            btf
+        }
+    }
+
+    /// BTF for a ring buffer `events` and a hash map whose name is
+    /// longer than the kernel keeps, both in `.maps`.
+    fn maps_btf() -> Vec<u8> {
+        let mut btf = BtfBuilder::new();
+        let int = btf.add("int", 1, 0, 4, &[0x0100_0020]);
+
+        let ringbuf_type = btf.uint(int, 27);
+        let ringbuf_entries = btf.uint(int, 4096);
+        let (type_name, entries_name) = (btf.name("type"), btf.name("max_entries"));
+        let ringbuf = btf.add(
+            "",
+            4,
+            2,
+            16,
+            &[
+                type_name,
+                ringbuf_type,
+                0,
+                entries_name,
+                ringbuf_entries,
+                64,
+            ],
+        );
+        let events = btf.add("events", 14, 0, ringbuf, &[1]);
+
+        let hash_type = btf.uint(int, 1);
+        let u32_typedef = btf.add("__u32", 8, 0, int, &[]);
+        let key = btf.add("", 2, 0, u32_typedef, &[]);
+        let stats = btf.add("stats", 4, 0, 16, &[]);
+        let const_stats = btf.add("", 10, 0, stats, &[]);
+        let value = btf.add("", 2, 0, const_stats, &[]);
+        let hash_entries = btf.uint(int, 1024);
+        let (key_name, value_name) = (btf.name("key"), btf.name("value"));
+        let hash = btf.add(
+            "",
+            4,
+            4,
+            32,
+            &[
+                type_name,
+                hash_type,
+                0,
+                key_name,
+                key,
+                64,
+                value_name,
+                value,
+                128,
+                entries_name,
+                hash_entries,
+                192,
+            ],
+        );
+        let stats_map = btf.add("xdp_stats_map_long", 14, 0, hash, &[1]);
+
+        btf.add(".maps", 15, 2, 48, &[events, 0, 16, stats_map, 16, 32]);
+
+        // A function, whose prototype's parameters must be skipped.
+        let ctx = btf.name("ctx");
+        let proto = btf.add("", 13, 1, int, &[ctx, key]);
+        btf.add("xdp_pass", 12, 0, proto, &[]);
+        btf.finish()
+    }
+
+    fn add_function(obj: &mut WriteObject<'_>, section: &str, name: &str) {
+        let id = obj.add_section(vec![], section.as_bytes().to_vec(), SectionKind::Text);
+        add_symbol(obj, id, name, &XDP_PASS, SymbolKind::Text);
+    }
+
+    fn add_symbol(
+        obj: &mut WriteObject<'_>,
+        section: object::write::SectionId,
+        name: &str,
+        data: &[u8],
+        kind: SymbolKind,
+    ) {
+        let value = obj.append_section_data(section, data, 8);
+        obj.add_symbol(Symbol {
+            name: name.as_bytes().to_vec(),
+            value,
+            size: data.len() as u64,
+            kind,
+            scope: SymbolScope::Linkage,
+            weak: false,
+            section: SymbolSection::Section(section),
+            flags: SymbolFlags::None,
+        });
+    }
+
+    /// Returns an object with an XDP program `xdp_pass`, a tracepoint
+    /// program, a subprogram, a legacy map `legacy_counts` and the
+    /// maps [`maps_btf`] declares, built as clang would lay it out.
+    pub(crate) fn xdp_object() -> Vec<u8> {
+        let mut obj = WriteObject::new(BinaryFormat::Elf, Architecture::Bpf, Endianness::Little);
+        add_function(&mut obj, "xdp", "xdp_pass");
+        add_function(
+            &mut obj,
+            "tracepoint/syscalls/sys_enter_openat",
+            "trace_open",
+        );
+        add_function(&mut obj, ".text", "helper");
+
+        let maps = obj.add_section(vec![], b"maps".to_vec(), SectionKind::Data);
+        let def: Vec<u8> = [6u32, 4, 16, 5, 0]
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect();
+        add_symbol(&mut obj, maps, "legacy_counts", &def, SymbolKind::Data);
+
+        let btf_maps = obj.add_section(vec![], b".maps".to_vec(), SectionKind::Data);
+        add_symbol(&mut obj, btf_maps, "events", &[0; 16], SymbolKind::Data);
+        add_symbol(
+            &mut obj,
+            btf_maps,
+            "xdp_stats_map_long",
+            &[0; 32],
+            SymbolKind::Data,
+        );
+
+        let license = obj.add_section(vec![], b"license".to_vec(), SectionKind::Data);
+        obj.append_section_data(license, b"GPL\0", 1);
+        let btf = obj.add_section(vec![], b".BTF".to_vec(), SectionKind::Other);
+        obj.append_section_data(btf, &maps_btf(), 4);
+
+        obj.write().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let elf = ElfObject::parse(&xdp_object()).unwrap();
+
+        let section = |name: &str| elf.sections.iter().find(|s| s.name == name).unwrap();
+        assert!(section("xdp").executable);
+        assert_eq!(section("xdp").size, 16);
+        assert!(!section("maps").executable);
+
+        let function = |name: &str, section: &str, kind| Function {
+            name: name.to_string(),
+            section: section.to_string(),
+            kind,
+            size: 16,
+        };
+        assert_eq!(
+            elf.functions,
+            vec![
+                function("xdp_pass", "xdp", Some(ProgramKind::Xdp)),
+                function(
+                    "trace_open",
+                    "tracepoint/syscalls/sys_enter_openat",
+                    Some(ProgramKind::Tracepoint)
+                ),
+                function("helper", ".text", None),
+            ]
+        );
+
+        let map = |name: &str, section: &str, map_type, sizes: [Option<u32>; 3]| DeclaredMap {
+            name: name.to_string(),
+            section: section.to_string(),
+            map_type: Some(map_type),
+            key_size: sizes[0],
+            value_size: sizes[1],
+            max_entries: sizes[2],
+        };
+        assert_eq!(
+            elf.maps,
+            vec![
+                map(
+                    "legacy_counts",
+                    "maps",
+                    MapType::PerCpuArray,
+                    [Some(4), Some(16), Some(5)]
+                ),
+                map(
+                    "events",
+                    ".maps",
+                    MapType::RingBuf,
+                    [None, None, Some(4096)]
+                ),
+                map(
+                    "xdp_stats_map_long",
+                    ".maps",
+                    MapType::Hash,
+                    [Some(4), Some(16), Some(1024)]
+                ),
+            ]
+        );
+        assert_eq!(
+            elf.map("xdp_stats_map_l").unwrap().name,
+            "xdp_stats_map_long"
+        );
+        assert!(elf.map("xdp_stats_map_long").is_none());
+
+        assert_eq!(elf.license.as_deref(), Some("GPL"));
+        assert!(elf.btf);
+        assert!(!elf.btf_ext);
+    }
+
+    #[test]
+    fn test_section_kind() {
+        for (section, kind) in [
+            ("xdp", Some(ProgramKind::Xdp)),
+            ("xdp.frags", Some(ProgramKind::Xdp)),
+            ("classifier", Some(ProgramKind::Tc)),
+            ("tc", Some(ProgramKind::Tc)),
+            ("tc/ingress", Some(ProgramKind::Tcx)),
+            ("tcx/egress", Some(ProgramKind::Tcx)),
+            ("tp/sched/sched_switch", Some(ProgramKind::Tracepoint)),
+            ("kretprobe/do_unlinkat", Some(ProgramKind::Kprobe)),
+            ("uprobe.s/libc.so.6:malloc", Some(ProgramKind::Uprobe)),
+            ("fentry/do_unlinkat", Some(ProgramKind::Fentry)),
+            ("fexit/do_unlinkat", Some(ProgramKind::Fexit)),
+            ("socket", None),
+            ("xdpfoo", None),
+            (".text", None),
+        ] {
+            assert_eq!(section_kind(section), kind, "{section}");
+        }
+
+        let tc = Function {
+            name: "tc_pass".to_string(),
+            section: "classifier".to_string(),
+            kind: Some(ProgramKind::Tc),
+            size: 16,
+        };
+        assert!(tc.matches_kind(ProgramKind::Tc));
+        assert!(tc.matches_kind(ProgramKind::Tcx));
+        assert!(!tc.matches_kind(ProgramKind::Xdp));
+    }
+
+    #[test]
+    fn test_size_of() {
+        let mut btf = BtfBuilder::new();
+        let int = btf.add("int", 1, 0, 4, &[0x0100_0020]);
+        let row = btf.add("", 3, 0, 0, &[int, int, 3]);
+        let matrix = btf.add("", 3, 0, 0, &[row, int, 2]);
+        let huge = btf.add("", 3, 0, 0, &[matrix, int, u32::MAX]);
+        // An array of itself, and one of an array of it.
+        let cycle = btf.add("", 3, 0, 0, &[5, int, 1]);
+        let outer = btf.add("", 3, 0, 0, &[cycle, int, 2]);
+        let bytes = btf.finish();
+        let btf = Btf::parse(&bytes, true).unwrap();
+
+        assert_eq!(btf.size_of(int), Ok(4));
+        assert_eq!(btf.size_of(row), Ok(12));
+        assert_eq!(btf.size_of(matrix), Ok(24));
+        assert_eq!(
+            btf.size_of(huge),
+            Err(format!("array type {huge} overflows"))
+        );
+        assert_eq!(cycle, 5);
+        assert_eq!(btf.size_of(cycle), Err("type 5 is a cycle".to_string()));
+        assert_eq!(btf.size_of(outer), Err("type 6 is a cycle".to_string()));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            ElfObject::parse(&[0x7F, 0x45, 0x4C, 0x46, 0x02]),
+            Err(ElfError::Parse(_))
+        ));
+
+        let obj = WriteObject::new(BinaryFormat::Elf, Architecture::X86_64, Endianness::Little);
+        let err = ElfObject::parse(&obj.write().unwrap()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "not an eBPF object: architecture is X86_64"
+        );
+
+        let mut obj = WriteObject::new(BinaryFormat::Elf, Architecture::Bpf, Endianness::Little);
+        let btf = obj.add_section(vec![], b".BTF".to_vec(), SectionKind::Other);
+        obj.append_section_data(btf, &[0x9F, 0xEB, 1, 0, 24, 0], 4);
+        let err = ElfObject::parse(&obj.write().unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "section .BTF: truncated at offset 4");
+    }
+}
@@ -0,0 +1,601 @@
+//! Schema migrations.
+//!
+//! [`establish_connection`](crate::establish_connection) brings the
+//! schema up to date on every connection. The functions here give
+//! finer control, for the `s2s migrate` subcommands: listing which
+//! migrations are applied, applying them, and rolling back to an
+//! earlier schema. None of them print anything.
+//!
+//! A migration is named after its directory, e.g.
+//! `2025-02-12-140102_create_initial_schema`, and its version is the
+//! timestamp with the dashes removed, `20250212140102`. Functions
+//! taking a version accept either form, or the dashed timestamp alone.
+//!
+//! Each migration runs in its own transaction, so one that fails
+//! leaves the schema as it was before that migration. Foreign key
+//! enforcement is turned off while migrations run: several rebuild a
+//! table, and with enforcement on, dropping the original would first
+//! delete its rows and every row referencing them. It cannot be turned
+//! off inside a transaction, so these functions must not be called
+//! inside one.
+//!
+//! Some migrations call SQL functions that s2s registers on the
+//! connections it opens, such as `sha256_hex`, rather than ones SQLite
+//! provides. Applying them on a connection opened some other way fails
+//! with [`MigrationError::MissingFunction`] before anything changes.
+
+use diesel::{
+    connection::SimpleConnection,
+    dsl::sql,
+    migration::{Migration, MigrationSource},
+    prelude::*,
+    sql_types::Bool,
+    sqlite::{Sqlite, SqliteConnection},
+};
+use diesel_migrations::MigrationHarness;
+use thiserror::Error;
+
+use crate::MIGRATIONS;
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("unknown migration `{0}`")]
+    UnknownVersion(String),
+
+    #[error("migration `{0}` is not applied")]
+    NotApplied(String),
+
+    #[error("no migrations are applied")]
+    NothingApplied,
+
+    #[error(
+        "migration `{migration}` calls the SQL function `{function}`, which s2s registers \
+         on the connections it opens; apply it on one of those"
+    )]
+    MissingFunction {
+        migration: String,
+        function: &'static str,
+    },
+
+    #[error("{0}")]
+    Harness(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for MigrationError {
+    fn from(err: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        MigrationError::Harness(err)
+    }
+}
+
+impl From<diesel::result::Error> for MigrationError {
+    fn from(err: diesel::result::Error) -> Self {
+        MigrationError::Harness(Box::new(err))
+    }
+}
+
+/// A migration known to this build and whether it is applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    /// Migration version, e.g. `20250212140102`.
+    pub version: String,
+
+    /// Migration name, e.g. `2025-02-12-140102_create_initial_schema`.
+    pub name: String,
+
+    /// Whether the migration is applied to the database.
+    pub applied: bool,
+}
+
+type BoxedMigration = Box<dyn Migration<Sqlite>>;
+
+/// Returns every migration known to this build, oldest first.
+fn known_migrations() -> Result<Vec<BoxedMigration>, MigrationError> {
+    let mut migrations = MigrationSource::<Sqlite>::migrations(&MIGRATIONS)?;
+    migrations.sort_by_key(|m| m.name().version().as_owned());
+    Ok(migrations)
+}
+
+/// Returns the applied versions, newest first.
+fn applied_versions(conn: &mut SqliteConnection) -> Result<Vec<String>, MigrationError> {
+    Ok(conn
+        .applied_migrations()?
+        .iter()
+        .map(|v| v.to_string())
+        .collect())
+}
+
+/// The SQL functions registered by s2s that migrations call, with the
+/// version of the migration calling each.
+const REGISTERED_FUNCTIONS: &[(&str, &str)] = &[("20261016200902", "sha256_hex")];
+
+/// Fails if any of `migrations` calls a function in
+/// [`REGISTERED_FUNCTIONS`] that `conn` does not have.
+fn check_functions(
+    conn: &mut SqliteConnection,
+    migrations: &[BoxedMigration],
+) -> Result<(), MigrationError> {
+    for migration in migrations {
+        let version = migration.name().version().to_string();
+        for &(_, function) in REGISTERED_FUNCTIONS.iter().filter(|(v, _)| *v == version) {
+            let registered: bool = diesel::select(sql::<Bool>(&format!(
+                "EXISTS (SELECT 1 FROM pragma_function_list WHERE name = '{function}')"
+            )))
+            .get_result(conn)?;
+            if !registered {
+                return Err(MigrationError::MissingFunction {
+                    migration: migration.name().to_string(),
+                    function,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the version named by `version`, which may be a version, a
+/// dashed timestamp or a full migration name.
+fn normalise_version(version: &str) -> String {
+    version
+        .split('_')
+        .next()
+        .unwrap_or_default()
+        .replace('-', "")
+}
+
+fn find_migration<'a>(
+    migrations: &'a [BoxedMigration],
+    version: &str,
+) -> Result<&'a BoxedMigration, MigrationError> {
+    migrations
+        .iter()
+        .find(|m| m.name().version().to_string() == version)
+        .ok_or_else(|| MigrationError::UnknownVersion(version.to_string()))
+}
+
+/// Runs `f` with foreign key enforcement off, then restores it.
+fn without_foreign_keys<T>(
+    conn: &mut SqliteConnection,
+    f: impl FnOnce(&mut SqliteConnection) -> Result<T, MigrationError>,
+) -> Result<T, MigrationError> {
+    let enabled: bool = diesel::select(sql::<Bool>(
+        "(SELECT foreign_keys FROM pragma_foreign_keys)",
+    ))
+    .get_result(conn)?;
+    if !enabled {
+        return f(conn);
+    }
+
+    conn.batch_execute("PRAGMA foreign_keys = OFF")?;
+    let result = f(conn);
+    conn.batch_execute("PRAGMA foreign_keys = ON")?;
+    result
+}
+
+/// Returns every migration known to this build, oldest first, with
+/// whether it is applied.
+pub fn status(conn: &mut SqliteConnection) -> Result<Vec<MigrationStatus>, MigrationError> {
+    let applied = applied_versions(conn)?;
+    Ok(known_migrations()?
+        .iter()
+        .map(|m| {
+            let version = m.name().version().to_string();
+            MigrationStatus {
+                applied: applied.contains(&version),
+                name: m.name().to_string(),
+                version,
+            }
+        })
+        .collect())
+}
+
+/// Applies every pending migration, oldest first, and returns the
+/// names of those applied.
+///
+/// Fails without applying any if one calls a function s2s registers
+/// that `conn` does not have.
+pub fn run_pending(conn: &mut SqliteConnection) -> Result<Vec<String>, MigrationError> {
+    let pending = conn.pending_migrations(MIGRATIONS)?;
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+    check_functions(conn, &pending)?;
+
+    without_foreign_keys(conn, |conn| {
+        pending
+            .iter()
+            .map(|m| {
+                conn.run_migration(m.as_ref())?;
+                Ok(m.name().to_string())
+            })
+            .collect()
+    })
+}
+
+/// Reverts every migration applied after `version`, newest first,
+/// and returns the names of those reverted. `version` itself stays
+/// applied.
+///
+/// Fails if `version` is unknown or not applied, or if a migration
+/// to revert is not known to this build.
+pub fn revert_to(
+    conn: &mut SqliteConnection,
+    version: &str,
+) -> Result<Vec<String>, MigrationError> {
+    let migrations = known_migrations()?;
+    let target = normalise_version(version);
+    find_migration(&migrations, &target)?;
+
+    let applied = applied_versions(conn)?;
+    if !applied.contains(&target) {
+        return Err(MigrationError::NotApplied(version.to_string()));
+    }
+
+    without_foreign_keys(conn, |conn| {
+        let mut reverted = Vec::new();
+        for version in applied.iter().take_while(|&v| *v > target) {
+            let migration = find_migration(&migrations, version)?;
+            conn.revert_migration(migration.as_ref())?;
+            reverted.push(migration.name().to_string());
+        }
+        Ok(reverted)
+    })
+}
+
+/// Reverts and reapplies the most recently applied migration, and
+/// returns its name.
+pub fn redo(conn: &mut SqliteConnection) -> Result<String, MigrationError> {
+    let migrations = known_migrations()?;
+    let applied = applied_versions(conn)?;
+    let last = applied.first().ok_or(MigrationError::NothingApplied)?;
+    let migration = find_migration(&migrations, last)?;
+    check_functions(conn, std::slice::from_ref(migration))?;
+
+    without_foreign_keys(conn, |conn| {
+        conn.revert_migration(migration.as_ref())?;
+        conn.run_migration(migration.as_ref())?;
+        Ok(migration.name().to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{prelude::*, sql_types::Text};
+
+    use super::*;
+    use crate::{
+        models::{BpfLink, BpfProgram, LinkState, LocationType, ProgramKind},
+        open_connection,
+        schema::bpf_links,
+    };
+
+    const INITIAL: &str = "2025-02-12-140102_create_initial_schema";
+    const CREATE_IMAGES: &str = "20261016191512";
+    const EXTERNALIZE_CREDENTIALS: &str = "20261016192030";
+    const UNSIGNED_KERNEL_IDS: &str = "20261016192828";
+    const BYTECODE_OBJECTS: &str = "20261016200902";
+
+    #[derive(QueryableByName, Debug, PartialEq)]
+    struct SchemaObject {
+        #[diesel(sql_type = Text)]
+        kind: String,
+        #[diesel(sql_type = Text)]
+        name: String,
+        #[diesel(sql_type = Text)]
+        sql: String,
+    }
+
+    /// Returns the schema objects other than Diesel's own bookkeeping,
+    /// with whitespace, comments and quoting in their SQL normalised
+    /// away. SQLite quotes the name of a renamed table, so a rebuilt
+    /// table differs from the original only in that.
+    fn schema(conn: &mut SqliteConnection) -> Vec<SchemaObject> {
+        let mut objects: Vec<SchemaObject> = diesel::sql_query(
+            "SELECT type AS kind, name, coalesce(sql, '') AS sql FROM sqlite_schema \
+             WHERE name NOT LIKE '%diesel_schema_migrations%' ORDER BY type, name",
+        )
+        .load(conn)
+        .unwrap();
+        for object in &mut objects {
+            object.sql = object
+                .sql
+                .lines()
+                .map(|line| line.split("--").next().unwrap())
+                .flat_map(str::split_whitespace)
+                .collect::<Vec<_>>()
+                .join(" ")
+                .replace("( ", "(")
+                .replace(" )", ")")
+                .replace('"', "");
+        }
+        objects
+    }
+
+    fn insert_program_and_link(conn: &mut SqliteConnection) {
+        let mut prog = BpfProgram {
+            id: 914.into(),
+            name: "xdp_pass".to_string(),
+            kind: ProgramKind::Xdp,
+            location_type: LocationType::File,
+            file_path: Some("/path/to/prog.o".to_string()),
+            map_pin_path: "/run/bpfman/fs/maps/914".to_string(),
+            ..Default::default()
+        };
+        BpfProgram::create_record(conn, &mut prog).unwrap();
+        let mut link = BpfLink {
+            id: 1.into(),
+            program_id: prog.id,
+            link_type: Some(ProgramKind::Xdp),
+            state: LinkState::Attached,
+            ..Default::default()
+        };
+        BpfLink::create_record(conn, &mut link).unwrap();
+    }
+
+    #[test]
+    fn test_status_and_run_pending() {
+        let mut conn = open_connection(":memory:").unwrap();
+
+        let pending = status(&mut conn).unwrap();
+        assert!(pending.len() > 1);
+        assert!(pending.iter().all(|m| !m.applied));
+        assert_eq!(pending[0].name, INITIAL);
+        assert_eq!(pending[0].version, "20250212140102");
+
+        let applied = run_pending(&mut conn).unwrap();
+        let names: Vec<String> = pending.iter().map(|m| m.name.clone()).collect();
+        assert_eq!(applied, names);
+        assert!(status(&mut conn).unwrap().iter().all(|m| m.applied));
+        assert!(run_pending(&mut conn).unwrap().is_empty());
+    }
+
+    #[test]
+    /// Tests that migrations needing a function s2s registers fail
+    /// clearly, and apply nothing, on a connection without it.
+    fn test_run_pending_without_registered_functions() {
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        let err = run_pending(&mut conn).unwrap_err();
+        assert!(matches!(
+            &err,
+            MigrationError::MissingFunction {
+                function: "sha256_hex",
+                ..
+            }
+        ));
+        assert!(
+            err.to_string().starts_with(
+                "migration `2026-10-16-200902_bytecode_objects` calls the SQL function \
+                 `sha256_hex`"
+            ),
+            "{err}"
+        );
+        assert!(status(&mut conn).unwrap().iter().all(|m| !m.applied));
+    }
+
+    #[test]
+    /// Tests rolling back to an earlier version, which keeps the data
+    /// in tables that survive, and redoing the last migration.
+    fn test_revert_to_and_redo() {
+        let mut conn = open_connection(":memory:").unwrap();
+        assert!(matches!(
+            redo(&mut conn),
+            Err(MigrationError::NothingApplied)
+        ));
+
+        let all = run_pending(&mut conn).unwrap();
+        insert_program_and_link(&mut conn);
+
+        assert!(matches!(
+            revert_to(&mut conn, "20990101000000"),
+            Err(MigrationError::UnknownVersion(_))
+        ));
+
+        let reverted = revert_to(&mut conn, "2026-10-16-192828").unwrap();
+        let position = all
+            .iter()
+            .position(|name| normalise_version(name) == UNSIGNED_KERNEL_IDS)
+            .unwrap();
+        let mut expected = all[position + 1..].to_vec();
+        expected.reverse();
+        assert_eq!(reverted, expected);
+        assert!(
+            revert_to(&mut conn, UNSIGNED_KERNEL_IDS)
+                .unwrap()
+                .is_empty()
+        );
+
+        let applied: Vec<bool> = status(&mut conn)
+            .unwrap()
+            .iter()
+            .map(|m| m.applied)
+            .collect();
+        assert_eq!(applied.iter().filter(|&&a| a).count(), position + 1);
+        assert!(applied[..=position].iter().all(|&a| a));
+
+        let links: i64 = bpf_links::table.count().get_result(&mut conn).unwrap();
+        assert_eq!(links, 1);
+
+        assert_eq!(run_pending(&mut conn).unwrap(), all[position + 1..]);
+        assert_eq!(redo(&mut conn).unwrap(), *all.last().unwrap());
+        assert_eq!(BpfLink::find_all(&mut conn).unwrap().len(), 1);
+
+        revert_to(&mut conn, INITIAL).unwrap();
+        assert!(matches!(
+            revert_to(&mut conn, UNSIGNED_KERNEL_IDS),
+            Err(MigrationError::NotApplied(_))
+        ));
+    }
+
+    #[test]
+    /// Tests that the migration moving credentials out of the database
+    /// refuses to run while a program still has them.
+    fn test_externalize_credentials_keeps_stored_credentials() {
+        let mut conn = open_connection(":memory:").unwrap();
+        run_pending(&mut conn).unwrap();
+        revert_to(&mut conn, CREATE_IMAGES).unwrap();
+        conn.batch_execute(
+            "INSERT INTO bpf_programs (id, name, kind, state, location_type, image_url, \
+             username, password, map_pin_path, program_bytes) VALUES (1, 'xdp_pass', 'xdp', \
+             'pre_load', 'image', 'quay.io/bpfman-bytecode/xdp_pass:latest', 'robot', \
+             'hunter2', '/run/bpfman/fs/maps/1', x'')",
+        )
+        .unwrap();
+
+        let err = run_pending(&mut conn).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("bpf_programs has registry credentials"),
+            "{err}"
+        );
+        assert_eq!(applied_versions(&mut conn).unwrap()[0], CREATE_IMAGES);
+
+        conn.batch_execute("UPDATE bpf_programs SET username = NULL, password = NULL")
+            .unwrap();
+        run_pending(&mut conn).unwrap();
+        assert!(status(&mut conn).unwrap().iter().all(|m| m.applied));
+    }
+
+    #[test]
+    /// Tests that adding the map sharing rules fails on existing
+    /// programs with a missing map owner or a chain of owners.
+    fn test_map_ownership_rejects_invalid_owners() {
+        let mut conn = open_connection(":memory:").unwrap();
+        run_pending(&mut conn).unwrap();
+        revert_to(&mut conn, EXTERNALIZE_CREDENTIALS).unwrap();
+        let insert = |id: u32, owner: &str| {
+            format!(
+                "INSERT INTO bpf_programs (id, name, kind, state, location_type, file_path, \
+                 map_pin_path, program_bytes, map_owner_id) VALUES ({id}, 'xdp_pass', 'xdp', \
+                 'pre_load', 'file', '/path/to/prog.o', '/run/bpfman/fs/maps/{id}', x'', {owner})"
+            )
+        };
+        let assert_rejected = |conn: &mut SqliteConnection| {
+            let err = run_pending(conn).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("map owner does not exist or does not own its maps"),
+                "{err}"
+            );
+            assert_eq!(applied_versions(conn).unwrap()[0], EXTERNALIZE_CREDENTIALS);
+        };
+
+        conn.batch_execute(&insert(1, "NULL")).unwrap();
+        conn.batch_execute(&insert(2, "99")).unwrap();
+        assert_rejected(&mut conn);
+
+        // 3 uses the maps of 2, which uses those of 1.
+        conn.batch_execute("UPDATE bpf_programs SET map_owner_id = 1 WHERE id = 2")
+            .unwrap();
+        conn.batch_execute(&insert(3, "2")).unwrap();
+        assert_rejected(&mut conn);
+
+        conn.batch_execute("UPDATE bpf_programs SET map_owner_id = 1 WHERE id = 3")
+            .unwrap();
+        run_pending(&mut conn).unwrap();
+        assert_eq!(BpfProgram::find_all(&mut conn).unwrap().len(), 3);
+    }
+
+    #[test]
+    /// Tests that adding the link type constraint fails on an existing
+    /// link whose type is not its program's kind.
+    fn test_link_and_map_checks_reject_mismatched_links() {
+        let mut conn = open_connection(":memory:").unwrap();
+        run_pending(&mut conn).unwrap();
+        revert_to(&mut conn, UNSIGNED_KERNEL_IDS).unwrap();
+        conn.batch_execute(
+            "INSERT INTO bpf_programs (id, name, kind, state, location_type, file_path, \
+             map_pin_path, program_bytes) VALUES (914, 'xdp_pass', 'xdp', 'pre_load', 'file', \
+             '/path/to/prog.o', '/run/bpfman/fs/maps/914', x''); \
+             INSERT INTO bpf_links (id, program_id, link_type, state) \
+             VALUES (1, 914, 'tc', 'attached')",
+        )
+        .unwrap();
+
+        let err = run_pending(&mut conn).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("link type does not match the kind of its program"),
+            "{err}"
+        );
+        assert_eq!(applied_versions(&mut conn).unwrap()[0], UNSIGNED_KERNEL_IDS);
+
+        conn.batch_execute("UPDATE bpf_links SET link_type = 'xdp'")
+            .unwrap();
+        run_pending(&mut conn).unwrap();
+        assert_eq!(BpfLink::find_all(&mut conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    /// Tests that tying program state to kernel information fails on
+    /// existing programs that break the rule.
+    fn test_loaded_kernel_info_rejects_existing_programs() {
+        let mut conn = open_connection(":memory:").unwrap();
+        run_pending(&mut conn).unwrap();
+        revert_to(&mut conn, BYTECODE_OBJECTS).unwrap();
+        conn.batch_execute(
+            "INSERT INTO bpf_programs (id, name, kind, state, location_type, file_path, \
+             map_pin_path) VALUES (914, 'xdp_pass', 'xdp', 'loaded', 'file', \
+             '/path/to/prog.o', '/run/bpfman/fs/maps/914')",
+        )
+        .unwrap();
+
+        for fix in [
+            "UPDATE bpf_programs SET state = 'pre_load', kernel_tag = 'abc'",
+            "UPDATE bpf_programs SET kernel_tag = NULL",
+        ] {
+            let err = run_pending(&mut conn).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("program state does not match its kernel information"),
+                "{err}"
+            );
+            assert_eq!(applied_versions(&mut conn).unwrap()[0], BYTECODE_OBJECTS);
+            conn.batch_execute(fix).unwrap();
+        }
+        run_pending(&mut conn).unwrap();
+        assert_eq!(BpfProgram::find_all(&mut conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    /// Tests that program, map and link IDs outside the u32 range are
+    /// rejected by every table holding them.
+    fn test_kernel_ids_are_u32() {
+        let mut conn = open_connection(":memory:").unwrap();
+        run_pending(&mut conn).unwrap();
+        insert_program_and_link(&mut conn);
+        conn.batch_execute("INSERT INTO bpf_maps (id, name) VALUES (1, 'counts')")
+            .unwrap();
+
+        for sql in [
+            "INSERT INTO bpf_maps (id, name) VALUES (4294967296, 'counts')",
+            "INSERT INTO bpf_links (id, program_id, state) VALUES (-1, 914, 'pre_attach')",
+            "INSERT INTO bpf_program_maps (program_id, map_id) VALUES (914, 4294967296)",
+        ] {
+            let err = conn.batch_execute(sql).unwrap_err();
+            assert!(
+                err.to_string().contains("CHECK constraint failed"),
+                "{sql}: {err}"
+            );
+        }
+        conn.batch_execute("INSERT INTO bpf_program_maps (program_id, map_id) VALUES (914, 1)")
+            .unwrap();
+    }
+
+    #[test]
+    /// Tests that every down migration restores the schema its up
+    /// migration started from.
+    fn test_down_migrations_restore_schema() {
+        let mut conn = open_connection(":memory:").unwrap();
+        let mut schemas = vec![schema(&mut conn)];
+        while conn.run_next_migration(MIGRATIONS).is_ok() {
+            schemas.push(schema(&mut conn));
+        }
+        assert_eq!(schemas.len(), status(&mut conn).unwrap().len() + 1);
+
+        schemas.pop();
+        while let Some(expected) = schemas.pop() {
+            let version = conn.revert_last_migration(MIGRATIONS).unwrap();
+            assert_eq!(schema(&mut conn), expected, "reverting {version}");
+        }
+        assert!(applied_versions(&mut conn).unwrap().is_empty());
+    }
+}
@@ -1,13 +1,49 @@
 use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 
+mod attachments;
+mod base64_serde;
+mod bytecode;
+mod credentials;
+mod declarations;
+mod dispatchers;
+mod events;
+mod ids;
+mod images;
+mod kernel_maps;
+mod lifecycle;
+mod map_sharing;
+mod metadata;
+mod types;
+
+pub use attachments::{
+    Attachment, FentryAttachment, FexitAttachment, KprobeAttachment, TcAttachment, TcxAttachment,
+    TracepointAttachment, UprobeAttachment, XdpAttachment,
+};
+pub(crate) use bytecode::register_functions;
+pub use bytecode::{BytecodeObject, BytecodeProblem, verify_bytecode};
+pub use credentials::CredentialsError;
+pub use declarations::{DeclarationMismatch, InspectError, check_declarations};
+pub use dispatchers::{TcDispatcher, XdpDispatcher};
+pub use events::{Event, replay, with_actor};
+pub use ids::{BtfId, ByteCount, LinkId, MapId, NamespaceId, OutOfRangeError, ProgramId};
+pub use images::{Image, ImageError, ImageMap, ImageProgram, MAPS_LABEL, PROGRAMS_LABEL};
+pub use kernel_maps::{KernelMapMismatch, check_kernel_maps};
+pub use lifecycle::{KernelInfo, TransitionError};
+pub use map_sharing::MapSharing;
+pub use metadata::{PROGRAM_NAME_METADATA_KEY, UUID_METADATA_KEY};
+pub use types::{
+    Direction, EntityType, EventAction, InvalidVariantError, LinkState, LocationType, MapType,
+    ProgramKind, ProgramState,
+};
+
 #[derive(
     Debug,
+    Clone,
     PartialEq,
     Eq,
     serde::Serialize,
     serde::Deserialize,
-    AsChangeset,
     Insertable,
     Identifiable,
     Selectable,
@@ -17,7 +53,7 @@ use diesel::prelude::*;
 #[diesel(table_name = crate::schema::bpf_programs)]
 pub struct BpfProgram {
     /// Kernel's BPF program ID (alias for rowid).
-    pub id: i64,
+    pub id: ProgramId,
 
     /// Program name (NOT NULL).
     pub name: String,
@@ -25,15 +61,14 @@ pub struct BpfProgram {
     /// Optional program description.
     pub description: Option<String>,
 
-    /// Program type discriminator in lowercase.
-    /// Allowed values: "xdp", "tc", "tcx", "tracepoint", "kprobe", "uprobe", "fentry", "fexit".
-    pub kind: String,
+    /// Program type discriminator.
+    pub kind: ProgramKind,
 
-    /// Program state: "pre_load" or "loaded"
-    pub state: String,
+    /// Program state: pre-load or loaded.
+    pub state: ProgramState,
 
-    /// Location type: either "file" or "image"
-    pub location_type: String,
+    /// Location type: either file or image.
+    pub location_type: LocationType,
 
     /// For file-based programs; required when location_type = "file"
     pub file_path: Option<String>,
@@ -44,21 +79,16 @@ pub struct BpfProgram {
     /// Image pull policy (optional)
     pub image_pull_policy: Option<String>,
 
-    /// Optional username for image-based authentication.
-    pub username: Option<String>,
-
-    /// Optional password for image-based authentication.
-    pub password: Option<String>,
-
     /// Map pin path (NOT NULL)
     pub map_pin_path: String,
 
     /// Optional map owner ID.
-    pub map_owner_id: Option<i32>,
+    pub map_owner_id: Option<ProgramId>,
 
-    /// The program binary; NOT NULL.
-    #[diesel(sql_type = diesel::sql_types::Binary)]
-    pub program_bytes: Vec<u8>,
+    /// SHA-256 of the program's bytecode, the key of its
+    /// [`BytecodeObject`], or `None` if it has none. Set with
+    /// [`BpfProgram::set_bytecode`].
+    pub bytecode_sha256: Option<String>,
 
     /// Arbitrary metadata as a JSON string, defaults to {}.
     pub metadata: String,
@@ -88,66 +118,212 @@ pub struct BpfProgram {
     pub kernel_gpl_compatible: Option<bool>,
 
     /// Kernel BTF ID.
-    pub kernel_btf_id: Option<i32>,
+    pub kernel_btf_id: Option<BtfId>,
 
     /// Size (in bytes) of the translated program.
-    pub kernel_bytes_xlated: Option<i32>,
+    pub kernel_bytes_xlated: Option<ByteCount>,
 
     /// Whether the program was JIT compiled.
     pub kernel_jited: Option<bool>,
 
     /// Size (in bytes) of the JIT compiled program.
-    pub kernel_bytes_jited: Option<i32>,
+    pub kernel_bytes_jited: Option<ByteCount>,
 
     /// Number of verified instructions.
     pub kernel_verified_insns: Option<i32>,
 
-    /// Kernel map IDs as a JSON array string, defaults to [].
+    /// Kernel map IDs as a JSON array string, defaults to []. Kept in
+    /// step with `bpf_program_maps`, which is authoritative, by
+    /// [`BpfProgram::record_kernel_maps`].
     pub kernel_map_ids: String,
 
     /// Kernel allocated memory (in bytes).
-    pub kernel_bytes_memlock: Option<i32>,
+    pub kernel_bytes_memlock: Option<ByteCount>,
 
     /// Timestamp when the record was created.
     pub created_at: NaiveDateTime,
 
     /// Timestamp when the record was last updated.
     pub updated_at: NaiveDateTime,
+
+    /// Key of the image registry credentials in the
+    /// [`SecretStore`](crate::secrets::SecretStore), if any.
+    pub credentials_ref: Option<String>,
 }
 
-#[derive(Debug, AsChangeset, Insertable, Identifiable, Selectable, Queryable)]
+/// The columns [`BpfProgram::update_record`] writes: all but the ID,
+/// `state` and the other `kernel_*` columns, which only the lifecycle
+/// transitions write, and `kernel_map_ids`, which only
+/// [`BpfProgram::record_kernel_maps`] writes, so that it stays in step
+/// with `bpf_program_maps`. `None` fields are left as they are.
+#[derive(AsChangeset)]
+#[diesel(table_name = crate::schema::bpf_programs)]
+struct ProgramColumns<'a> {
+    name: &'a str,
+    description: Option<&'a str>,
+    kind: ProgramKind,
+    location_type: LocationType,
+    file_path: Option<&'a str>,
+    image_url: Option<&'a str>,
+    image_pull_policy: Option<&'a str>,
+    map_pin_path: &'a str,
+    map_owner_id: Option<ProgramId>,
+    bytecode_sha256: Option<&'a str>,
+    metadata: &'a str,
+    global_data: &'a str,
+    retprobe: Option<bool>,
+    fn_name: Option<&'a str>,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    credentials_ref: Option<&'a str>,
+}
+
+impl<'a> From<&'a BpfProgram> for ProgramColumns<'a> {
+    fn from(program: &'a BpfProgram) -> Self {
+        ProgramColumns {
+            name: &program.name,
+            description: program.description.as_deref(),
+            kind: program.kind,
+            location_type: program.location_type,
+            file_path: program.file_path.as_deref(),
+            image_url: program.image_url.as_deref(),
+            image_pull_policy: program.image_pull_policy.as_deref(),
+            map_pin_path: &program.map_pin_path,
+            map_owner_id: program.map_owner_id,
+            bytecode_sha256: program.bytecode_sha256.as_deref(),
+            metadata: &program.metadata,
+            global_data: &program.global_data,
+            retprobe: program.retprobe,
+            fn_name: program.fn_name.as_deref(),
+            created_at: program.created_at,
+            updated_at: program.updated_at,
+            credentials_ref: program.credentials_ref.as_deref(),
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+    Associations,
+)]
 #[diesel(belongs_to(BpfProgram, foreign_key = program_id))]
 #[diesel(table_name = crate::schema::bpf_links)]
 pub struct BpfLink {
-    pub id: i64, // PRIMARY KEY
-    pub program_id: i64,
-    pub link_type: Option<String>,
+    /// Link ID (alias for rowid).
+    pub id: LinkId,
+
+    /// The program this link attaches.
+    pub program_id: ProgramId,
+
+    /// Link type, matching the kind of the program it attaches.
+    pub link_type: Option<ProgramKind>,
+
+    /// Human readable attach target, e.g. an interface or function
+    /// name.
     pub target: Option<String>,
-    pub state: String,
+
+    /// Link state: pre-attach or attached.
+    pub state: LinkState,
+
+    /// Timestamp when the record was created.
+    pub created_at: NaiveDateTime,
+
+    /// Timestamp when the record was last updated.
+    pub updated_at: NaiveDateTime,
+}
+
+/// The columns [`BpfLink::update_record`] writes: all but the ID and
+/// `state`, which only the lifecycle transitions write.
+#[derive(AsChangeset)]
+#[diesel(table_name = crate::schema::bpf_links)]
+struct LinkColumns<'a> {
+    program_id: ProgramId,
+    link_type: Option<ProgramKind>,
+    target: Option<&'a str>,
     created_at: NaiveDateTime,
     updated_at: NaiveDateTime,
 }
 
-#[derive(Debug, AsChangeset, Insertable, Identifiable, Selectable, Queryable)]
+impl<'a> From<&'a BpfLink> for LinkColumns<'a> {
+    fn from(link: &'a BpfLink) -> Self {
+        LinkColumns {
+            program_id: link.program_id,
+            link_type: link.link_type,
+            target: link.target.as_deref(),
+            created_at: link.created_at,
+            updated_at: link.updated_at,
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    AsChangeset,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+)]
 #[diesel(table_name = crate::schema::bpf_maps)]
 pub struct BpfMap {
-    pub id: i64, // PRIMARY KEY for Identifiable
+    /// Kernel's BPF map ID (alias for rowid).
+    pub id: MapId,
+
+    /// Map name (NOT NULL).
     pub name: String,
-    pub map_type: Option<String>,
+
+    /// Map type, e.g. `per_cpu_array`.
+    pub map_type: Option<MapType>,
+
+    /// Key size in bytes.
     pub key_size: Option<i32>,
+
+    /// Value size in bytes.
     pub value_size: Option<i32>,
+
+    /// Maximum number of entries.
     pub max_entries: Option<i32>,
-    created_at: NaiveDateTime,
-    updated_at: NaiveDateTime,
+
+    /// Timestamp when the record was created.
+    pub created_at: NaiveDateTime,
+
+    /// Timestamp when the record was last updated.
+    pub updated_at: NaiveDateTime,
 }
 
-#[derive(Debug, Queryable, Selectable, Associations)]
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    Insertable,
+    Queryable,
+    Selectable,
+    Associations,
+)]
 #[diesel(belongs_to(BpfProgram, foreign_key = program_id))]
 #[diesel(belongs_to(BpfMap, foreign_key = map_id))]
 #[diesel(table_name = crate::schema::bpf_program_maps)]
 pub struct BpfProgramMap {
-    pub program_id: i64,
-    pub map_id: i64,
+    pub program_id: ProgramId,
+    pub map_id: MapId,
 }
 
 /// BPF Program database operations.
@@ -162,7 +338,7 @@ pub struct BpfProgramMap {
 /// These functions do not manage database transactions. Transaction
 /// control should be handled at a higher level where operation
 /// grouping and rollback behavior can be determined by business
-/// logic.
+/// logic, such as [`crate::service`].
 ///
 /// # Error Handling
 ///
@@ -194,49 +370,179 @@ impl BpfProgram {
     }
 
     /// Finds a BPF program by its ID.
-    pub fn find_record(conn: &mut SqliteConnection, search_id: i64) -> QueryResult<BpfProgram> {
+    pub fn find_record(
+        conn: &mut SqliteConnection,
+        search_id: ProgramId,
+    ) -> QueryResult<BpfProgram> {
         use crate::schema::bpf_programs::dsl::*;
         bpf_programs.filter(id.eq(search_id)).first(conn)
     }
 
     /// Updates an existing BPF program record. Updates the updated_at
     /// timestamp. Returns the updated record if successful.
+    ///
+    /// This leaves `state` and the `kernel_*` columns as stored; use
+    /// [`BpfProgram::mark_loaded`] and [`BpfProgram::unload`] to change
+    /// them, and [`BpfProgram::record_kernel_maps`] to change
+    /// `kernel_map_ids` alone.
     pub fn update_record(&mut self, conn: &mut SqliteConnection) -> QueryResult<BpfProgram> {
         use crate::schema::bpf_programs::dsl::*;
         self.updated_at = Utc::now().naive_utc();
 
         diesel::update(bpf_programs.filter(id.eq(self.id)))
-            .set(&*self)
+            .set(ProgramColumns::from(&*self))
             .get_result(conn)
     }
 
     /// Deletes a BPF program by its ID. Returns true if a record was
     /// deleted, false if no record matched the ID.
-    pub fn delete_record(conn: &mut SqliteConnection, delete_id: i64) -> QueryResult<bool> {
+    pub fn delete_record(conn: &mut SqliteConnection, delete_id: ProgramId) -> QueryResult<bool> {
         use crate::schema::bpf_programs::dsl::*;
 
         let num_deleted = diesel::delete(bpf_programs.filter(id.eq(delete_id))).execute(conn)?;
 
         Ok(num_deleted > 0)
     }
+
+    /// Returns the links attaching this program.
+    pub fn links(&self, conn: &mut SqliteConnection) -> QueryResult<Vec<BpfLink>> {
+        BpfLink::find_by_program(conn, self.id)
+    }
+
+    /// Returns the maps this program uses.
+    pub fn maps(&self, conn: &mut SqliteConnection) -> QueryResult<Vec<BpfMap>> {
+        BpfMap::find_by_program(conn, self.id)
+    }
 }
 
+/// BPF Map database operations.
+///
+/// As with [`BpfProgram`], these are thin CRUD wrappers that leave
+/// transaction handling to the caller.
 impl BpfMap {
-    pub fn insert(conn: &mut SqliteConnection, mut map: BpfMap) -> QueryResult<BpfMap> {
+    /// Creates a new BPF map record in the database.
+    ///
+    /// Updates created_at and updated_at timestamps before insertion.
+    pub fn create_record(conn: &mut SqliteConnection, map: &mut BpfMap) -> QueryResult<BpfMap> {
         use crate::schema::bpf_maps::dsl::*;
 
         map.created_at = Utc::now().naive_utc();
         map.updated_at = map.created_at;
 
         diesel::insert_into(crate::schema::bpf_maps::table)
-            .values(&map)
+            .values(&*map)
             .returning(bpf_maps::all_columns())
             .get_result(conn)
     }
+
+    /// Returns all BPF maps in the database.
+    pub fn find_all(conn: &mut SqliteConnection) -> QueryResult<Vec<BpfMap>> {
+        use crate::schema::bpf_maps::dsl::*;
+        bpf_maps.load(conn)
+    }
+
+    /// Finds a BPF map by its ID.
+    pub fn find_record(conn: &mut SqliteConnection, search_id: MapId) -> QueryResult<BpfMap> {
+        use crate::schema::bpf_maps::dsl::*;
+        bpf_maps.filter(id.eq(search_id)).first(conn)
+    }
+
+    /// Returns the maps used by a program, ordered by ID.
+    pub fn find_by_program(
+        conn: &mut SqliteConnection,
+        search_program_id: ProgramId,
+    ) -> QueryResult<Vec<BpfMap>> {
+        use crate::schema::{bpf_maps, bpf_program_maps};
+
+        bpf_maps::table
+            .inner_join(bpf_program_maps::table)
+            .filter(bpf_program_maps::program_id.eq(search_program_id))
+            .select(BpfMap::as_select())
+            .order(bpf_maps::id.asc())
+            .load(conn)
+    }
+
+    /// Updates an existing BPF map record. Updates the updated_at
+    /// timestamp. Returns the updated record if successful.
+    pub fn update_record(&mut self, conn: &mut SqliteConnection) -> QueryResult<BpfMap> {
+        use crate::schema::bpf_maps::dsl::*;
+        self.updated_at = Utc::now().naive_utc();
+
+        diesel::update(bpf_maps.filter(id.eq(self.id)))
+            .set(&*self)
+            .get_result(conn)
+    }
+
+    /// Deletes a BPF map by its ID. Returns true if a record was
+    /// deleted, false if no record matched the ID.
+    pub fn delete_record(conn: &mut SqliteConnection, delete_id: MapId) -> QueryResult<bool> {
+        use crate::schema::bpf_maps::dsl::*;
+
+        let num_deleted = diesel::delete(bpf_maps.filter(id.eq(delete_id))).execute(conn)?;
+
+        Ok(num_deleted > 0)
+    }
+
+    /// Returns the programs sharing this map, ordered by ID.
+    pub fn programs(&self, conn: &mut SqliteConnection) -> QueryResult<Vec<BpfProgram>> {
+        use crate::schema::{bpf_program_maps, bpf_programs};
+
+        bpf_programs::table
+            .inner_join(bpf_program_maps::table)
+            .filter(bpf_program_maps::map_id.eq(self.id))
+            .select(BpfProgram::as_select())
+            .order(bpf_programs::id.asc())
+            .load(conn)
+    }
 }
 
+/// Program to map associations.
+impl BpfProgramMap {
+    /// Records that `program_id` uses `map_id`. Recording an existing
+    /// association is not an error.
+    pub fn create_record(
+        conn: &mut SqliteConnection,
+        program_id: ProgramId,
+        map_id: MapId,
+    ) -> QueryResult<BpfProgramMap> {
+        let association = BpfProgramMap { program_id, map_id };
+        diesel::insert_into(crate::schema::bpf_program_maps::table)
+            .values(&association)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        Ok(association)
+    }
+
+    /// Removes the association between `program_id` and `map_id`.
+    /// Returns true if a record was deleted.
+    pub fn delete_record(
+        conn: &mut SqliteConnection,
+        program_id: ProgramId,
+        map_id: MapId,
+    ) -> QueryResult<bool> {
+        use crate::schema::bpf_program_maps::dsl;
+
+        let num_deleted = diesel::delete(
+            dsl::bpf_program_maps
+                .filter(dsl::program_id.eq(program_id))
+                .filter(dsl::map_id.eq(map_id)),
+        )
+        .execute(conn)?;
+
+        Ok(num_deleted > 0)
+    }
+}
+
+/// BPF Link database operations.
+///
+/// As with [`BpfProgram`], these are thin CRUD wrappers that leave
+/// transaction handling to the caller.
 impl BpfLink {
-    pub fn link_insert(conn: &mut SqliteConnection, link: &mut BpfLink) -> QueryResult<BpfLink> {
+    /// Creates a new BPF link record in the database.
+    ///
+    /// Updates created_at and updated_at timestamps before insertion.
+    pub fn create_record(conn: &mut SqliteConnection, link: &mut BpfLink) -> QueryResult<BpfLink> {
         use crate::schema::bpf_links::dsl::*;
 
         link.created_at = Utc::now().naive_utc();
@@ -247,25 +553,74 @@ impl BpfLink {
             .returning(bpf_links::all_columns())
             .get_result(conn)
     }
+
+    /// Returns all BPF links in the database.
+    pub fn find_all(conn: &mut SqliteConnection) -> QueryResult<Vec<BpfLink>> {
+        use crate::schema::bpf_links::dsl::*;
+        bpf_links.load(conn)
+    }
+
+    /// Finds a BPF link by its ID.
+    pub fn find_record(conn: &mut SqliteConnection, search_id: LinkId) -> QueryResult<BpfLink> {
+        use crate::schema::bpf_links::dsl::*;
+        bpf_links.filter(id.eq(search_id)).first(conn)
+    }
+
+    /// Returns the links attaching a program, ordered by ID.
+    pub fn find_by_program(
+        conn: &mut SqliteConnection,
+        search_program_id: ProgramId,
+    ) -> QueryResult<Vec<BpfLink>> {
+        use crate::schema::bpf_links::dsl::*;
+        bpf_links
+            .filter(program_id.eq(search_program_id))
+            .order(id.asc())
+            .load(conn)
+    }
+
+    /// Updates an existing BPF link record. Updates the updated_at
+    /// timestamp. Returns the updated record if successful.
+    ///
+    /// This leaves `state` as stored; use [`BpfLink::mark_attached`]
+    /// and [`BpfLink::detach`] to change it.
+    pub fn update_record(&mut self, conn: &mut SqliteConnection) -> QueryResult<BpfLink> {
+        use crate::schema::bpf_links::dsl::*;
+        self.updated_at = Utc::now().naive_utc();
+
+        diesel::update(bpf_links.filter(id.eq(self.id)))
+            .set(LinkColumns::from(&*self))
+            .get_result(conn)
+    }
+
+    /// Deletes a BPF link by its ID. Returns true if a record was
+    /// deleted, false if no record matched the ID.
+    pub fn delete_record(conn: &mut SqliteConnection, delete_id: LinkId) -> QueryResult<bool> {
+        use crate::schema::bpf_links::dsl::*;
+
+        let num_deleted = diesel::delete(bpf_links.filter(id.eq(delete_id))).execute(conn)?;
+
+        Ok(num_deleted > 0)
+    }
 }
 
+/// An empty program for filling in with struct update syntax. Its
+/// `kind` is [`ProgramKind::Xdp`] only because a field must hold some
+/// kind; callers set `kind` rather than rely on it.
 impl Default for BpfProgram {
     fn default() -> Self {
         Self {
-            id: 0,
+            id: Default::default(),
             name: "".to_string(),
             description: None,
-            kind: "".to_string(),
-            state: "".to_string(),
-            location_type: "".to_string(),
+            kind: ProgramKind::Xdp,
+            state: Default::default(),
+            location_type: Default::default(),
             file_path: None,
             image_url: None,
             image_pull_policy: None,
-            username: None,
-            password: None,
             map_pin_path: "".to_string(),
             map_owner_id: None,
-            program_bytes: vec![],
+            bytecode_sha256: None,
             metadata: "{}".to_string(),
             global_data: "{}".to_string(),
             retprobe: None,
@@ -284,20 +639,7 @@ impl Default for BpfProgram {
             kernel_bytes_memlock: None,
             created_at: Default::default(),
             updated_at: Default::default(),
-        }
-    }
-}
-
-impl Default for BpfLink {
-    fn default() -> Self {
-        Self {
-            id: 0, // Indicates an unsaved record
-            program_id: 0,
-            link_type: None,
-            target: None,
-            state: "".to_string(),
-            created_at: Default::default(),
-            updated_at: Default::default(),
+            credentials_ref: None,
         }
     }
 }
@@ -305,7 +647,7 @@ impl Default for BpfLink {
 impl Default for BpfMap {
     fn default() -> Self {
         Self {
-            id: 0, // Indicates an unsaved record
+            id: Default::default(), // Indicates an unsaved record
             name: "".to_string(),
             map_type: None,
             key_size: None,
@@ -320,7 +662,11 @@ impl Default for BpfMap {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{establish_connection, models::BpfProgram};
+    use crate::{
+        ConnectionOptions, establish_connection,
+        models::BpfProgram,
+        schema::{bpf_links, bpf_program_maps, xdp_attachments},
+    };
 
     fn setup_test_db() -> SqliteConnection {
         let database_url = ":memory:";
@@ -361,16 +707,17 @@ mod tests {
 
         // Setup test program with minimal required fields.
         let mut prog = BpfProgram {
-            id: 100,
+            id: 100.into(),
             name: "xdp_test_program".to_string(),
-            kind: "xdp".to_string(),
-            state: "pre_load".to_string(),
-            location_type: "file".to_string(),
+            kind: ProgramKind::Xdp,
+            state: ProgramState::PreLoad,
+            location_type: LocationType::File,
             file_path: Some("/path/to/test_program.o".to_string()),
             map_pin_path: "/sys/fs/bpf/test_program".to_string(),
-            program_bytes: vec![0xAA, 0xBB, 0xCC],
             ..Default::default()
         };
+        prog.set_bytecode(&mut db_conn, &[0xAA, 0xBB, 0xCC])
+            .unwrap();
 
         // Verify default timestamps are epoch.
         let epoch: NaiveDateTime = Default::default();
@@ -453,20 +800,18 @@ mod tests {
     ///    - Verifies all fields match.
     fn test_bpf_program_serde_roundtrip() {
         let mut prog = BpfProgram {
-            id: 100,
+            id: 100.into(),
             name: "xdp_test_program".to_string(),
             description: Some("Test program description".to_string()),
-            kind: "xdp".to_string(),
-            state: "pre_load".to_string(),
-            location_type: "file".to_string(),
+            kind: ProgramKind::Xdp,
+            state: ProgramState::Loaded,
+            location_type: LocationType::File,
             file_path: Some("/path/to/test_program.o".to_string()),
             image_url: Some("registry.example.com/image:tag".to_string()),
             image_pull_policy: Some("Always".to_string()),
-            username: Some("testuser".to_string()),
-            password: Some("testpass".to_string()),
             map_pin_path: "/sys/fs/bpf/test_program".to_string(),
-            map_owner_id: Some(1234),
-            program_bytes: vec![0xAA, 0xBB, 0xCC],
+            map_owner_id: Some(1234.into()),
+            bytecode_sha256: Some(BytecodeObject::digest(&[0xAA, 0xBB, 0xCC])),
             metadata: "{}".to_string(),
             global_data: "{}".to_string(),
             retprobe: Some(true),
@@ -476,13 +821,14 @@ mod tests {
             kernel_loaded_at: Some("2024-02-18T12:00:00Z".to_string()),
             kernel_tag: Some("abcdef123456".to_string()),
             kernel_gpl_compatible: Some(true),
-            kernel_btf_id: Some(456),
-            kernel_bytes_xlated: Some(1024),
+            kernel_btf_id: Some(456.into()),
+            kernel_bytes_xlated: Some(1024.into()),
             kernel_jited: Some(true),
-            kernel_bytes_jited: Some(2048),
+            kernel_bytes_jited: Some(2048.into()),
             kernel_verified_insns: Some(100),
             kernel_map_ids: "[]".to_string(),
-            kernel_bytes_memlock: Some(4096),
+            kernel_bytes_memlock: Some(4096.into()),
+            credentials_ref: Some("program-100".to_string()),
             ..Default::default()
         };
 
@@ -500,6 +846,10 @@ mod tests {
         {
             let mut db_conn = setup_test_db();
 
+            // The map owner and bytecode object must exist.
+            insert_program(&mut db_conn, 1234);
+            BytecodeObject::store(&mut db_conn, &[0xAA, 0xBB, 0xCC]).unwrap();
+
             let inserted =
                 BpfProgram::create_record(&mut db_conn, &mut prog).expect("Failed to insert");
 
@@ -512,4 +862,385 @@ mod tests {
             assert_eq!(inserted, deserialized_after_db);
         }
     }
+
+    #[test]
+    /// Tests that IDs and sizes above i32::MAX survive the database
+    /// round-trip, and that the schema rejects values outside the u32
+    /// range written behind the model's back.
+    fn test_bpf_program_unsigned_columns() {
+        let mut db_conn = setup_test_db();
+        let owner = insert_program(&mut db_conn, u32::MAX);
+
+        let mut prog = BpfProgram {
+            id: (i32::MAX as u32 + 1).into(),
+            name: "xdp_test_program".to_string(),
+            location_type: LocationType::File,
+            file_path: Some("/path/to/test_program.o".to_string()),
+            map_pin_path: "/sys/fs/bpf/test_program".to_string(),
+            map_owner_id: Some(owner.id),
+            state: ProgramState::Loaded,
+            kernel_name: Some("xdp_test_prog".to_string()),
+            kernel_btf_id: Some(u32::MAX.into()),
+            kernel_bytes_xlated: Some(3_000_000_000.into()),
+            kernel_bytes_jited: Some(4_000_000_000.into()),
+            kernel_bytes_memlock: Some(u32::MAX.into()),
+            ..Default::default()
+        };
+        let inserted = BpfProgram::create_record(&mut db_conn, &mut prog).expect("Insert failed");
+        assert_eq!(
+            BpfProgram::find_record(&mut db_conn, prog.id).unwrap(),
+            inserted
+        );
+        assert_eq!(owner.map_dependants(&mut db_conn).unwrap(), vec![inserted]);
+
+        for sql in [
+            "UPDATE bpf_programs SET kernel_btf_id = -1",
+            "UPDATE bpf_programs SET kernel_bytes_memlock = 4294967296",
+            "UPDATE bpf_programs SET id = -1 WHERE map_owner_id IS NOT NULL",
+        ] {
+            let err = diesel::sql_query(sql).execute(&mut db_conn).unwrap_err();
+            assert!(
+                err.to_string().contains("CHECK constraint failed"),
+                "{sql}: {err}"
+            );
+        }
+    }
+
+    /// Inserts a minimal file-based XDP program with the given ID.
+    fn insert_program(conn: &mut SqliteConnection, id: u32) -> BpfProgram {
+        let mut prog = BpfProgram {
+            id: id.into(),
+            name: format!("xdp_prog_{id}"),
+            kind: ProgramKind::Xdp,
+            location_type: LocationType::File,
+            file_path: Some("/path/to/test_program.o".to_string()),
+            map_pin_path: format!("/sys/fs/bpf/{id}"),
+            ..Default::default()
+        };
+        BpfProgram::create_record(conn, &mut prog).expect("Insert program failed")
+    }
+
+    #[test]
+    /// Tests the insertion, retrieval, update and deletion of BPF
+    /// links.
+    ///
+    /// 1. Link Creation: verifies default timestamps are epoch and are
+    ///    set on insert, after which the inserted record equals the
+    ///    input.
+    /// 2. Record Retrieval: by ID, for all links, and by program.
+    /// 3. Update: changes the target and verifies updated_at moves and
+    ///    the state is left as stored.
+    /// 4. Deletion: verifies delete reports whether a record matched.
+    fn test_bpf_link_crud() {
+        let mut db_conn = setup_test_db();
+        let prog = insert_program(&mut db_conn, 100);
+        let other = insert_program(&mut db_conn, 200);
+
+        let mut link = BpfLink {
+            id: 1.into(),
+            program_id: prog.id,
+            link_type: Some(ProgramKind::Xdp),
+            target: Some("eth0".to_string()),
+            state: LinkState::PreAttach,
+            ..Default::default()
+        };
+
+        let epoch: NaiveDateTime = Default::default();
+        assert_eq!(link.created_at, epoch, "Default created_at should be epoch");
+
+        let inserted = BpfLink::create_record(&mut db_conn, &mut link).expect("Insert failed");
+        assert_ne!(inserted.created_at, epoch);
+        assert_eq!(link, inserted);
+
+        let mut other_link = BpfLink {
+            id: 2.into(),
+            program_id: other.id,
+            state: LinkState::Attached,
+            ..Default::default()
+        };
+        let other_link =
+            BpfLink::create_record(&mut db_conn, &mut other_link).expect("Insert failed");
+
+        assert_eq!(
+            BpfLink::find_record(&mut db_conn, 1.into()).unwrap(),
+            inserted
+        );
+        assert_eq!(
+            BpfLink::find_all(&mut db_conn).unwrap(),
+            vec![inserted, other_link]
+        );
+        assert_eq!(prog.links(&mut db_conn).unwrap(), vec![link]);
+        assert!(
+            BpfLink::find_by_program(&mut db_conn, 999.into())
+                .unwrap()
+                .is_empty()
+        );
+
+        let mut link = BpfLink::find_record(&mut db_conn, 1.into()).unwrap();
+        let created_at = link.created_at;
+        link.target = Some("eth1".to_string());
+        link.state = LinkState::Attached;
+        let updated = link.update_record(&mut db_conn).expect("Update failed");
+        assert_eq!(updated.target.as_deref(), Some("eth1"));
+        assert_eq!(updated.state, LinkState::PreAttach);
+        assert_eq!(updated.created_at, created_at);
+        assert!(updated.updated_at >= created_at);
+
+        assert!(BpfLink::delete_record(&mut db_conn, 1.into()).unwrap());
+        assert!(!BpfLink::delete_record(&mut db_conn, 1.into()).unwrap());
+        assert!(BpfLink::find_record(&mut db_conn, 1.into()).is_err());
+    }
+
+    #[test]
+    /// Tests the insertion, retrieval, update and deletion of BPF
+    /// maps, mirroring `test_bpf_link_crud`.
+    fn test_bpf_map_crud() {
+        let mut db_conn = setup_test_db();
+
+        let mut map = BpfMap {
+            id: 1414.into(),
+            name: "xdp_stats_map".to_string(),
+            map_type: Some(MapType::PerCpuArray),
+            key_size: Some(4),
+            value_size: Some(16),
+            max_entries: Some(5),
+            ..Default::default()
+        };
+
+        let epoch: NaiveDateTime = Default::default();
+        assert_eq!(map.created_at, epoch, "Default created_at should be epoch");
+
+        let inserted = BpfMap::create_record(&mut db_conn, &mut map).expect("Insert failed");
+        assert_ne!(inserted.created_at, epoch);
+        assert_eq!(map, inserted);
+
+        assert_eq!(
+            BpfMap::find_record(&mut db_conn, 1414.into()).unwrap(),
+            inserted
+        );
+        assert_eq!(BpfMap::find_all(&mut db_conn).unwrap(), vec![inserted]);
+
+        map.max_entries = Some(10);
+        let updated = map.update_record(&mut db_conn).expect("Update failed");
+        assert_eq!(updated.max_entries, Some(10));
+        assert_eq!(
+            BpfMap::find_record(&mut db_conn, 1414.into()).unwrap(),
+            updated
+        );
+
+        assert!(BpfMap::delete_record(&mut db_conn, 1414.into()).unwrap());
+        assert!(!BpfMap::delete_record(&mut db_conn, 1414.into()).unwrap());
+        assert!(BpfMap::find_all(&mut db_conn).unwrap().is_empty());
+    }
+
+    #[test]
+    /// Tests that the schema rejects unknown link states and map
+    /// types, and link types that do not match their program's kind,
+    /// and that reverting the migration lifts those constraints.
+    fn test_link_and_map_constraints() {
+        let mut db_conn = setup_test_db();
+        let xdp = insert_program(&mut db_conn, 100);
+        let mut tc = BpfProgram {
+            id: 200.into(),
+            name: "tc_prog".to_string(),
+            kind: ProgramKind::Tc,
+            location_type: LocationType::File,
+            file_path: Some("/path/to/test_program.o".to_string()),
+            map_pin_path: "/sys/fs/bpf/200".to_string(),
+            ..Default::default()
+        };
+        BpfProgram::create_record(&mut db_conn, &mut tc).unwrap();
+
+        let assert_rejected = |conn: &mut SqliteConnection, sql: &str, message: &str| {
+            let err = diesel::sql_query(sql).execute(conn).unwrap_err();
+            assert!(err.to_string().contains(message), "{sql}: {err}");
+        };
+
+        assert_rejected(
+            &mut db_conn,
+            "INSERT INTO bpf_links (id, program_id, state) VALUES (1, 100, 'detached')",
+            "CHECK constraint failed",
+        );
+        assert_rejected(
+            &mut db_conn,
+            "INSERT INTO bpf_links (id, program_id, link_type, state) \
+             VALUES (1, 100, 'socket_filter', 'attached')",
+            "link type does not match the kind of its program",
+        );
+        assert_rejected(
+            &mut db_conn,
+            "INSERT INTO bpf_maps (id, name, map_type) VALUES (1, 'm', 'PerCpuArray')",
+            "CHECK constraint failed",
+        );
+
+        let mut mismatched = BpfLink {
+            id: 1.into(),
+            program_id: xdp.id,
+            link_type: Some(ProgramKind::Tc),
+            ..Default::default()
+        };
+        let err = BpfLink::create_record(&mut db_conn, &mut mismatched).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("link type does not match the kind of its program")
+        );
+
+        // A link of the right type, or with no type, is accepted...
+        mismatched.link_type = Some(ProgramKind::Xdp);
+        let mut link = BpfLink::create_record(&mut db_conn, &mut mismatched).unwrap();
+        let mut untyped = BpfLink {
+            id: 2.into(),
+            program_id: tc.id,
+            ..Default::default()
+        };
+        BpfLink::create_record(&mut db_conn, &mut untyped).unwrap();
+
+        // ...but cannot be moved to a program of another kind...
+        link.program_id = tc.id;
+        let err = link.update_record(&mut db_conn).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("link type does not match the kind of its program")
+        );
+
+        // ...and its program cannot change kind under it.
+        let mut xdp = BpfProgram::find_record(&mut db_conn, xdp.id).unwrap();
+        xdp.kind = ProgramKind::Tcx;
+        let err = xdp.update_record(&mut db_conn).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("program kind does not match the type of its links")
+        );
+        tc.kind = ProgramKind::Tcx;
+        tc.update_record(&mut db_conn).unwrap();
+
+        let mut map = BpfMap {
+            id: 1.into(),
+            name: "untyped".to_string(),
+            ..Default::default()
+        };
+        BpfMap::create_record(&mut db_conn, &mut map).unwrap();
+
+        // The down migration restores the unconstrained tables and
+        // keeps their rows.
+        crate::migrations::revert_to(&mut db_conn, "20261016192828").unwrap();
+        diesel::sql_query(
+            "INSERT INTO bpf_links (id, program_id, link_type, state) \
+             VALUES (3, 100, 'tc', 'detached')",
+        )
+        .execute(&mut db_conn)
+        .unwrap();
+        diesel::sql_query("INSERT INTO bpf_maps (id, name, map_type) VALUES (2, 'm', 'bogus')")
+            .execute(&mut db_conn)
+            .unwrap();
+        let links: i64 = bpf_links::table.count().get_result(&mut db_conn).unwrap();
+        assert_eq!(links, 3);
+
+        // Re-applying it refuses the rows that now violate it.
+        assert!(crate::migrations::run_pending(&mut db_conn).is_err());
+    }
+
+    /// Inserts program 100 using map 10 with link 1, which has XDP
+    /// attachment details, and returns the number of rows left in
+    /// `bpf_links`, `bpf_program_maps` and `xdp_attachments` after
+    /// deleting the program.
+    fn delete_program_with_dependants(db_conn: &mut SqliteConnection) -> (i64, i64, i64) {
+        let prog = insert_program(db_conn, 100);
+        let mut map = BpfMap {
+            id: 10.into(),
+            name: "xdp_stats_map".to_string(),
+            ..Default::default()
+        };
+        BpfMap::create_record(db_conn, &mut map).unwrap();
+        BpfProgramMap::create_record(db_conn, prog.id, map.id).unwrap();
+
+        let mut link = BpfLink {
+            id: 1.into(),
+            program_id: prog.id,
+            link_type: Some(ProgramKind::Xdp),
+            ..Default::default()
+        };
+        BpfLink::create_record(db_conn, &mut link).unwrap();
+        let xdp = XdpAttachment {
+            link_id: link.id,
+            iface: "eth0".to_string(),
+            if_index: None,
+            priority: 50,
+            proceed_on: "[2]".to_string(),
+            current_position: None,
+            attached: false,
+            nsid: None,
+        };
+        XdpAttachment::create_record(db_conn, &xdp).unwrap();
+
+        assert!(BpfProgram::delete_record(db_conn, prog.id).unwrap());
+        (
+            bpf_links::table.count().get_result(db_conn).unwrap(),
+            bpf_program_maps::table.count().get_result(db_conn).unwrap(),
+            xdp_attachments::table.count().get_result(db_conn).unwrap(),
+        )
+    }
+
+    #[test]
+    /// Tests that deleting a program deletes its links, their
+    /// attachment details and its map associations, but not the maps
+    /// themselves, which other programs may share.
+    fn test_delete_program_cascades() {
+        let mut db_conn = setup_test_db();
+        assert_eq!(delete_program_with_dependants(&mut db_conn), (0, 0, 0));
+        assert_eq!(BpfMap::find_all(&mut db_conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    /// Tests that the cascades depend on foreign key enforcement, which
+    /// SQLite leaves off unless the connection enables it.
+    fn test_delete_program_without_foreign_keys() {
+        let mut db_conn = ConnectionOptions::new()
+            .foreign_keys(false)
+            .establish(":memory:")
+            .unwrap();
+        assert_eq!(delete_program_with_dependants(&mut db_conn), (1, 1, 1));
+    }
+
+    #[test]
+    /// Tests the program to map relation: the maps a program uses,
+    /// the programs sharing a map, and that recording an association
+    /// twice is harmless.
+    fn test_program_map_queries() {
+        let mut db_conn = setup_test_db();
+        let owner = insert_program(&mut db_conn, 100);
+        let sharer = insert_program(&mut db_conn, 200);
+        let loner = insert_program(&mut db_conn, 300);
+
+        for (id, name) in [(10, "shared_map"), (20, "owner_map"), (30, "loner_map")] {
+            let mut map = BpfMap {
+                id: id.into(),
+                name: name.to_string(),
+                ..Default::default()
+            };
+            BpfMap::create_record(&mut db_conn, &mut map).unwrap();
+        }
+
+        for (program_id, map_id) in [(100, 10), (100, 20), (200, 10), (300, 30), (100, 10)] {
+            BpfProgramMap::create_record(&mut db_conn, program_id.into(), map_id.into()).unwrap();
+        }
+
+        let map_ids = |maps: Vec<BpfMap>| maps.iter().map(|m| m.id.get()).collect::<Vec<_>>();
+        assert_eq!(map_ids(owner.maps(&mut db_conn).unwrap()), vec![10, 20]);
+        assert_eq!(map_ids(sharer.maps(&mut db_conn).unwrap()), vec![10]);
+        assert_eq!(map_ids(loner.maps(&mut db_conn).unwrap()), vec![30]);
+
+        let shared = BpfMap::find_record(&mut db_conn, 10.into()).unwrap();
+        let program_ids: Vec<u32> = shared
+            .programs(&mut db_conn)
+            .unwrap()
+            .iter()
+            .map(|p| p.id.get())
+            .collect();
+        assert_eq!(program_ids, vec![100, 200]);
+
+        assert!(BpfProgramMap::delete_record(&mut db_conn, 200.into(), 10.into()).unwrap());
+        assert!(!BpfProgramMap::delete_record(&mut db_conn, 200.into(), 10.into()).unwrap());
+        assert_eq!(shared.programs(&mut db_conn).unwrap(), vec![owner]);
+    }
 }
@@ -0,0 +1,620 @@
+//! Per-kind attachment details for BPF links.
+//!
+//! Each program kind needs different parameters to attach, so each
+//! has its own table holding exactly one row per [`BpfLink`], keyed
+//! by `link_id`. The [`Attachment`] enum wraps the per-kind models
+//! for callers that only know a link's kind at runtime.
+
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{BpfLink, Direction, LinkId, NamespaceId, ProgramKind};
+use crate::schema::{
+    fentry_attachments, fexit_attachments, kprobe_attachments, tc_attachments, tcx_attachments,
+    tracepoint_attachments, uprobe_attachments, xdp_attachments,
+};
+
+/// XDP attachment via the XDP dispatcher.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    AsChangeset,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+    Associations,
+)]
+#[diesel(belongs_to(BpfLink, foreign_key = link_id))]
+#[diesel(primary_key(link_id))]
+#[diesel(table_name = xdp_attachments)]
+pub struct XdpAttachment {
+    pub link_id: LinkId,
+
+    /// Interface name.
+    pub iface: String,
+
+    /// Interface index, resolved at attach time.
+    pub if_index: Option<i32>,
+
+    /// Position within the dispatcher; lower runs first.
+    pub priority: i32,
+
+    /// XDP actions that continue the dispatcher chain, as a JSON array
+    /// of integers.
+    pub proceed_on: String,
+
+    /// Slot in the dispatcher once attached.
+    pub current_position: Option<i32>,
+
+    /// Whether the program is attached to the dispatcher.
+    pub attached: bool,
+
+    /// Network namespace inode number.
+    pub nsid: Option<NamespaceId>,
+}
+
+/// TC attachment via the TC dispatcher.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    AsChangeset,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+    Associations,
+)]
+#[diesel(belongs_to(BpfLink, foreign_key = link_id))]
+#[diesel(primary_key(link_id))]
+#[diesel(table_name = tc_attachments)]
+pub struct TcAttachment {
+    pub link_id: LinkId,
+    pub iface: String,
+    pub if_index: Option<i32>,
+    pub direction: Direction,
+    pub priority: i32,
+
+    /// TC actions that continue the dispatcher chain, as a JSON array
+    /// of integers.
+    pub proceed_on: String,
+    pub current_position: Option<i32>,
+    pub attached: bool,
+    pub nsid: Option<NamespaceId>,
+}
+
+/// TCX attachment. The kernel orders TCX links itself, so there is
+/// no dispatcher state.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    AsChangeset,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+    Associations,
+)]
+#[diesel(belongs_to(BpfLink, foreign_key = link_id))]
+#[diesel(primary_key(link_id))]
+#[diesel(table_name = tcx_attachments)]
+pub struct TcxAttachment {
+    pub link_id: LinkId,
+    pub iface: String,
+    pub if_index: Option<i32>,
+    pub direction: Direction,
+    pub priority: i32,
+    pub current_position: Option<i32>,
+    pub nsid: Option<NamespaceId>,
+}
+
+/// Kprobe or kretprobe attachment.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    AsChangeset,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+    Associations,
+)]
+#[diesel(belongs_to(BpfLink, foreign_key = link_id))]
+#[diesel(primary_key(link_id))]
+#[diesel(table_name = kprobe_attachments)]
+pub struct KprobeAttachment {
+    pub link_id: LinkId,
+
+    /// Kernel function to probe.
+    pub fn_name: String,
+
+    /// Offset within the function.
+    pub fn_offset: i64,
+    pub retprobe: bool,
+
+    /// PID of the container to attach within, if any.
+    pub container_pid: Option<i32>,
+}
+
+/// Uprobe or uretprobe attachment.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    AsChangeset,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+    Associations,
+)]
+#[diesel(belongs_to(BpfLink, foreign_key = link_id))]
+#[diesel(primary_key(link_id))]
+#[diesel(table_name = uprobe_attachments)]
+pub struct UprobeAttachment {
+    pub link_id: LinkId,
+
+    /// Binary or library path.
+    pub target: String,
+
+    /// Optional symbol within the target.
+    pub fn_name: Option<String>,
+    pub fn_offset: i64,
+    pub retprobe: bool,
+
+    /// Restricts the probe to a single process.
+    pub pid: Option<i32>,
+    pub container_pid: Option<i32>,
+}
+
+/// Tracepoint attachment.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    AsChangeset,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+    Associations,
+)]
+#[diesel(belongs_to(BpfLink, foreign_key = link_id))]
+#[diesel(primary_key(link_id))]
+#[diesel(table_name = tracepoint_attachments)]
+pub struct TracepointAttachment {
+    pub link_id: LinkId,
+
+    /// `<category>/<name>`, e.g. `syscalls/sys_enter_kill`.
+    pub tracepoint: String,
+}
+
+/// Fentry attachment.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    AsChangeset,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+    Associations,
+)]
+#[diesel(belongs_to(BpfLink, foreign_key = link_id))]
+#[diesel(primary_key(link_id))]
+#[diesel(table_name = fentry_attachments)]
+pub struct FentryAttachment {
+    pub link_id: LinkId,
+    pub fn_name: String,
+}
+
+/// Fexit attachment.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    AsChangeset,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+    Associations,
+)]
+#[diesel(belongs_to(BpfLink, foreign_key = link_id))]
+#[diesel(primary_key(link_id))]
+#[diesel(table_name = fexit_attachments)]
+pub struct FexitAttachment {
+    pub link_id: LinkId,
+    pub fn_name: String,
+}
+
+/// Basic CRUD for the attachment tables, mirroring [`BpfProgram`]'s.
+///
+/// As with the other models, these functions do not manage
+/// transactions.
+///
+/// [`BpfProgram`]: super::BpfProgram
+macro_rules! impl_attachment_crud {
+    ($model:ident, $table:ident) => {
+        impl $model {
+            /// Inserts the attachment details for a link.
+            pub fn create_record(
+                conn: &mut SqliteConnection,
+                attachment: &$model,
+            ) -> QueryResult<$model> {
+                diesel::insert_into($table::table)
+                    .values(attachment)
+                    .returning($table::all_columns)
+                    .get_result(conn)
+            }
+
+            /// Finds the attachment details for a link.
+            pub fn find_record(
+                conn: &mut SqliteConnection,
+                link_id: LinkId,
+            ) -> QueryResult<$model> {
+                $table::table.find(link_id).first(conn)
+            }
+
+            /// Updates the attachment details, returning the updated
+            /// record.
+            pub fn update_record(&self, conn: &mut SqliteConnection) -> QueryResult<$model> {
+                diesel::update($table::table.find(self.link_id))
+                    .set(self)
+                    .get_result(conn)
+            }
+
+            /// Deletes the attachment details for a link. Returns true
+            /// if a record was deleted.
+            pub fn delete_record(
+                conn: &mut SqliteConnection,
+                link_id: LinkId,
+            ) -> QueryResult<bool> {
+                let num_deleted = diesel::delete($table::table.find(link_id)).execute(conn)?;
+                Ok(num_deleted > 0)
+            }
+        }
+    };
+}
+
+impl_attachment_crud!(XdpAttachment, xdp_attachments);
+impl_attachment_crud!(TcAttachment, tc_attachments);
+impl_attachment_crud!(TcxAttachment, tcx_attachments);
+impl_attachment_crud!(KprobeAttachment, kprobe_attachments);
+impl_attachment_crud!(UprobeAttachment, uprobe_attachments);
+impl_attachment_crud!(TracepointAttachment, tracepoint_attachments);
+impl_attachment_crud!(FentryAttachment, fentry_attachments);
+impl_attachment_crud!(FexitAttachment, fexit_attachments);
+
+/// Attachment details for a link of any kind.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Attachment {
+    Xdp(XdpAttachment),
+    Tc(TcAttachment),
+    Tcx(TcxAttachment),
+    Kprobe(KprobeAttachment),
+    Uprobe(UprobeAttachment),
+    Tracepoint(TracepointAttachment),
+    Fentry(FentryAttachment),
+    Fexit(FexitAttachment),
+}
+
+impl Attachment {
+    /// Returns the program kind these details belong to.
+    pub fn kind(&self) -> ProgramKind {
+        match self {
+            Attachment::Xdp(_) => ProgramKind::Xdp,
+            Attachment::Tc(_) => ProgramKind::Tc,
+            Attachment::Tcx(_) => ProgramKind::Tcx,
+            Attachment::Kprobe(_) => ProgramKind::Kprobe,
+            Attachment::Uprobe(_) => ProgramKind::Uprobe,
+            Attachment::Tracepoint(_) => ProgramKind::Tracepoint,
+            Attachment::Fentry(_) => ProgramKind::Fentry,
+            Attachment::Fexit(_) => ProgramKind::Fexit,
+        }
+    }
+
+    /// Returns the ID of the link these details belong to.
+    pub fn link_id(&self) -> LinkId {
+        match self {
+            Attachment::Xdp(a) => a.link_id,
+            Attachment::Tc(a) => a.link_id,
+            Attachment::Tcx(a) => a.link_id,
+            Attachment::Kprobe(a) => a.link_id,
+            Attachment::Uprobe(a) => a.link_id,
+            Attachment::Tracepoint(a) => a.link_id,
+            Attachment::Fentry(a) => a.link_id,
+            Attachment::Fexit(a) => a.link_id,
+        }
+    }
+
+    /// Returns a short description of the attach point, suitable for
+    /// `bpf_links.target`.
+    pub fn target(&self) -> String {
+        match self {
+            Attachment::Xdp(a) => a.iface.clone(),
+            Attachment::Tc(a) => a.iface.clone(),
+            Attachment::Tcx(a) => a.iface.clone(),
+            Attachment::Kprobe(a) => a.fn_name.clone(),
+            Attachment::Uprobe(a) => match &a.fn_name {
+                Some(fn_name) => format!("{}:{}", a.target, fn_name),
+                None => a.target.clone(),
+            },
+            Attachment::Tracepoint(a) => a.tracepoint.clone(),
+            Attachment::Fentry(a) => a.fn_name.clone(),
+            Attachment::Fexit(a) => a.fn_name.clone(),
+        }
+    }
+
+    /// Inserts the details into the table for their kind.
+    pub fn create_record(&self, conn: &mut SqliteConnection) -> QueryResult<Attachment> {
+        Ok(match self {
+            Attachment::Xdp(a) => Attachment::Xdp(XdpAttachment::create_record(conn, a)?),
+            Attachment::Tc(a) => Attachment::Tc(TcAttachment::create_record(conn, a)?),
+            Attachment::Tcx(a) => Attachment::Tcx(TcxAttachment::create_record(conn, a)?),
+            Attachment::Kprobe(a) => Attachment::Kprobe(KprobeAttachment::create_record(conn, a)?),
+            Attachment::Uprobe(a) => Attachment::Uprobe(UprobeAttachment::create_record(conn, a)?),
+            Attachment::Tracepoint(a) => {
+                Attachment::Tracepoint(TracepointAttachment::create_record(conn, a)?)
+            }
+            Attachment::Fentry(a) => Attachment::Fentry(FentryAttachment::create_record(conn, a)?),
+            Attachment::Fexit(a) => Attachment::Fexit(FexitAttachment::create_record(conn, a)?),
+        })
+    }
+
+    /// Updates the details in the table for their kind.
+    pub fn update_record(&self, conn: &mut SqliteConnection) -> QueryResult<Attachment> {
+        Ok(match self {
+            Attachment::Xdp(a) => Attachment::Xdp(a.update_record(conn)?),
+            Attachment::Tc(a) => Attachment::Tc(a.update_record(conn)?),
+            Attachment::Tcx(a) => Attachment::Tcx(a.update_record(conn)?),
+            Attachment::Kprobe(a) => Attachment::Kprobe(a.update_record(conn)?),
+            Attachment::Uprobe(a) => Attachment::Uprobe(a.update_record(conn)?),
+            Attachment::Tracepoint(a) => Attachment::Tracepoint(a.update_record(conn)?),
+            Attachment::Fentry(a) => Attachment::Fentry(a.update_record(conn)?),
+            Attachment::Fexit(a) => Attachment::Fexit(a.update_record(conn)?),
+        })
+    }
+
+    /// Finds the details for a link of the given kind.
+    pub fn find_for_link(
+        conn: &mut SqliteConnection,
+        kind: ProgramKind,
+        link_id: LinkId,
+    ) -> QueryResult<Attachment> {
+        Ok(match kind {
+            ProgramKind::Xdp => Attachment::Xdp(XdpAttachment::find_record(conn, link_id)?),
+            ProgramKind::Tc => Attachment::Tc(TcAttachment::find_record(conn, link_id)?),
+            ProgramKind::Tcx => Attachment::Tcx(TcxAttachment::find_record(conn, link_id)?),
+            ProgramKind::Kprobe => {
+                Attachment::Kprobe(KprobeAttachment::find_record(conn, link_id)?)
+            }
+            ProgramKind::Uprobe => {
+                Attachment::Uprobe(UprobeAttachment::find_record(conn, link_id)?)
+            }
+            ProgramKind::Tracepoint => {
+                Attachment::Tracepoint(TracepointAttachment::find_record(conn, link_id)?)
+            }
+            ProgramKind::Fentry => {
+                Attachment::Fentry(FentryAttachment::find_record(conn, link_id)?)
+            }
+            ProgramKind::Fexit => Attachment::Fexit(FexitAttachment::find_record(conn, link_id)?),
+        })
+    }
+
+    /// Deletes the details for a link of the given kind. Returns true
+    /// if a record was deleted.
+    pub fn delete_for_link(
+        conn: &mut SqliteConnection,
+        kind: ProgramKind,
+        link_id: LinkId,
+    ) -> QueryResult<bool> {
+        match kind {
+            ProgramKind::Xdp => XdpAttachment::delete_record(conn, link_id),
+            ProgramKind::Tc => TcAttachment::delete_record(conn, link_id),
+            ProgramKind::Tcx => TcxAttachment::delete_record(conn, link_id),
+            ProgramKind::Kprobe => KprobeAttachment::delete_record(conn, link_id),
+            ProgramKind::Uprobe => UprobeAttachment::delete_record(conn, link_id),
+            ProgramKind::Tracepoint => TracepointAttachment::delete_record(conn, link_id),
+            ProgramKind::Fentry => FentryAttachment::delete_record(conn, link_id),
+            ProgramKind::Fexit => FexitAttachment::delete_record(conn, link_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        establish_connection,
+        models::{BpfProgram, LinkState, LocationType},
+    };
+
+    fn setup_link(kind: ProgramKind) -> SqliteConnection {
+        let mut conn = establish_connection(":memory:").unwrap();
+
+        let mut prog = BpfProgram {
+            id: 100.into(),
+            name: "test_program".to_string(),
+            kind,
+            location_type: LocationType::File,
+            file_path: Some("/path/to/test_program.o".to_string()),
+            map_pin_path: "/sys/fs/bpf/test_program".to_string(),
+            retprobe: matches!(kind, ProgramKind::Kprobe | ProgramKind::Uprobe).then_some(false),
+            fn_name: matches!(kind, ProgramKind::Fentry | ProgramKind::Fexit)
+                .then(|| "do_unlinkat".to_string()),
+            ..Default::default()
+        };
+        BpfProgram::create_record(&mut conn, &mut prog).unwrap();
+
+        let mut link = BpfLink {
+            id: 7.into(),
+            program_id: prog.id,
+            link_type: Some(kind),
+            state: LinkState::PreAttach,
+            ..Default::default()
+        };
+        BpfLink::create_record(&mut conn, &mut link).unwrap();
+
+        conn
+    }
+
+    #[test]
+    /// Exercises create, find, update and delete for a TC attachment
+    /// through the kind-specific API.
+    fn test_tc_attachment_crud() {
+        let mut conn = setup_link(ProgramKind::Tc);
+
+        let tc = TcAttachment {
+            link_id: 7.into(),
+            iface: "eth0".to_string(),
+            if_index: Some(10),
+            direction: Direction::Ingress,
+            priority: 55,
+            proceed_on: "[3,30]".to_string(),
+            current_position: None,
+            attached: false,
+            nsid: Some(4026533525.into()),
+        };
+
+        let inserted = TcAttachment::create_record(&mut conn, &tc).expect("Insert failed");
+        assert_eq!(inserted, tc);
+        assert_eq!(TcAttachment::find_record(&mut conn, 7.into()).unwrap(), tc);
+
+        let mut attached = tc.clone();
+        attached.current_position = Some(1);
+        attached.attached = true;
+        assert_eq!(attached.update_record(&mut conn).unwrap(), attached);
+
+        assert!(TcAttachment::delete_record(&mut conn, 7.into()).unwrap());
+        assert!(!TcAttachment::delete_record(&mut conn, 7.into()).unwrap());
+        assert!(TcAttachment::find_record(&mut conn, 7.into()).is_err());
+    }
+
+    #[test]
+    /// Round-trips one attachment of every kind through the
+    /// kind-agnostic [`Attachment`] API.
+    fn test_attachment_roundtrip_all_kinds() {
+        let attachments = [
+            Attachment::Xdp(XdpAttachment {
+                link_id: 7.into(),
+                iface: "eth0".to_string(),
+                if_index: Some(10),
+                priority: 55,
+                proceed_on: "[2,31]".to_string(),
+                current_position: Some(0),
+                attached: true,
+                nsid: Some(4026533525.into()),
+            }),
+            Attachment::Tc(TcAttachment {
+                link_id: 7.into(),
+                iface: "eth0".to_string(),
+                if_index: Some(10),
+                direction: Direction::Egress,
+                priority: 55,
+                proceed_on: "[3,30]".to_string(),
+                current_position: Some(1),
+                attached: true,
+                nsid: None,
+            }),
+            Attachment::Tcx(TcxAttachment {
+                link_id: 7.into(),
+                iface: "eth0".to_string(),
+                if_index: Some(10),
+                direction: Direction::Ingress,
+                priority: 500,
+                current_position: Some(1),
+                nsid: Some(4026533525.into()),
+            }),
+            Attachment::Kprobe(KprobeAttachment {
+                link_id: 7.into(),
+                fn_name: "try_to_wake_up".to_string(),
+                fn_offset: 0,
+                retprobe: false,
+                container_pid: None,
+            }),
+            Attachment::Uprobe(UprobeAttachment {
+                link_id: 7.into(),
+                target: "/go-target".to_string(),
+                fn_name: Some("main.getCount".to_string()),
+                fn_offset: 0,
+                retprobe: true,
+                pid: None,
+                container_pid: Some(3015),
+            }),
+            Attachment::Tracepoint(TracepointAttachment {
+                link_id: 7.into(),
+                tracepoint: "syscalls/sys_enter_kill".to_string(),
+            }),
+            Attachment::Fentry(FentryAttachment {
+                link_id: 7.into(),
+                fn_name: "do_unlinkat".to_string(),
+            }),
+            Attachment::Fexit(FexitAttachment {
+                link_id: 7.into(),
+                fn_name: "do_unlinkat".to_string(),
+            }),
+        ];
+
+        for attachment in attachments {
+            let kind = attachment.kind();
+            let mut conn = setup_link(kind);
+
+            assert_eq!(attachment.create_record(&mut conn).unwrap(), attachment);
+            assert_eq!(
+                Attachment::find_for_link(&mut conn, kind, 7.into()).unwrap(),
+                attachment
+            );
+
+            let json = serde_json::to_string(&attachment).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Attachment>(&json).unwrap(),
+                attachment
+            );
+
+            assert!(Attachment::delete_for_link(&mut conn, kind, 7.into()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_tc_direction_check_constraint() {
+        let mut conn = setup_link(ProgramKind::Tc);
+
+        let result = diesel::sql_query(
+            "INSERT INTO tc_attachments (link_id, iface, direction, priority)
+             VALUES (7, 'eth0', 'sideways', 50)",
+        )
+        .execute(&mut conn);
+        assert!(result.is_err());
+    }
+}
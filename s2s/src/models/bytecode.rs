@@ -0,0 +1,392 @@
+//! Content-addressed program bytecode.
+//!
+//! Each distinct program binary is stored once, as a
+//! [`BytecodeObject`] keyed by the SHA-256 of its bytes, and programs
+//! reference it by that digest in `bytecode_sha256`. Programs loaded
+//! from the same image therefore share one copy, and the programs that
+//! came from an exact object can be found by its digest.
+//!
+//! Objects outlive the programs that reference them until
+//! [`BytecodeObject::collect_garbage`] deletes them, and
+//! [`verify_bytecode`] checks that every object still matches its
+//! digest.
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{define_sql_function, prelude::*, sql_types::Binary};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{BpfProgram, ProgramId};
+use crate::schema::{bpf_programs, bytecode_objects};
+
+define_sql_function! {
+    /// Returns the SHA-256 digest of a blob as lowercase hex.
+    fn sha256_hex(bytes: Binary) -> Text;
+}
+
+/// Registers the `sha256_hex` SQL function on a connection. Must be
+/// called on every connection that uses it, including those that
+/// apply migrations.
+pub(crate) fn register_functions(conn: &mut SqliteConnection) -> QueryResult<()> {
+    sha256_hex_utils::register_impl(conn, |bytes: Vec<u8>| BytecodeObject::digest(&bytes))
+}
+
+/// A program binary, stored once however many programs use it.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Insertable, Selectable, Queryable,
+)]
+#[diesel(table_name = bytecode_objects)]
+pub struct BytecodeObject {
+    /// SHA-256 of `bytes`, as lowercase hex.
+    pub sha256: String,
+
+    /// Serialised as base64.
+    #[serde(with = "super::base64_serde")]
+    pub bytes: Vec<u8>,
+
+    /// Timestamp when the object was first stored.
+    pub created_at: NaiveDateTime,
+}
+
+/// An inconsistency found by [`verify_bytecode`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BytecodeProblem {
+    /// An object whose bytes no longer hash to its digest.
+    Corrupt { sha256: String, actual: String },
+
+    /// A program referencing an object that does not exist, which
+    /// foreign key enforcement prevents unless it was turned off.
+    Missing {
+        program_id: ProgramId,
+        sha256: String,
+    },
+}
+
+impl std::fmt::Display for BytecodeProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BytecodeProblem::Corrupt { sha256, actual } => {
+                write!(f, "bytecode object {sha256}: contents hash to {actual}")
+            }
+            BytecodeProblem::Missing { program_id, sha256 } => {
+                write!(
+                    f,
+                    "program {program_id}: bytecode object {sha256} is missing"
+                )
+            }
+        }
+    }
+}
+
+impl BytecodeObject {
+    /// Returns the SHA-256 of `bytes` as lowercase hex, the digest
+    /// they are stored under.
+    pub fn digest(bytes: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(bytes))
+    }
+
+    /// Stores `bytes` unless an object with the same digest is already
+    /// stored. Returns the digest.
+    pub fn store(conn: &mut SqliteConnection, bytes: &[u8]) -> QueryResult<String> {
+        let sha256 = Self::digest(bytes);
+        diesel::insert_into(bytecode_objects::table)
+            .values((
+                bytecode_objects::sha256.eq(&sha256),
+                bytecode_objects::bytes.eq(bytes),
+                bytecode_objects::created_at.eq(Utc::now().naive_utc()),
+            ))
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        Ok(sha256)
+    }
+
+    /// Retrieves an object by its digest.
+    pub fn find_record(conn: &mut SqliteConnection, sha256: &str) -> QueryResult<BytecodeObject> {
+        bytecode_objects::table.find(sha256).first(conn)
+    }
+
+    /// Deletes every object no program references. Returns their
+    /// digests, sorted.
+    pub fn collect_garbage(conn: &mut SqliteConnection) -> QueryResult<Vec<String>> {
+        let referenced = bpf_programs::table
+            .select(bpf_programs::bytecode_sha256.assume_not_null())
+            .filter(bpf_programs::bytecode_sha256.is_not_null());
+
+        let mut deleted: Vec<String> = diesel::delete(
+            bytecode_objects::table.filter(bytecode_objects::sha256.ne_all(referenced)),
+        )
+        .returning(bytecode_objects::sha256)
+        .get_results(conn)?;
+        deleted.sort();
+        Ok(deleted)
+    }
+}
+
+impl BpfProgram {
+    /// Stores `bytes` as a [`BytecodeObject`] and points
+    /// `bytecode_sha256` at it. Call
+    /// [`update_record`](BpfProgram::update_record) or
+    /// [`create_record`](BpfProgram::create_record) to persist the
+    /// reference.
+    ///
+    /// If `bytes` is empty, clears `bytecode_sha256`, writing the
+    /// cleared column at once if the program is stored, as
+    /// `update_record` leaves `None` fields as they are.
+    pub fn set_bytecode(&mut self, conn: &mut SqliteConnection, bytes: &[u8]) -> QueryResult<()> {
+        if bytes.is_empty() {
+            self.bytecode_sha256 = None;
+            diesel::update(bpf_programs::table.find(self.id))
+                .set(bpf_programs::bytecode_sha256.eq(None::<String>))
+                .execute(conn)?;
+        } else {
+            self.bytecode_sha256 = Some(BytecodeObject::store(conn, bytes)?);
+        }
+        Ok(())
+    }
+
+    /// Returns this program's bytecode, or `None` if it has none.
+    pub fn bytecode(&self, conn: &mut SqliteConnection) -> QueryResult<Option<Vec<u8>>> {
+        match &self.bytecode_sha256 {
+            Some(sha256) => bytecode_objects::table
+                .find(sha256)
+                .select(bytecode_objects::bytes)
+                .first(conn)
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the programs whose bytecode is the object with digest
+    /// `sha256`, ordered by ID.
+    pub fn find_by_bytecode(
+        conn: &mut SqliteConnection,
+        sha256: &str,
+    ) -> QueryResult<Vec<BpfProgram>> {
+        bpf_programs::table
+            .filter(bpf_programs::bytecode_sha256.eq(sha256))
+            .order(bpf_programs::id.asc())
+            .load(conn)
+    }
+}
+
+/// Rehashes every bytecode object and checks that every program's
+/// object exists. Returns the problems found: corrupt objects ordered
+/// by digest, then missing ones ordered by program ID.
+pub fn verify_bytecode(conn: &mut SqliteConnection) -> QueryResult<Vec<BytecodeProblem>> {
+    let actual = sha256_hex(bytecode_objects::bytes);
+    let corrupt: Vec<(String, String)> = bytecode_objects::table
+        .select((bytecode_objects::sha256, actual))
+        .filter(actual.ne(bytecode_objects::sha256))
+        .order(bytecode_objects::sha256.asc())
+        .load(conn)?;
+
+    let missing: Vec<(ProgramId, String)> = bpf_programs::table
+        .select((
+            bpf_programs::id,
+            bpf_programs::bytecode_sha256.assume_not_null(),
+        ))
+        .filter(bpf_programs::bytecode_sha256.is_not_null())
+        .filter(
+            bpf_programs::bytecode_sha256
+                .assume_not_null()
+                .ne_all(bytecode_objects::table.select(bytecode_objects::sha256)),
+        )
+        .order(bpf_programs::id.asc())
+        .load(conn)?;
+
+    Ok(corrupt
+        .into_iter()
+        .map(|(sha256, actual)| BytecodeProblem::Corrupt { sha256, actual })
+        .chain(
+            missing
+                .into_iter()
+                .map(|(program_id, sha256)| BytecodeProblem::Missing { program_id, sha256 }),
+        )
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConnectionOptions, establish_connection, models::LocationType};
+
+    const ELF: &[u8] = &[0x7F, 0x45, 0x4C, 0x46, 0x02, 0x01, 0x01];
+
+    fn insert_program(conn: &mut SqliteConnection, id: u32, bytes: &[u8]) -> BpfProgram {
+        let mut prog = BpfProgram {
+            id: id.into(),
+            name: format!("prog_{id}"),
+            location_type: LocationType::File,
+            file_path: Some("/path/to/prog.o".to_string()),
+            map_pin_path: format!("/run/bpfman/fs/maps/{id}"),
+            ..Default::default()
+        };
+        prog.set_bytecode(conn, bytes).unwrap();
+        BpfProgram::create_record(conn, &mut prog).unwrap()
+    }
+
+    fn object_count(conn: &mut SqliteConnection) -> i64 {
+        bytecode_objects::table.count().get_result(conn).unwrap()
+    }
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(
+            BytecodeObject::digest(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        let mut conn = establish_connection(":memory:").unwrap();
+        let in_sql: String = diesel::select(sha256_hex(ELF))
+            .get_result(&mut conn)
+            .unwrap();
+        assert_eq!(in_sql, BytecodeObject::digest(ELF));
+    }
+
+    #[test]
+    /// Tests that identical bytecode is stored once and shared, and
+    /// that programs can be found by the object they came from.
+    fn test_programs_share_identical_bytecode() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        let first = insert_program(&mut conn, 1, ELF);
+        let second = insert_program(&mut conn, 2, ELF);
+        let other = insert_program(&mut conn, 3, &ELF[..4]);
+        let none = insert_program(&mut conn, 4, &[]);
+
+        assert_eq!(object_count(&mut conn), 2);
+        assert_eq!(first.bytecode_sha256, second.bytecode_sha256);
+        assert_ne!(first.bytecode_sha256, other.bytecode_sha256);
+        assert_eq!(none.bytecode_sha256, None);
+
+        assert_eq!(first.bytecode(&mut conn).unwrap().as_deref(), Some(ELF));
+        assert_eq!(
+            other.bytecode(&mut conn).unwrap().as_deref(),
+            Some(&ELF[..4])
+        );
+        assert_eq!(none.bytecode(&mut conn).unwrap(), None);
+
+        let sha256 = first.bytecode_sha256.as_deref().unwrap();
+        let object = BytecodeObject::find_record(&mut conn, sha256).unwrap();
+        assert_eq!(object.bytes, ELF);
+        let ids: Vec<u32> = BpfProgram::find_by_bytecode(&mut conn, sha256)
+            .unwrap()
+            .iter()
+            .map(|p| p.id.get())
+            .collect();
+        assert_eq!(ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_collect_garbage() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        let mut first = insert_program(&mut conn, 1, ELF);
+        let second = insert_program(&mut conn, 2, ELF);
+        let other = insert_program(&mut conn, 3, &ELF[..4]);
+        assert!(
+            BytecodeObject::collect_garbage(&mut conn)
+                .unwrap()
+                .is_empty()
+        );
+
+        // An object stays while any program references it.
+        BpfProgram::delete_record(&mut conn, second.id).unwrap();
+        assert!(
+            BytecodeObject::collect_garbage(&mut conn)
+                .unwrap()
+                .is_empty()
+        );
+
+        first.set_bytecode(&mut conn, &ELF[..5]).unwrap();
+        first.update_record(&mut conn).unwrap();
+        BpfProgram::delete_record(&mut conn, other.id).unwrap();
+        let mut expected = vec![
+            BytecodeObject::digest(ELF),
+            BytecodeObject::digest(&ELF[..4]),
+        ];
+        expected.sort();
+        assert_eq!(
+            BytecodeObject::collect_garbage(&mut conn).unwrap(),
+            expected
+        );
+        assert_eq!(object_count(&mut conn), 1);
+
+        // Dropping a program's bytecode releases its object at once.
+        first.set_bytecode(&mut conn, &[]).unwrap();
+        let stored = BpfProgram::find_record(&mut conn, first.id).unwrap();
+        assert_eq!(stored.bytecode_sha256, None);
+        assert_eq!(
+            BytecodeObject::collect_garbage(&mut conn).unwrap(),
+            vec![BytecodeObject::digest(&ELF[..5])]
+        );
+        assert_eq!(object_count(&mut conn), 0);
+    }
+
+    #[test]
+    /// Tests that a referenced object cannot be deleted, and that a
+    /// program cannot reference an object that is not stored.
+    fn test_references_are_enforced() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        let prog = insert_program(&mut conn, 1, ELF);
+        let sha256 = prog.bytecode_sha256.unwrap();
+
+        let err = diesel::delete(bytecode_objects::table.find(&sha256))
+            .execute(&mut conn)
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("FOREIGN KEY constraint failed"),
+            "{err}"
+        );
+
+        let mut dangling = BpfProgram {
+            id: 2.into(),
+            name: "dangling".to_string(),
+            file_path: Some("/path/to/prog.o".to_string()),
+            bytecode_sha256: Some(BytecodeObject::digest(b"not stored")),
+            ..Default::default()
+        };
+        let err = BpfProgram::create_record(&mut conn, &mut dangling).unwrap_err();
+        assert!(
+            err.to_string().contains("FOREIGN KEY constraint failed"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_verify_bytecode() {
+        let mut conn = ConnectionOptions::new()
+            .foreign_keys(false)
+            .establish(":memory:")
+            .unwrap();
+        let prog = insert_program(&mut conn, 1, ELF);
+        insert_program(&mut conn, 2, &ELF[..4]);
+        assert!(verify_bytecode(&mut conn).unwrap().is_empty());
+
+        let sha256 = prog.bytecode_sha256.unwrap();
+        diesel::update(bytecode_objects::table.find(&sha256))
+            .set(bytecode_objects::bytes.eq(&ELF[..6]))
+            .execute(&mut conn)
+            .unwrap();
+        let missing = BytecodeObject::digest(&ELF[..4]);
+        diesel::delete(bytecode_objects::table.find(&missing))
+            .execute(&mut conn)
+            .unwrap();
+
+        let problems = verify_bytecode(&mut conn).unwrap();
+        assert_eq!(
+            problems,
+            vec![
+                BytecodeProblem::Corrupt {
+                    sha256: sha256.clone(),
+                    actual: BytecodeObject::digest(&ELF[..6]),
+                },
+                BytecodeProblem::Missing {
+                    program_id: 2.into(),
+                    sha256: missing.clone(),
+                },
+            ]
+        );
+        assert_eq!(
+            problems[1].to_string(),
+            format!("program 2: bytecode object {missing} is missing")
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Image registry credentials for a program.
+//!
+//! The credentials themselves live in a [`SecretStore`]; the program
+//! only records the key, `credentials_ref`, so they never appear in
+//! the database or in the program's serde or debug output.
+
+use diesel::prelude::*;
+use thiserror::Error;
+
+use super::BpfProgram;
+use crate::{
+    schema::bpf_programs,
+    secrets::{Credentials, SecretStore, SecretStoreError},
+};
+
+#[derive(Debug, Error)]
+pub enum CredentialsError {
+    #[error("database error: {0}")]
+    Database(#[from] diesel::result::Error),
+
+    #[error("secret store error: {0}")]
+    Secrets(#[from] SecretStoreError),
+}
+
+impl BpfProgram {
+    /// Returns the secret store key used for this program's
+    /// credentials.
+    pub fn credentials_key(&self) -> String {
+        format!("program-{}", self.id)
+    }
+
+    /// Looks up this program's registry credentials in `store`.
+    pub fn credentials(
+        &self,
+        store: &dyn SecretStore,
+    ) -> Result<Option<Credentials>, SecretStoreError> {
+        match &self.credentials_ref {
+            Some(key) => store.get(key),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores `credentials` in `store` and points `credentials_ref` at
+    /// them. Call [`update_record`](BpfProgram::update_record) or
+    /// [`create_record`](BpfProgram::create_record) to persist the
+    /// reference.
+    ///
+    /// If `credentials` is `None`, removes any stored credentials and
+    /// clears `credentials_ref`, writing the cleared column at once if
+    /// the program is stored, as `update_record` leaves `None` fields
+    /// as they are.
+    pub fn set_credentials(
+        &mut self,
+        conn: &mut SqliteConnection,
+        store: &mut dyn SecretStore,
+        credentials: Option<&Credentials>,
+    ) -> Result<(), CredentialsError> {
+        match credentials {
+            Some(credentials) => {
+                let key = self.credentials_key();
+                store.put(&key, credentials)?;
+                self.credentials_ref = Some(key);
+            }
+            None => {
+                diesel::update(bpf_programs::table.find(self.id))
+                    .set(bpf_programs::credentials_ref.eq(None::<String>))
+                    .execute(conn)?;
+                if let Some(key) = self.credentials_ref.take() {
+                    store.delete(&key)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{establish_connection, models::LocationType, secrets::MemorySecretStore};
+
+    #[test]
+    /// Tests that credentials round-trip through the secret store, and
+    /// that neither the database row nor the serialised or debug form
+    /// of the program contains the password.
+    fn test_credentials_are_kept_out_of_the_program() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        let mut store = MemorySecretStore::default();
+        let credentials = Credentials {
+            username: "robot".to_string(),
+            password: "hunter2".to_string(),
+        };
+
+        let mut prog = BpfProgram {
+            id: 42.into(),
+            name: "xdp_stats".to_string(),
+            location_type: LocationType::Image,
+            image_url: Some("quay.io/private/xdp:latest".to_string()),
+            map_pin_path: "/run/bpfman/fs/maps/42".to_string(),
+            ..Default::default()
+        };
+        prog.set_credentials(&mut conn, &mut store, Some(&credentials))
+            .unwrap();
+        let prog = BpfProgram::create_record(&mut conn, &mut prog).unwrap();
+
+        assert_eq!(prog.credentials_ref.as_deref(), Some("program-42"));
+        assert_eq!(prog.credentials(&store).unwrap(), Some(credentials));
+
+        let json = serde_json::to_string(&prog).unwrap();
+        assert!(!json.contains("hunter2"));
+        assert!(!format!("{prog:?}").contains("hunter2"));
+
+        let mut prog = BpfProgram::find_record(&mut conn, 42.into()).unwrap();
+        prog.set_credentials(&mut conn, &mut store, None).unwrap();
+        assert_eq!(prog.credentials_ref, None);
+        assert_eq!(store.get("program-42").unwrap(), None);
+        let stored = BpfProgram::find_record(&mut conn, 42.into()).unwrap();
+        assert_eq!(stored.credentials_ref, None);
+        assert_eq!(prog.update_record(&mut conn).unwrap().credentials_ref, None);
+    }
+}
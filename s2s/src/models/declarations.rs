@@ -0,0 +1,347 @@
+//! Programs and maps checked against the bytecode they came from.
+//!
+//! [`BpfProgram::inspect_bytecode`] parses a program's stored object
+//! with [`ElfObject::parse`], and [`check_declarations`] compares every
+//! program's `kind` and `bpf_maps` rows with what its object declares.
+
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use thiserror::Error;
+
+use super::{BpfMap, BpfProgram, MapId, ProgramId, ProgramKind};
+use crate::{
+    elf::{DeclaredMap, ElfError, ElfObject},
+    schema::bpf_programs,
+};
+
+#[derive(Debug, Error)]
+pub enum InspectError {
+    #[error("database error: {0}")]
+    Database(#[from] diesel::result::Error),
+
+    #[error("invalid bytecode: {0}")]
+    Elf(#[from] ElfError),
+}
+
+/// A program that disagrees with its bytecode, as found by
+/// [`check_declarations`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeclarationMismatch {
+    /// The bytecode is not an eBPF object that can be parsed.
+    InvalidObject {
+        program_id: ProgramId,
+        reason: String,
+    },
+
+    /// The bytecode has no function with the program's name.
+    MissingFunction { program_id: ProgramId, name: String },
+
+    /// The program's function is in a section for another kind of
+    /// program.
+    Kind {
+        program_id: ProgramId,
+        section: String,
+        kind: ProgramKind,
+    },
+
+    /// A map the program uses has an attribute other than the one its
+    /// declaration gives.
+    Map {
+        program_id: ProgramId,
+        map_id: MapId,
+        field: &'static str,
+        declared: String,
+        recorded: String,
+    },
+}
+
+impl std::fmt::Display for DeclarationMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeclarationMismatch::InvalidObject { program_id, reason } => {
+                write!(f, "program {program_id}: {reason}")
+            }
+            DeclarationMismatch::MissingFunction { program_id, name } => {
+                write!(f, "program {program_id}: bytecode has no function {name}")
+            }
+            DeclarationMismatch::Kind {
+                program_id,
+                section,
+                kind,
+            } => write!(
+                f,
+                "program {program_id}: {kind} program in section {section}"
+            ),
+            DeclarationMismatch::Map {
+                program_id,
+                map_id,
+                field,
+                declared,
+                recorded,
+            } => write!(
+                f,
+                "program {program_id}: map {map_id} has {field} {recorded}, \
+                 bytecode declares {declared}"
+            ),
+        }
+    }
+}
+
+impl BpfProgram {
+    /// Parses this program's bytecode, or returns `None` if it has
+    /// none.
+    pub fn inspect_bytecode(
+        &self,
+        conn: &mut SqliteConnection,
+    ) -> Result<Option<ElfObject>, InspectError> {
+        match self.bytecode(conn)? {
+            Some(bytes) => Ok(Some(ElfObject::parse(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Compares this program and the maps it uses with `object`.
+    fn declaration_mismatches(
+        &self,
+        object: &ElfObject,
+        maps: &[BpfMap],
+    ) -> Vec<DeclarationMismatch> {
+        let mut mismatches = Vec::new();
+        match object.function(&self.name) {
+            None => mismatches.push(DeclarationMismatch::MissingFunction {
+                program_id: self.id,
+                name: self.name.clone(),
+            }),
+            Some(function) if !function.matches_kind(self.kind) => {
+                mismatches.push(DeclarationMismatch::Kind {
+                    program_id: self.id,
+                    section: function.section.clone(),
+                    kind: self.kind,
+                });
+            }
+            Some(_) => {}
+        }
+
+        for map in maps {
+            if let Some(declared) = object.map(&map.name) {
+                mismatches.extend(map_mismatches(self.id, map, declared));
+            }
+        }
+        mismatches
+    }
+}
+
+/// Compares the attributes `map` and `declared` both have. A declared
+/// `max_entries` of 0 is skipped, as loaders replace it, with the
+/// number of CPUs for a perf event array.
+fn map_mismatches(
+    program_id: ProgramId,
+    map: &BpfMap,
+    declared: &DeclaredMap,
+) -> Vec<DeclarationMismatch> {
+    let sizes = [
+        ("key_size", declared.key_size, map.key_size),
+        ("value_size", declared.value_size, map.value_size),
+        (
+            "max_entries",
+            declared.max_entries.filter(|&n| n != 0),
+            map.max_entries,
+        ),
+    ];
+    let types = declared
+        .map_type
+        .zip(map.map_type)
+        .filter(|(declared, recorded)| declared != recorded)
+        .map(|(declared, recorded)| ("map_type", declared.to_string(), recorded.to_string()));
+
+    types
+        .into_iter()
+        .chain(sizes.into_iter().filter_map(|(field, declared, recorded)| {
+            let (declared, recorded) = (declared?, recorded?);
+            (i64::from(declared) != i64::from(recorded))
+                .then(|| (field, declared.to_string(), recorded.to_string()))
+        }))
+        .map(|(field, declared, recorded)| DeclarationMismatch::Map {
+            program_id,
+            map_id: map.id,
+            field,
+            declared,
+            recorded,
+        })
+        .collect()
+}
+
+/// Checks every program with bytecode against it: that the object
+/// has a function with the program's name in a section for its kind,
+/// and that the maps the program uses match the object's declarations
+/// of them. Maps are matched by the name the kernel gives them, and
+/// those the object does not declare, such as its `.rodata`, are not
+/// checked. Returns the mismatches ordered by program ID.
+pub fn check_declarations(conn: &mut SqliteConnection) -> QueryResult<Vec<DeclarationMismatch>> {
+    let programs: Vec<BpfProgram> = bpf_programs::table
+        .filter(bpf_programs::bytecode_sha256.is_not_null())
+        .order(bpf_programs::id.asc())
+        .load(conn)?;
+
+    // Programs loaded from the same image share an object; parse it once.
+    let mut objects: HashMap<String, Result<ElfObject, String>> = HashMap::new();
+    let mut mismatches = Vec::new();
+    for program in programs {
+        let sha256 = program.bytecode_sha256.clone().expect("filtered on");
+        if !objects.contains_key(&sha256) {
+            let parsed = match program.inspect_bytecode(conn) {
+                Ok(object) => Ok(object.expect("program has bytecode")),
+                Err(InspectError::Elf(err)) => Err(err.to_string()),
+                Err(InspectError::Database(err)) => return Err(err),
+            };
+            objects.insert(sha256.clone(), parsed);
+        }
+
+        match &objects[&sha256] {
+            Ok(object) => {
+                let maps = program.maps(conn)?;
+                mismatches.extend(program.declaration_mismatches(object, &maps));
+            }
+            Err(reason) => mismatches.push(DeclarationMismatch::InvalidObject {
+                program_id: program.id,
+                reason: reason.clone(),
+            }),
+        }
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        elf::tests::xdp_object,
+        establish_connection,
+        models::{LocationType, MapType},
+    };
+
+    fn insert_program(
+        conn: &mut SqliteConnection,
+        id: u32,
+        name: &str,
+        kind: ProgramKind,
+        bytes: &[u8],
+    ) -> BpfProgram {
+        let mut prog = BpfProgram {
+            id: id.into(),
+            name: name.to_string(),
+            kind,
+            location_type: LocationType::File,
+            file_path: Some("/path/to/xdp_stats.o".to_string()),
+            map_pin_path: format!("/run/bpfman/fs/maps/{id}"),
+            ..Default::default()
+        };
+        prog.set_bytecode(conn, bytes).unwrap();
+        BpfProgram::create_record(conn, &mut prog).unwrap()
+    }
+
+    fn map(id: u32, name: &str, map_type: MapType, sizes: [i32; 3]) -> BpfMap {
+        BpfMap {
+            id: id.into(),
+            name: name.to_string(),
+            map_type: Some(map_type),
+            key_size: Some(sizes[0]),
+            value_size: Some(sizes[1]),
+            max_entries: Some(sizes[2]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_inspect_bytecode() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        let prog = insert_program(&mut conn, 1, "xdp_pass", ProgramKind::Xdp, &xdp_object());
+        let object = prog.inspect_bytecode(&mut conn).unwrap().unwrap();
+        assert_eq!(object, ElfObject::parse(&xdp_object()).unwrap());
+
+        let none = insert_program(&mut conn, 2, "none", ProgramKind::Xdp, &[]);
+        assert_eq!(none.inspect_bytecode(&mut conn).unwrap(), None);
+
+        let garbage = insert_program(&mut conn, 3, "garbage", ProgramKind::Xdp, b"garbage");
+        assert!(matches!(
+            garbage.inspect_bytecode(&mut conn),
+            Err(InspectError::Elf(ElfError::Parse(_)))
+        ));
+    }
+
+    #[test]
+    fn test_check_declarations() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        let object = xdp_object();
+        let mut good = insert_program(&mut conn, 1, "xdp_pass", ProgramKind::Xdp, &object);
+        for mut m in [
+            map(10, "legacy_counts", MapType::PerCpuArray, [4, 16, 5]),
+            map(11, "events", MapType::RingBuf, [0, 0, 4096]),
+            map(12, "xdp_stats_map_l", MapType::Hash, [4, 16, 1024]),
+            map(13, "xdp_pass.rodata", MapType::Array, [4, 64, 1]),
+        ] {
+            BpfMap::create_record(&mut conn, &mut m).unwrap();
+        }
+        good.record_kernel_maps(&mut conn, &[10.into(), 11.into(), 12.into(), 13.into()])
+            .unwrap();
+        insert_program(&mut conn, 2, "trace_open", ProgramKind::Tracepoint, &object);
+        assert_eq!(check_declarations(&mut conn).unwrap(), vec![]);
+
+        let mut stale = map(20, "legacy_counts", MapType::Hash, [4, 8, 5]);
+        BpfMap::create_record(&mut conn, &mut stale).unwrap();
+        let mut prog = insert_program(&mut conn, 3, "xdp_pass", ProgramKind::Xdp, &object);
+        prog.record_kernel_maps(&mut conn, &[20.into()]).unwrap();
+        insert_program(&mut conn, 4, "trace_open", ProgramKind::Xdp, &object);
+        insert_program(&mut conn, 5, "missing", ProgramKind::Xdp, &object);
+        insert_program(&mut conn, 6, "garbage", ProgramKind::Xdp, b"garbage");
+
+        let mismatches = check_declarations(&mut conn).unwrap();
+        assert_eq!(
+            mismatches[..4],
+            [
+                DeclarationMismatch::Map {
+                    program_id: 3.into(),
+                    map_id: 20.into(),
+                    field: "map_type",
+                    declared: "per_cpu_array".to_string(),
+                    recorded: "hash".to_string(),
+                },
+                DeclarationMismatch::Map {
+                    program_id: 3.into(),
+                    map_id: 20.into(),
+                    field: "value_size",
+                    declared: "16".to_string(),
+                    recorded: "8".to_string(),
+                },
+                DeclarationMismatch::Kind {
+                    program_id: 4.into(),
+                    section: "tracepoint/syscalls/sys_enter_openat".to_string(),
+                    kind: ProgramKind::Xdp,
+                },
+                DeclarationMismatch::MissingFunction {
+                    program_id: 5.into(),
+                    name: "missing".to_string(),
+                },
+            ]
+        );
+        assert!(matches!(
+            &mismatches[4],
+            DeclarationMismatch::InvalidObject { program_id, .. } if *program_id == 6.into()
+        ));
+        assert_eq!(mismatches.len(), 5);
+        assert_eq!(
+            mismatches[1].to_string(),
+            "program 3: map 20 has value_size 8, bytecode declares 16"
+        );
+        assert_eq!(
+            mismatches[2].to_string(),
+            "program 4: xdp program in section tracepoint/syscalls/sys_enter_openat"
+        );
+        assert!(
+            mismatches[4]
+                .to_string()
+                .starts_with("program 6: not an ELF object")
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! TC and XDP dispatcher state.
+//!
+//! A dispatcher is identified by its attach point: network namespace,
+//! interface index and, for TC, direction. The extensions it runs are
+//! the attached [`TcAttachment`] / [`XdpAttachment`] rows at the same
+//! attach point, ordered by `current_position`.
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{Direction, NamespaceId, TcAttachment, XdpAttachment};
+use crate::schema::{tc_attachments, tc_dispatchers, xdp_attachments, xdp_dispatchers};
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Default,
+    Serialize,
+    Deserialize,
+    AsChangeset,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+)]
+#[diesel(primary_key(nsid, if_index, direction))]
+#[diesel(table_name = tc_dispatchers)]
+pub struct TcDispatcher {
+    /// Network namespace inode number.
+    pub nsid: NamespaceId,
+
+    /// Interface index.
+    pub if_index: i32,
+
+    /// Traffic direction.
+    pub direction: Direction,
+
+    /// Revision, incremented each time bpfman rebuilds the dispatcher.
+    pub revision: i32,
+
+    /// Interface name.
+    pub if_name: String,
+
+    /// TC filter priority of the dispatcher itself.
+    pub priority: i32,
+
+    /// TC filter handle, once attached.
+    pub handle: Option<i32>,
+
+    /// Number of extension slots in use.
+    pub num_extensions: i32,
+
+    /// Dispatcher program name.
+    pub program_name: Option<String>,
+
+    /// Timestamp when the record was created.
+    pub created_at: NaiveDateTime,
+
+    /// Timestamp when the record was last updated.
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Default,
+    Serialize,
+    Deserialize,
+    AsChangeset,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+)]
+#[diesel(primary_key(nsid, if_index))]
+#[diesel(table_name = xdp_dispatchers)]
+pub struct XdpDispatcher {
+    /// Network namespace inode number.
+    pub nsid: NamespaceId,
+
+    /// Interface index.
+    pub if_index: i32,
+
+    /// Revision, incremented each time bpfman rebuilds the dispatcher.
+    pub revision: i32,
+
+    /// Interface name.
+    pub if_name: String,
+
+    /// bpfman's XDP attach mode discriminant.
+    pub mode: i32,
+
+    /// Number of extension slots in use.
+    pub num_extensions: i32,
+
+    /// Dispatcher program name.
+    pub program_name: Option<String>,
+
+    /// Timestamp when the record was created.
+    pub created_at: NaiveDateTime,
+
+    /// Timestamp when the record was last updated.
+    pub updated_at: NaiveDateTime,
+}
+
+impl TcDispatcher {
+    /// Creates a new TC dispatcher record.
+    ///
+    /// Updates created_at and updated_at timestamps before insertion.
+    pub fn create_record(
+        conn: &mut SqliteConnection,
+        dispatcher: &mut TcDispatcher,
+    ) -> QueryResult<TcDispatcher> {
+        dispatcher.created_at = Utc::now().naive_utc();
+        dispatcher.updated_at = dispatcher.created_at;
+
+        diesel::insert_into(tc_dispatchers::table)
+            .values(&*dispatcher)
+            .returning(tc_dispatchers::all_columns)
+            .get_result(conn)
+    }
+
+    /// Returns all TC dispatchers.
+    pub fn find_all(conn: &mut SqliteConnection) -> QueryResult<Vec<TcDispatcher>> {
+        tc_dispatchers::table.load(conn)
+    }
+
+    /// Finds the dispatcher for an attach point.
+    pub fn find_record(
+        conn: &mut SqliteConnection,
+        nsid: NamespaceId,
+        if_index: i32,
+        direction: Direction,
+    ) -> QueryResult<TcDispatcher> {
+        tc_dispatchers::table
+            .find((nsid, if_index, direction))
+            .first(conn)
+    }
+
+    /// Updates an existing dispatcher record. Updates the updated_at
+    /// timestamp. Returns the updated record if successful.
+    pub fn update_record(&mut self, conn: &mut SqliteConnection) -> QueryResult<TcDispatcher> {
+        self.updated_at = Utc::now().naive_utc();
+
+        diesel::update(&*self).set(&*self).get_result(conn)
+    }
+
+    /// Deletes the dispatcher for an attach point. Returns true if a
+    /// record was deleted.
+    pub fn delete_record(
+        conn: &mut SqliteConnection,
+        nsid: NamespaceId,
+        if_index: i32,
+        direction: Direction,
+    ) -> QueryResult<bool> {
+        let num_deleted = diesel::delete(tc_dispatchers::table.find((nsid, if_index, direction)))
+            .execute(conn)?;
+
+        Ok(num_deleted > 0)
+    }
+
+    /// Returns the attached TC programs occupying this dispatcher's
+    /// extension slots, in slot order.
+    pub fn extensions(&self, conn: &mut SqliteConnection) -> QueryResult<Vec<TcAttachment>> {
+        tc_attachments::table
+            .filter(tc_attachments::nsid.eq(self.nsid))
+            .filter(tc_attachments::if_index.eq(self.if_index))
+            .filter(tc_attachments::direction.eq(self.direction))
+            .filter(tc_attachments::attached.eq(true))
+            .order(tc_attachments::current_position.asc())
+            .load(conn)
+    }
+}
+
+impl XdpDispatcher {
+    /// Creates a new XDP dispatcher record.
+    ///
+    /// Updates created_at and updated_at timestamps before insertion.
+    pub fn create_record(
+        conn: &mut SqliteConnection,
+        dispatcher: &mut XdpDispatcher,
+    ) -> QueryResult<XdpDispatcher> {
+        dispatcher.created_at = Utc::now().naive_utc();
+        dispatcher.updated_at = dispatcher.created_at;
+
+        diesel::insert_into(xdp_dispatchers::table)
+            .values(&*dispatcher)
+            .returning(xdp_dispatchers::all_columns)
+            .get_result(conn)
+    }
+
+    /// Returns all XDP dispatchers.
+    pub fn find_all(conn: &mut SqliteConnection) -> QueryResult<Vec<XdpDispatcher>> {
+        xdp_dispatchers::table.load(conn)
+    }
+
+    /// Finds the dispatcher for an attach point.
+    pub fn find_record(
+        conn: &mut SqliteConnection,
+        nsid: NamespaceId,
+        if_index: i32,
+    ) -> QueryResult<XdpDispatcher> {
+        xdp_dispatchers::table.find((nsid, if_index)).first(conn)
+    }
+
+    /// Updates an existing dispatcher record. Updates the updated_at
+    /// timestamp. Returns the updated record if successful.
+    pub fn update_record(&mut self, conn: &mut SqliteConnection) -> QueryResult<XdpDispatcher> {
+        self.updated_at = Utc::now().naive_utc();
+
+        diesel::update(&*self).set(&*self).get_result(conn)
+    }
+
+    /// Deletes the dispatcher for an attach point. Returns true if a
+    /// record was deleted.
+    pub fn delete_record(
+        conn: &mut SqliteConnection,
+        nsid: NamespaceId,
+        if_index: i32,
+    ) -> QueryResult<bool> {
+        let num_deleted =
+            diesel::delete(xdp_dispatchers::table.find((nsid, if_index))).execute(conn)?;
+
+        Ok(num_deleted > 0)
+    }
+
+    /// Returns the attached XDP programs occupying this dispatcher's
+    /// extension slots, in slot order.
+    pub fn extensions(&self, conn: &mut SqliteConnection) -> QueryResult<Vec<XdpAttachment>> {
+        xdp_attachments::table
+            .filter(xdp_attachments::nsid.eq(self.nsid))
+            .filter(xdp_attachments::if_index.eq(self.if_index))
+            .filter(xdp_attachments::attached.eq(true))
+            .order(xdp_attachments::current_position.asc())
+            .load(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        establish_connection,
+        models::{BpfLink, BpfProgram, LinkState, LocationType, ProgramKind},
+    };
+
+    const NSID: NamespaceId = NamespaceId::new(4026533525);
+
+    /// Inserts a TC program, link and attachment at the given slot.
+    fn insert_tc_extension(
+        conn: &mut SqliteConnection,
+        id: u32,
+        direction: Direction,
+        position: Option<i32>,
+    ) {
+        let mut prog = BpfProgram {
+            id: id.into(),
+            name: format!("tc_{id}"),
+            kind: ProgramKind::Tc,
+            location_type: LocationType::File,
+            file_path: Some("/path/to/tc.o".to_string()),
+            map_pin_path: format!("/run/bpfman/fs/maps/{id}"),
+            ..Default::default()
+        };
+        BpfProgram::create_record(conn, &mut prog).unwrap();
+
+        let mut link = BpfLink {
+            id: id.into(),
+            program_id: id.into(),
+            state: LinkState::Attached,
+            ..Default::default()
+        };
+        BpfLink::create_record(conn, &mut link).unwrap();
+
+        TcAttachment::create_record(
+            conn,
+            &TcAttachment {
+                link_id: id.into(),
+                iface: "eth0".to_string(),
+                if_index: Some(10),
+                direction,
+                priority: 50,
+                proceed_on: "[3,30]".to_string(),
+                current_position: position,
+                attached: position.is_some(),
+                nsid: Some(NSID),
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    /// Tests create, lookup by attach point, update and delete of a
+    /// TC dispatcher, and that its extensions are the attached TC
+    /// programs at the same attach point in slot order.
+    fn test_tc_dispatcher_crud_and_extensions() {
+        let mut conn = establish_connection(":memory:").unwrap();
+
+        let mut dispatcher = TcDispatcher {
+            nsid: NSID,
+            if_index: 10,
+            direction: Direction::Ingress,
+            revision: 2,
+            if_name: "eth0".to_string(),
+            priority: 50,
+            handle: Some(2),
+            num_extensions: 2,
+            program_name: Some("tc_dispatcher".to_string()),
+            ..Default::default()
+        };
+        let inserted = TcDispatcher::create_record(&mut conn, &mut dispatcher).unwrap();
+        assert_eq!(inserted, dispatcher);
+
+        let found = TcDispatcher::find_record(&mut conn, NSID, 10, Direction::Ingress).unwrap();
+        assert_eq!(found, inserted);
+        assert!(TcDispatcher::find_record(&mut conn, NSID, 10, Direction::Egress).is_err());
+
+        insert_tc_extension(&mut conn, 929, Direction::Ingress, Some(1));
+        insert_tc_extension(&mut conn, 885, Direction::Ingress, Some(0));
+        insert_tc_extension(&mut conn, 886, Direction::Ingress, None);
+        insert_tc_extension(&mut conn, 887, Direction::Egress, Some(0));
+
+        let extensions: Vec<u32> = found
+            .extensions(&mut conn)
+            .unwrap()
+            .iter()
+            .map(|a| a.link_id.get())
+            .collect();
+        assert_eq!(extensions, vec![885, 929]);
+
+        dispatcher.revision = 3;
+        dispatcher.num_extensions = 3;
+        let updated = dispatcher.update_record(&mut conn).unwrap();
+        assert_eq!(updated.revision, 3);
+        assert_eq!(TcDispatcher::find_all(&mut conn).unwrap(), vec![updated]);
+
+        assert!(TcDispatcher::delete_record(&mut conn, NSID, 10, Direction::Ingress).unwrap());
+        assert!(TcDispatcher::find_all(&mut conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_xdp_dispatcher_crud() {
+        let mut conn = establish_connection(":memory:").unwrap();
+
+        let mut dispatcher = XdpDispatcher {
+            nsid: NSID,
+            if_index: 10,
+            revision: 3,
+            if_name: "eth0".to_string(),
+            mode: 1,
+            num_extensions: 3,
+            program_name: Some("xdp_dispatcher".to_string()),
+            ..Default::default()
+        };
+        let inserted = XdpDispatcher::create_record(&mut conn, &mut dispatcher).unwrap();
+        assert_eq!(
+            XdpDispatcher::find_record(&mut conn, NSID, 10).unwrap(),
+            inserted
+        );
+
+        // The attach point is unique.
+        assert!(XdpDispatcher::create_record(&mut conn, &mut dispatcher.clone()).is_err());
+
+        assert!(inserted.extensions(&mut conn).unwrap().is_empty());
+        assert!(XdpDispatcher::delete_record(&mut conn, NSID, 10).unwrap());
+        assert!(!XdpDispatcher::delete_record(&mut conn, NSID, 10).unwrap());
+    }
+}
@@ -0,0 +1,402 @@
+//! The history of changes to programs, links and maps.
+//!
+//! Triggers on `bpf_programs`, `bpf_links` and `bpf_maps` append an
+//! [`Event`] to the `events` table for every change to them, whichever
+//! API or tool makes it; the table itself rejects updates and deletes.
+//! Changes made inside [`with_actor`] record who made them.
+//!
+//! Create and delete events hold the whole row, update and state
+//! change events only the columns that changed, so folding an entity's
+//! events in order with [`replay`] rebuilds the row as it stood after
+//! the last of them. Timestamps are not recorded.
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{EntityType, EventAction, ProgramId};
+use crate::schema::{event_actor, events};
+
+/// One change to a program, link or map.
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Insertable, Queryable, Selectable,
+)]
+#[diesel(table_name = events)]
+pub struct Event {
+    /// Increases with each event, so gives the order they happened in.
+    pub id: i64,
+
+    /// When the change was made.
+    pub occurred_at: NaiveDateTime,
+
+    /// Who made the change, if it was made inside [`with_actor`].
+    pub actor: Option<String>,
+
+    pub entity_type: EntityType,
+
+    /// The program, link or map ID.
+    pub entity_id: i64,
+
+    /// The program, for program and link events.
+    pub program_id: Option<ProgramId>,
+
+    pub action: EventAction,
+
+    /// The changed columns before the change, as a JSON object. The
+    /// whole row for deletes, and `None` for creates.
+    pub old_values: Option<String>,
+
+    /// The changed columns after the change, as a JSON object. The
+    /// whole row for creates, and `None` for deletes.
+    pub new_values: Option<String>,
+}
+
+impl Event {
+    /// Returns the events for one program, link or map, oldest first.
+    pub fn history(
+        conn: &mut SqliteConnection,
+        entity_type: EntityType,
+        entity_id: i64,
+    ) -> QueryResult<Vec<Event>> {
+        events::table
+            .filter(events::entity_type.eq(entity_type))
+            .filter(events::entity_id.eq(entity_id))
+            .order(events::id.asc())
+            .load(conn)
+    }
+
+    /// Returns the events for a program and its links, oldest first.
+    pub fn program_history(
+        conn: &mut SqliteConnection,
+        program_id: ProgramId,
+    ) -> QueryResult<Vec<Event>> {
+        events::table
+            .filter(events::program_id.eq(program_id))
+            .order(events::id.asc())
+            .load(conn)
+    }
+
+    /// Returns the events after `after_id`, oldest first, for
+    /// following the history as it grows.
+    pub fn since(conn: &mut SqliteConnection, after_id: i64) -> QueryResult<Vec<Event>> {
+        events::table
+            .filter(events::id.gt(after_id))
+            .order(events::id.asc())
+            .load(conn)
+    }
+
+    /// Returns `old_values` as a JSON object, empty if unset. Fails if
+    /// it is not a JSON object, which the triggers never write but an
+    /// imported or hand-edited row might hold.
+    pub fn old_values(&self) -> Result<Map<String, Value>, serde_json::Error> {
+        parse_values(self.old_values.as_deref())
+    }
+
+    /// Returns `new_values` as a JSON object, empty if unset. Fails if
+    /// it is not a JSON object.
+    pub fn new_values(&self) -> Result<Map<String, Value>, serde_json::Error> {
+        parse_values(self.new_values.as_deref())
+    }
+}
+
+fn parse_values(values: Option<&str>) -> Result<Map<String, Value>, serde_json::Error> {
+    values.map_or_else(|| Ok(Map::new()), serde_json::from_str)
+}
+
+/// Folds one entity's events, oldest first, into its columns after the
+/// last of them. Returns `None` if the entity does not exist at that
+/// point, either because it was deleted or because the events start
+/// after its creation. Fails on the first event whose `new_values`
+/// is not a JSON object.
+pub fn replay<'a>(
+    events: impl IntoIterator<Item = &'a Event>,
+) -> Result<Option<Map<String, Value>>, serde_json::Error> {
+    let mut row = None;
+    for event in events {
+        match event.action {
+            EventAction::Create => row = Some(event.new_values()?),
+            EventAction::Update | EventAction::StateChange => {
+                if let Some(row) = &mut row {
+                    row.extend(event.new_values()?);
+                }
+            }
+            EventAction::Delete => row = None,
+        }
+    }
+    Ok(row)
+}
+
+/// Runs `f` in a transaction, recording `actor` on the events for the
+/// changes it makes. Nested calls record the innermost actor.
+pub fn with_actor<T, E, F>(conn: &mut SqliteConnection, actor: &str, f: F) -> Result<T, E>
+where
+    F: FnOnce(&mut SqliteConnection) -> Result<T, E>,
+    E: From<diesel::result::Error>,
+{
+    conn.transaction(|conn| {
+        let previous: Option<String> = event_actor::table
+            .select(event_actor::actor)
+            .first(conn)
+            .optional()?;
+        set_actor(conn, Some(actor))?;
+        let result = f(conn)?;
+        set_actor(conn, previous.as_deref())?;
+        Ok(result)
+    })
+}
+
+fn set_actor(conn: &mut SqliteConnection, actor: Option<&str>) -> QueryResult<()> {
+    match actor {
+        Some(actor) => diesel::replace_into(event_actor::table)
+            .values((event_actor::id.eq(1), event_actor::actor.eq(actor)))
+            .execute(conn)?,
+        None => diesel::delete(event_actor::table).execute(conn)?,
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::connection::SimpleConnection;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        establish_connection,
+        models::{
+            BpfLink, BpfMap, BpfProgram, BytecodeObject, KernelInfo, LinkState, LocationType,
+            ProgramKind, ProgramState,
+        },
+    };
+
+    fn insert_program(conn: &mut SqliteConnection) -> BpfProgram {
+        let mut program = BpfProgram {
+            id: 1.into(),
+            name: "xdp_pass".to_string(),
+            kind: ProgramKind::Xdp,
+            location_type: LocationType::File,
+            file_path: Some("/path/to/xdp_pass.o".to_string()),
+            map_pin_path: "/run/bpfman/fs/maps/1".to_string(),
+            ..Default::default()
+        };
+        program.set_bytecode(conn, &[0; 16]).unwrap();
+        BpfProgram::create_record(conn, &mut program).unwrap()
+    }
+
+    fn actions(events: &[Event]) -> Vec<(EntityType, i64, EventAction)> {
+        events
+            .iter()
+            .map(|e| (e.entity_type, e.entity_id, e.action))
+            .collect()
+    }
+
+    #[test]
+    /// Tests that a program's history records its whole lifecycle,
+    /// including its links, and replays to the stored row.
+    fn test_program_history() {
+        use EntityType::{Link, Program};
+        use EventAction::*;
+
+        let mut conn = establish_connection(":memory:").unwrap();
+        let mut program = insert_program(&mut conn);
+        let info = KernelInfo {
+            name: "xdp_pass".to_string(),
+            program_type: 6,
+            loaded_at: "2025-02-17T19:14:27+0000".to_string(),
+            tag: "4b9d1b2c140e87ce".to_string(),
+            ..Default::default()
+        };
+        program.mark_loaded(&mut conn, &info).unwrap();
+        let mut link = BpfLink {
+            id: 10.into(),
+            program_id: program.id,
+            link_type: Some(ProgramKind::Xdp),
+            ..Default::default()
+        };
+        let mut link = BpfLink::create_record(&mut conn, &mut link).unwrap();
+        link.mark_attached(&mut conn).unwrap();
+        program.description = Some("passes everything".to_string());
+        program.update_record(&mut conn).unwrap();
+
+        let history = Event::program_history(&mut conn, program.id).unwrap();
+        assert_eq!(
+            actions(&history),
+            vec![
+                (Program, 1, Create),
+                (Program, 1, StateChange),
+                (Link, 10, Create),
+                (Link, 10, StateChange),
+                (Program, 1, Update),
+            ]
+        );
+        assert!(history.windows(2).all(|w| w[0].id < w[1].id));
+        assert!(history.iter().all(|e| e.actor.is_none()));
+
+        let created = history[0].new_values().unwrap();
+        assert_eq!(
+            created["bytecode_sha256"],
+            json!(BytecodeObject::digest(&[0; 16]))
+        );
+        assert_eq!(created["state"], json!("pre_load"));
+        assert!(!created.contains_key("updated_at"));
+
+        // A state change records only the columns that changed.
+        assert_eq!(
+            Value::Object(history[1].old_values().unwrap()),
+            json!({
+                "state": "pre_load",
+                "kernel_name": null,
+                "kernel_program_type": null,
+                "kernel_loaded_at": null,
+                "kernel_tag": null,
+            })
+        );
+        assert_eq!(
+            Value::Object(history[1].new_values().unwrap()),
+            json!({
+                "state": "loaded",
+                "kernel_name": "xdp_pass",
+                "kernel_program_type": 6,
+                "kernel_loaded_at": "2025-02-17T19:14:27+0000",
+                "kernel_tag": "4b9d1b2c140e87ce",
+            })
+        );
+
+        let programs = || history.iter().filter(|e| e.entity_type == Program);
+        let replayed = replay(programs()).unwrap().unwrap();
+        assert_eq!(replayed["description"], json!("passes everything"));
+        assert_eq!(replayed["state"], json!(ProgramState::Loaded.as_str()));
+        assert_eq!(replayed["kernel_map_ids"], json!("[]"));
+        assert_eq!(
+            replay(programs().take(1)).unwrap().unwrap()["state"],
+            json!("pre_load")
+        );
+
+        let link_history = Event::history(&mut conn, Link, 10).unwrap();
+        assert_eq!(
+            replay(&link_history).unwrap().unwrap()["state"],
+            json!(LinkState::Attached.as_str())
+        );
+
+        // Deleting the program deletes its link, and both are recorded.
+        let last = history.last().unwrap().id;
+        assert!(BpfProgram::delete_record(&mut conn, program.id).unwrap());
+        let deleted = Event::since(&mut conn, last).unwrap();
+        assert_eq!(
+            actions(&deleted),
+            vec![(Link, 10, Delete), (Program, 1, Delete)]
+        );
+        assert_eq!(
+            deleted[1].old_values().unwrap()["description"],
+            json!("passes everything")
+        );
+        assert_eq!(deleted[1].new_values, None);
+        assert_eq!(
+            replay(&Event::program_history(&mut conn, program.id).unwrap()).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    /// Tests map events, and that an update changing nothing but the
+    /// timestamps records no event.
+    fn test_map_history() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        let mut map = BpfMap {
+            id: 30.into(),
+            name: "stats".to_string(),
+            ..Default::default()
+        };
+        let mut map = BpfMap::create_record(&mut conn, &mut map).unwrap();
+        map.update_record(&mut conn).unwrap();
+        map.max_entries = Some(1024);
+        map.update_record(&mut conn).unwrap();
+        BpfMap::delete_record(&mut conn, map.id).unwrap();
+
+        let history = Event::history(&mut conn, EntityType::Map, 30).unwrap();
+        assert_eq!(
+            actions(&history),
+            vec![
+                (EntityType::Map, 30, EventAction::Create),
+                (EntityType::Map, 30, EventAction::Update),
+                (EntityType::Map, 30, EventAction::Delete),
+            ]
+        );
+        assert!(history.iter().all(|e| e.program_id.is_none()));
+        assert_eq!(
+            Value::Object(history[1].new_values().unwrap()),
+            json!({"max_entries": 1024})
+        );
+        assert_eq!(
+            replay(&history[..2]).unwrap().unwrap()["max_entries"],
+            json!(1024)
+        );
+    }
+
+    #[test]
+    /// Tests that values that are not JSON objects are errors rather
+    /// than panics, and that replay stops at them.
+    fn test_invalid_values() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        insert_program(&mut conn);
+        let mut history = Event::since(&mut conn, 0).unwrap();
+        assert!(replay(&history).unwrap().is_some());
+
+        for values in ["not json", "[1, 2]", "null"] {
+            history[0].new_values = Some(values.to_string());
+            history[0].old_values = Some(values.to_string());
+            assert!(history[0].new_values().is_err(), "{values}");
+            assert!(history[0].old_values().is_err(), "{values}");
+            assert!(replay(&history).is_err(), "{values}");
+        }
+    }
+
+    #[test]
+    /// Tests that events record the actor, that the actor does not
+    /// outlive `with_actor`, and that a failed change records nothing.
+    fn test_with_actor() {
+        let mut conn = establish_connection(":memory:").unwrap();
+
+        let mut program = with_actor(&mut conn, "bpfman", |conn| {
+            let mut program = insert_program(conn);
+            with_actor(conn, "bpfman-agent", |conn| {
+                program.description = Some("nested".to_string());
+                program.update_record(conn)
+            })?;
+            program.name = "renamed".to_string();
+            program.update_record(conn)
+        })
+        .unwrap();
+        program.description = Some("outside".to_string());
+        program.update_record(&mut conn).unwrap();
+
+        let failed: QueryResult<()> = with_actor(&mut conn, "bpfman", |conn| {
+            program.name = "rolled back".to_string();
+            program.update_record(conn)?;
+            Err(diesel::result::Error::RollbackTransaction)
+        });
+        assert!(failed.is_err());
+
+        let history = Event::program_history(&mut conn, program.id).unwrap();
+        let actors: Vec<Option<&str>> = history.iter().map(|e| e.actor.as_deref()).collect();
+        assert_eq!(
+            actors,
+            vec![Some("bpfman"), Some("bpfman-agent"), Some("bpfman"), None]
+        );
+        let count: i64 = event_actor::table.count().get_result(&mut conn).unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_events_are_append_only() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        insert_program(&mut conn);
+
+        for sql in ["UPDATE events SET actor = 'someone'", "DELETE FROM events"] {
+            let err = conn.batch_execute(sql).unwrap_err();
+            assert!(err.to_string().contains("events are append-only"), "{err}");
+        }
+        assert_eq!(Event::since(&mut conn, 0).unwrap().len(), 1);
+    }
+}
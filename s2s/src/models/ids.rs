@@ -0,0 +1,287 @@
+//! Unsigned kernel identifiers and sizes.
+//!
+//! The kernel hands out program, map, link and BTF IDs, namespace
+//! inode numbers and program sizes as `u32`. SQLite has no unsigned
+//! type, so each of these is wrapped in a newtype stored in a 64-bit
+//! `BIGINT` column, which holds every `u32` exactly. Reading a stored
+//! value outside the `u32` range fails with [`OutOfRangeError`] rather
+//! than being truncated, and the wrappers keep, say, a [`MapId`] from
+//! being passed where a [`ProgramId`] is expected.
+//!
+//! Unlike [`crate::uintblob`], which encodes values as BLOBs, these
+//! are stored as plain integers so they remain usable as `INTEGER
+//! PRIMARY KEY` rowid aliases and in foreign keys.
+
+use diesel::{
+    backend::Backend,
+    deserialize::{FromSql, FromSqlRow},
+    expression::AsExpression,
+    serialize::{IsNull, Output, ToSql},
+    sql_types::BigInt,
+    sqlite::Sqlite,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Error returned when a stored integer does not fit the `u32`
+/// wrapper it is read into. `value` is wide enough to hold any `i64`
+/// or `u64` it was converted from.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{value} is out of range for {type_name}")]
+pub struct OutOfRangeError {
+    pub type_name: &'static str,
+    pub value: i128,
+}
+
+impl From<OutOfRangeError> for diesel::result::Error {
+    fn from(err: OutOfRangeError) -> Self {
+        diesel::result::Error::DeserializationError(Box::new(err))
+    }
+}
+
+macro_rules! define_kernel_u32 {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(
+            Debug,
+            Clone,
+            Copy,
+            PartialEq,
+            Eq,
+            PartialOrd,
+            Ord,
+            Hash,
+            Default,
+            Serialize,
+            Deserialize,
+            AsExpression,
+            FromSqlRow,
+        )]
+        #[diesel(sql_type = BigInt)]
+        #[serde(transparent)]
+        #[repr(transparent)]
+        pub struct $name(u32);
+
+        impl $name {
+            /// Wraps a `u32`.
+            pub const fn new(value: u32) -> Self {
+                $name(value)
+            }
+
+            /// Returns the inner value.
+            pub fn get(&self) -> u32 {
+                self.0
+            }
+        }
+
+        impl From<u32> for $name {
+            fn from(value: u32) -> Self {
+                $name(value)
+            }
+        }
+
+        impl From<$name> for u32 {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl From<$name> for i64 {
+            fn from(value: $name) -> Self {
+                value.0 as i64
+            }
+        }
+
+        impl TryFrom<i64> for $name {
+            type Error = OutOfRangeError;
+
+            fn try_from(value: i64) -> Result<Self, Self::Error> {
+                u32::try_from(value).map($name).map_err(|_| OutOfRangeError {
+                    type_name: stringify!($name),
+                    value: value.into(),
+                })
+            }
+        }
+
+        impl TryFrom<u64> for $name {
+            type Error = OutOfRangeError;
+
+            fn try_from(value: u64) -> Result<Self, Self::Error> {
+                u32::try_from(value).map($name).map_err(|_| OutOfRangeError {
+                    type_name: stringify!($name),
+                    value: value.into(),
+                })
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = std::num::ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map($name)
+            }
+        }
+
+        impl ToSql<BigInt, Sqlite> for $name {
+            fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+                out.set_value(self.0 as i64);
+                Ok(IsNull::No)
+            }
+        }
+
+        impl FromSql<BigInt, Sqlite> for $name {
+            fn from_sql(
+                bytes: <Sqlite as Backend>::RawValue<'_>,
+            ) -> diesel::deserialize::Result<Self> {
+                let value = <i64 as FromSql<BigInt, Sqlite>>::from_sql(bytes)?;
+                $name::try_from(value).map_err(|e| e.into())
+            }
+        }
+    };
+}
+
+define_kernel_u32! {
+    /// Kernel BPF program ID.
+    ProgramId
+}
+
+define_kernel_u32! {
+    /// Kernel BPF map ID.
+    MapId
+}
+
+define_kernel_u32! {
+    /// BPF link ID.
+    LinkId
+}
+
+define_kernel_u32! {
+    /// Kernel BTF object ID.
+    BtfId
+}
+
+define_kernel_u32! {
+    /// Network namespace inode number.
+    NamespaceId
+}
+
+define_kernel_u32! {
+    /// Size in bytes of a kernel object, such as a translated or JIT
+    /// compiled program.
+    ByteCount
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{prelude::*, sqlite::SqliteConnection};
+
+    use super::*;
+
+    table! {
+        id_test (id) {
+            id -> BigInt,
+            nsid -> Nullable<BigInt>,
+        }
+    }
+
+    #[derive(Debug, PartialEq, Queryable, Insertable)]
+    #[diesel(table_name = id_test)]
+    struct IdTest {
+        id: ProgramId,
+        nsid: Option<NamespaceId>,
+    }
+
+    fn setup() -> SqliteConnection {
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        diesel::sql_query("CREATE TABLE id_test (id INTEGER PRIMARY KEY NOT NULL, nsid BIGINT)")
+            .execute(&mut conn)
+            .unwrap();
+        conn
+    }
+
+    #[test]
+    /// Tests that the full u32 range, including values above
+    /// i32::MAX such as the nsid 4026533525 seen in bpfman dumps,
+    /// round-trips and orders numerically.
+    fn test_u32_range_roundtrip_and_order() {
+        let mut conn = setup();
+        let rows = vec![
+            IdTest {
+                id: ProgramId::from(u32::MAX),
+                nsid: Some(NamespaceId::from(4026533525)),
+            },
+            IdTest {
+                id: ProgramId::from(0),
+                nsid: None,
+            },
+            IdTest {
+                id: ProgramId::from(i32::MAX as u32 + 1),
+                nsid: Some(NamespaceId::from(4026531840)),
+            },
+        ];
+        diesel::insert_into(id_test::table)
+            .values(&rows)
+            .execute(&mut conn)
+            .unwrap();
+
+        let loaded: Vec<IdTest> = id_test::table
+            .order(id_test::id.asc())
+            .load(&mut conn)
+            .unwrap();
+        let ids: Vec<u32> = loaded.iter().map(|r| r.id.get()).collect();
+        assert_eq!(ids, vec![0, i32::MAX as u32 + 1, u32::MAX]);
+        assert_eq!(loaded[2].nsid, Some(NamespaceId::from(4026533525)));
+
+        let found: IdTest = id_test::table
+            .find(ProgramId::from(u32::MAX))
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(found.nsid.unwrap().get(), 4026533525);
+    }
+
+    #[test]
+    fn test_out_of_range_value_is_rejected() {
+        let mut conn = setup();
+        diesel::sql_query("INSERT INTO id_test (id, nsid) VALUES (1, -1), (2, 4294967296)")
+            .execute(&mut conn)
+            .unwrap();
+
+        let err = id_test::table
+            .find(1)
+            .first::<IdTest>(&mut conn)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("-1 is out of range for NamespaceId")
+        );
+        let err = id_test::table
+            .find(2)
+            .first::<IdTest>(&mut conn)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("4294967296 is out of range for NamespaceId")
+        );
+
+        assert_eq!(
+            MapId::try_from(-5i64),
+            Err(OutOfRangeError {
+                type_name: "MapId",
+                value: -5
+            })
+        );
+        assert_eq!(MapId::try_from(7u64), Ok(MapId::from(7)));
+        assert_eq!(
+            MapId::try_from(u64::MAX).unwrap_err().to_string(),
+            "18446744073709551615 is out of range for MapId"
+        );
+        assert_eq!("914".parse::<ProgramId>(), Ok(ProgramId::from(914)));
+        assert_eq!(serde_json::to_string(&LinkId::from(3)).unwrap(), "3");
+    }
+}
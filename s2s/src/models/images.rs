@@ -0,0 +1,413 @@
+//! The bytecode image cache.
+//!
+//! bpfman pulls program bytecode from OCI images and caches the
+//! manifest, config and bytecode layer so that later loads need not
+//! pull again. An [`Image`] is identified by the URL it was pulled
+//! from, which is the value [`BpfProgram::image_url`] refers to.
+//!
+//! Bytecode images declare the programs and maps they contain in the
+//! `io.ebpf.programs` and `io.ebpf.maps` config labels, each a JSON
+//! object mapping a name to its type. [`Image::create_record`] parses
+//! these into [`ImageProgram`] and [`ImageMap`] rows so they can be
+//! queried without decoding the config.
+
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use super::BpfProgram;
+use crate::schema::{image_maps, image_programs, images};
+
+/// Config label listing the programs in a bytecode image.
+pub const PROGRAMS_LABEL: &str = "io.ebpf.programs";
+
+/// Config label listing the maps in a bytecode image.
+pub const MAPS_LABEL: &str = "io.ebpf.maps";
+
+#[derive(Debug, Error)]
+pub enum ImageError {
+    #[error("database error: {0}")]
+    Database(#[from] diesel::result::Error),
+
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("invalid image: {0}")]
+    Invalid(String),
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Default,
+    Serialize,
+    Deserialize,
+    AsChangeset,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+)]
+#[diesel(primary_key(url))]
+#[diesel(table_name = images)]
+pub struct Image {
+    /// URL the image was pulled from.
+    pub url: String,
+
+    /// Config digest, e.g. `sha256:24c2...`.
+    pub digest: String,
+
+    /// Tag parsed from the URL, if any.
+    pub tag: Option<String>,
+
+    /// Timestamp when the image was pulled.
+    pub pulled_at: NaiveDateTime,
+
+    /// Raw OCI manifest JSON.
+    pub manifest: String,
+
+    /// Raw OCI image config JSON.
+    pub config: String,
+
+    /// Compressed bytecode layer. Serialised as base64.
+    #[serde(with = "super::base64_serde::option")]
+    pub layer: Option<Vec<u8>>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+    Associations,
+)]
+#[diesel(belongs_to(Image, foreign_key = image_url))]
+#[diesel(primary_key(image_url, name))]
+#[diesel(table_name = image_programs)]
+pub struct ImageProgram {
+    pub image_url: String,
+
+    /// Program (function) name.
+    pub name: String,
+
+    /// bpfman program type, e.g. `xdp`, `tc` or `probe`.
+    pub kind: String,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    Insertable,
+    Identifiable,
+    Selectable,
+    Queryable,
+    Associations,
+)]
+#[diesel(belongs_to(Image, foreign_key = image_url))]
+#[diesel(primary_key(image_url, name))]
+#[diesel(table_name = image_maps)]
+pub struct ImageMap {
+    pub image_url: String,
+
+    /// Map name.
+    pub name: String,
+
+    /// Map type, e.g. `per_cpu_array`.
+    pub map_type: String,
+}
+
+impl Image {
+    /// Builds an image from its pull URL and raw OCI manifest and
+    /// config, taking the digest from the manifest and the tag from
+    /// the URL. `pulled_at` is set to now.
+    pub fn from_oci(
+        url: &str,
+        manifest: &str,
+        config: &str,
+        layer: Option<Vec<u8>>,
+    ) -> Result<Image, ImageError> {
+        let parsed: Value = serde_json::from_str(manifest)?;
+        let digest = parsed
+            .pointer("/config/digest")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ImageError::Invalid("manifest has no config digest".to_string()))?;
+
+        // Validate the config up front so create_record cannot fail on
+        // it half way through.
+        serde_json::from_str::<Value>(config)?;
+
+        Ok(Image {
+            url: url.to_string(),
+            digest: digest.to_string(),
+            tag: tag_from_url(url),
+            pulled_at: Utc::now().naive_utc(),
+            manifest: manifest.to_string(),
+            config: config.to_string(),
+            layer,
+        })
+    }
+
+    /// Returns the parsed `io.ebpf.programs` label: program name to
+    /// bpfman program type. Empty if the label is absent.
+    pub fn program_labels(&self) -> Result<BTreeMap<String, String>, ImageError> {
+        self.label(PROGRAMS_LABEL)
+    }
+
+    /// Returns the parsed `io.ebpf.maps` label: map name to map type.
+    /// Empty if the label is absent.
+    pub fn map_labels(&self) -> Result<BTreeMap<String, String>, ImageError> {
+        self.label(MAPS_LABEL)
+    }
+
+    fn label(&self, label: &str) -> Result<BTreeMap<String, String>, ImageError> {
+        let config: Value = serde_json::from_str(&self.config)?;
+        match config
+            .pointer("/config/Labels")
+            .and_then(|labels| labels.get(label))
+        {
+            None => Ok(BTreeMap::new()),
+            Some(Value::String(json)) => Ok(serde_json::from_str(json)?),
+            Some(_) => Err(ImageError::Invalid(format!(
+                "label {label} is not a string"
+            ))),
+        }
+    }
+
+    /// Creates a new image record along with its program and map
+    /// label rows.
+    pub fn create_record(conn: &mut SqliteConnection, image: &Image) -> Result<Image, ImageError> {
+        let programs: Vec<ImageProgram> = image
+            .program_labels()?
+            .into_iter()
+            .map(|(name, kind)| ImageProgram {
+                image_url: image.url.clone(),
+                name,
+                kind,
+            })
+            .collect();
+        let maps: Vec<ImageMap> = image
+            .map_labels()?
+            .into_iter()
+            .map(|(name, map_type)| ImageMap {
+                image_url: image.url.clone(),
+                name,
+                map_type,
+            })
+            .collect();
+
+        let inserted = diesel::insert_into(images::table)
+            .values(image)
+            .returning(images::all_columns)
+            .get_result(conn)?;
+        diesel::insert_into(image_programs::table)
+            .values(&programs)
+            .execute(conn)?;
+        diesel::insert_into(image_maps::table)
+            .values(&maps)
+            .execute(conn)?;
+
+        Ok(inserted)
+    }
+
+    /// Returns all cached images.
+    pub fn find_all(conn: &mut SqliteConnection) -> QueryResult<Vec<Image>> {
+        images::table.order(images::url.asc()).load(conn)
+    }
+
+    /// Finds the image pulled from `url`.
+    pub fn find_by_url(conn: &mut SqliteConnection, url: &str) -> QueryResult<Image> {
+        images::table.find(url).first(conn)
+    }
+
+    /// Finds every cached image with the given config digest. More
+    /// than one URL can resolve to the same image.
+    pub fn find_by_digest(conn: &mut SqliteConnection, digest: &str) -> QueryResult<Vec<Image>> {
+        images::table
+            .filter(images::digest.eq(digest))
+            .order(images::url.asc())
+            .load(conn)
+    }
+
+    /// Deletes the image pulled from `url` and its label rows.
+    /// Returns true if a record was deleted.
+    pub fn delete_record(conn: &mut SqliteConnection, url: &str) -> QueryResult<bool> {
+        // The label rows cascade, but only on connections with
+        // foreign key enforcement enabled.
+        diesel::delete(image_programs::table.filter(image_programs::image_url.eq(url)))
+            .execute(conn)?;
+        diesel::delete(image_maps::table.filter(image_maps::image_url.eq(url))).execute(conn)?;
+        let num_deleted = diesel::delete(images::table.find(url)).execute(conn)?;
+
+        Ok(num_deleted > 0)
+    }
+
+    /// Returns the programs this image declares, ordered by name.
+    pub fn programs(&self, conn: &mut SqliteConnection) -> QueryResult<Vec<ImageProgram>> {
+        ImageProgram::belonging_to(self)
+            .order(image_programs::name.asc())
+            .load(conn)
+    }
+
+    /// Returns the maps this image declares, ordered by name.
+    pub fn maps(&self, conn: &mut SqliteConnection) -> QueryResult<Vec<ImageMap>> {
+        ImageMap::belonging_to(self)
+            .order(image_maps::name.asc())
+            .load(conn)
+    }
+}
+
+impl BpfProgram {
+    /// Returns the cached image this program was loaded from, if it
+    /// was loaded from an image and that image is in the cache.
+    pub fn image(&self, conn: &mut SqliteConnection) -> QueryResult<Option<Image>> {
+        match &self.image_url {
+            Some(url) => images::table.find(url).first(conn).optional(),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Returns the tag of an image reference such as
+/// `quay.io/bpfman-bytecode/go-xdp-counter:latest`. A `:` before the
+/// last `/` is a registry port, not a tag, and digest references
+/// (`...@sha256:...`) have no tag.
+fn tag_from_url(url: &str) -> Option<String> {
+    if url.contains('@') {
+        return None;
+    }
+    let name = url.rsplit('/').next().unwrap_or(url);
+    name.split_once(':').map(|(_, tag)| tag.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{establish_connection, models::LocationType};
+
+    const URL: &str = "quay.io/bpfman-bytecode/go-app-counter:latest";
+
+    const MANIFEST: &str = r#"{
+        "config": {
+            "digest": "sha256:24c28fb6352d8024fe8cb5c5bbe9c16b30dac3e47a317a1831a63d4cfb6d99cd",
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "size": 2879
+        },
+        "layers": [],
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "schemaVersion": 2
+    }"#;
+
+    const CONFIG: &str = r#"{
+        "architecture": "amd64",
+        "config": {
+            "Labels": {
+                "io.ebpf.maps": "{\"xdp_stats_map\":\"per_cpu_array\",\"tc_stats_map\":\"per_cpu_array\"}",
+                "io.ebpf.programs": "{\"xdp_stats\":\"xdp\",\"stats\":\"tc\",\"kprobe_counter\":\"probe\"}"
+            }
+        }
+    }"#;
+
+    #[test]
+    fn test_tag_from_url() {
+        assert_eq!(tag_from_url(URL).as_deref(), Some("latest"));
+        assert_eq!(
+            tag_from_url("localhost:5000/xdp-pass:v1").as_deref(),
+            Some("v1")
+        );
+        assert_eq!(tag_from_url("localhost:5000/xdp-pass"), None);
+        assert_eq!(tag_from_url("quay.io/x/y@sha256:abcd"), None);
+    }
+
+    #[test]
+    /// Tests that creating an image stores its parsed labels, that it
+    /// can be found by URL, by digest and from a program referring to
+    /// it, and that deleting it removes the label rows.
+    fn test_image_crud_and_labels() {
+        let mut conn = establish_connection(":memory:").unwrap();
+
+        let image = Image::from_oci(URL, MANIFEST, CONFIG, Some(vec![0x1f, 0x8b])).unwrap();
+        assert_eq!(
+            image.digest,
+            "sha256:24c28fb6352d8024fe8cb5c5bbe9c16b30dac3e47a317a1831a63d4cfb6d99cd"
+        );
+        assert_eq!(image.tag.as_deref(), Some("latest"));
+
+        let inserted = Image::create_record(&mut conn, &image).unwrap();
+        assert_eq!(inserted, image);
+        assert_eq!(Image::find_by_url(&mut conn, URL).unwrap(), image);
+        assert_eq!(
+            Image::find_by_digest(&mut conn, &image.digest).unwrap(),
+            vec![image.clone()]
+        );
+        assert!(
+            Image::find_by_digest(&mut conn, "sha256:00")
+                .unwrap()
+                .is_empty()
+        );
+
+        let programs: Vec<(String, String)> = image
+            .programs(&mut conn)
+            .unwrap()
+            .into_iter()
+            .map(|p| (p.name, p.kind))
+            .collect();
+        assert_eq!(
+            programs,
+            vec![
+                ("kprobe_counter".to_string(), "probe".to_string()),
+                ("stats".to_string(), "tc".to_string()),
+                ("xdp_stats".to_string(), "xdp".to_string()),
+            ]
+        );
+        let maps: Vec<String> = image
+            .maps(&mut conn)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.name)
+            .collect();
+        assert_eq!(maps, vec!["tc_stats_map", "xdp_stats_map"]);
+
+        let mut prog = BpfProgram {
+            name: "xdp_stats".to_string(),
+            location_type: LocationType::Image,
+            image_url: Some(URL.to_string()),
+            map_pin_path: "/run/bpfman/fs/maps/1".to_string(),
+            ..Default::default()
+        };
+        let prog = BpfProgram::create_record(&mut conn, &mut prog).unwrap();
+        assert_eq!(prog.image(&mut conn).unwrap(), Some(image.clone()));
+
+        assert!(Image::delete_record(&mut conn, URL).unwrap());
+        assert_eq!(prog.image(&mut conn).unwrap(), None);
+        assert!(image.programs(&mut conn).unwrap().is_empty());
+        assert!(image.maps(&mut conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_from_oci_rejects_invalid_manifest() {
+        assert!(matches!(
+            Image::from_oci(URL, r#"{"layers": []}"#, CONFIG, None),
+            Err(ImageError::Invalid(_))
+        ));
+        assert!(matches!(
+            Image::from_oci(URL, "not json", CONFIG, None),
+            Err(ImageError::Json(_))
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+//! The maps the kernel reports a program using.
+//!
+//! `bpf_program_maps` is the source of truth for which maps a program
+//! uses. The `kernel_map_ids` JSON column predates it and is still
+//! written alongside it, only by [`BpfProgram::record_kernel_maps`],
+//! but databases written before then may have the two disagree;
+//! [`check_kernel_maps`] reports where they do.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use diesel::prelude::*;
+
+use super::{BpfMap, BpfProgram, BpfProgramMap, MapId, ProgramId};
+use crate::schema::{bpf_program_maps, bpf_programs};
+
+/// A program whose `kernel_map_ids` column disagrees with its
+/// `bpf_program_maps` rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelMapMismatch {
+    pub program_id: ProgramId,
+
+    /// Map IDs listed in `kernel_map_ids` with no join row.
+    pub json_only: Vec<MapId>,
+
+    /// Map IDs with a join row that `kernel_map_ids` does not list.
+    pub join_only: Vec<MapId>,
+
+    /// Set if `kernel_map_ids` is not a JSON array of IDs, in which
+    /// case every join row is reported in `join_only`.
+    pub invalid_json: Option<String>,
+}
+
+impl std::fmt::Display for KernelMapMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ids = |ids: &[MapId]| {
+            let ids: Vec<String> = ids.iter().map(MapId::to_string).collect();
+            format!("[{}]", ids.join(", "))
+        };
+
+        write!(f, "program {}:", self.program_id)?;
+        if let Some(err) = &self.invalid_json {
+            write!(f, " invalid kernel_map_ids ({err})")?;
+        }
+        if !self.json_only.is_empty() {
+            write!(f, " kernel_map_ids only {}", ids(&self.json_only))?;
+        }
+        if !self.join_only.is_empty() {
+            write!(f, " bpf_program_maps only {}", ids(&self.join_only))?;
+        }
+        Ok(())
+    }
+}
+
+impl BpfProgram {
+    /// Records the maps the kernel reports this program using,
+    /// replacing any previously recorded. Writes both the
+    /// `bpf_program_maps` rows and the `kernel_map_ids` column, and
+    /// creates a placeholder `bpf_maps` row, named after its ID, for
+    /// any map not already known.
+    ///
+    /// The program must already exist.
+    pub fn record_kernel_maps(
+        &mut self,
+        conn: &mut SqliteConnection,
+        map_ids: &[MapId],
+    ) -> QueryResult<()> {
+        let map_ids: BTreeSet<MapId> = map_ids.iter().copied().collect();
+
+        diesel::delete(bpf_program_maps::table.filter(bpf_program_maps::program_id.eq(self.id)))
+            .execute(conn)?;
+
+        for &map_id in &map_ids {
+            if BpfMap::find_record(conn, map_id).optional()?.is_none() {
+                let mut map = BpfMap {
+                    id: map_id,
+                    name: map_id.to_string(),
+                    ..Default::default()
+                };
+                BpfMap::create_record(conn, &mut map)?;
+            }
+            BpfProgramMap::create_record(conn, self.id, map_id)?;
+        }
+
+        self.kernel_map_ids = serde_json::to_string(&map_ids).expect("id list serialises");
+        diesel::update(bpf_programs::table.find(self.id))
+            .set(bpf_programs::kernel_map_ids.eq(&self.kernel_map_ids))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Returns the maps the kernel reports this program using, from
+    /// `bpf_program_maps`, ordered by ID.
+    pub fn kernel_maps(&self, conn: &mut SqliteConnection) -> QueryResult<Vec<BpfMap>> {
+        self.maps(conn)
+    }
+}
+
+/// Compares every program's `kernel_map_ids` column with its
+/// `bpf_program_maps` rows and returns the programs where they differ,
+/// ordered by program ID.
+pub fn check_kernel_maps(conn: &mut SqliteConnection) -> QueryResult<Vec<KernelMapMismatch>> {
+    let programs: Vec<(ProgramId, String)> = bpf_programs::table
+        .select((bpf_programs::id, bpf_programs::kernel_map_ids))
+        .order(bpf_programs::id.asc())
+        .load(conn)?;
+
+    let mut joined: BTreeMap<ProgramId, BTreeSet<MapId>> = BTreeMap::new();
+    for (program_id, map_id) in bpf_program_maps::table
+        .select((bpf_program_maps::program_id, bpf_program_maps::map_id))
+        .load::<(ProgramId, MapId)>(conn)?
+    {
+        joined.entry(program_id).or_default().insert(map_id);
+    }
+
+    let mut mismatches = Vec::new();
+    for (program_id, json) in programs {
+        let rows = joined.remove(&program_id).unwrap_or_default();
+        let (listed, invalid_json) = match serde_json::from_str::<BTreeSet<MapId>>(&json) {
+            Ok(listed) => (listed, None),
+            Err(e) => (BTreeSet::new(), Some(e.to_string())),
+        };
+
+        let json_only: Vec<MapId> = listed.difference(&rows).copied().collect();
+        let join_only: Vec<MapId> = rows.difference(&listed).copied().collect();
+        if invalid_json.is_some() || !json_only.is_empty() || !join_only.is_empty() {
+            mismatches.push(KernelMapMismatch {
+                program_id,
+                json_only,
+                join_only,
+                invalid_json,
+            });
+        }
+    }
+
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{establish_connection, models::LocationType};
+
+    fn insert_program(conn: &mut SqliteConnection, id: u32) -> BpfProgram {
+        let mut prog = BpfProgram {
+            id: id.into(),
+            name: format!("prog_{id}"),
+            location_type: LocationType::File,
+            file_path: Some("/path/to/prog.o".to_string()),
+            map_pin_path: format!("/run/bpfman/fs/maps/{id}"),
+            ..Default::default()
+        };
+        BpfProgram::create_record(conn, &mut prog).unwrap()
+    }
+
+    fn map_ids(ids: &[u32]) -> Vec<MapId> {
+        ids.iter().copied().map(MapId::from).collect()
+    }
+
+    #[test]
+    /// Tests that recording kernel maps writes matching join rows and
+    /// JSON, creates placeholder maps, and replaces earlier records.
+    fn test_record_kernel_maps() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        let mut prog = insert_program(&mut conn, 914);
+
+        prog.record_kernel_maps(&mut conn, &map_ids(&[1415, 1414, 1414]))
+            .unwrap();
+        assert_eq!(prog.kernel_map_ids, "[1414,1415]");
+
+        let maps = prog.kernel_maps(&mut conn).unwrap();
+        let ids: Vec<MapId> = maps.iter().map(|m| m.id).collect();
+        assert_eq!(ids, map_ids(&[1414, 1415]));
+        assert_eq!(maps[0].name, "1414");
+        assert_eq!(
+            BpfProgram::find_record(&mut conn, 914.into())
+                .unwrap()
+                .kernel_map_ids,
+            "[1414,1415]"
+        );
+
+        prog.kernel_map_ids = "[1]".to_string();
+        prog = prog.update_record(&mut conn).unwrap();
+        assert_eq!(prog.kernel_map_ids, "[1414,1415]");
+
+        prog.record_kernel_maps(&mut conn, &map_ids(&[1416]))
+            .unwrap();
+        let ids: Vec<MapId> = prog
+            .kernel_maps(&mut conn)
+            .unwrap()
+            .iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(ids, map_ids(&[1416]));
+        assert!(check_kernel_maps(&mut conn).unwrap().is_empty());
+    }
+
+    #[test]
+    /// Tests that the checker reports IDs only in the JSON, IDs only
+    /// in the join table, and unparseable JSON.
+    fn test_check_kernel_maps() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        let mut consistent = insert_program(&mut conn, 1);
+        consistent
+            .record_kernel_maps(&mut conn, &map_ids(&[10]))
+            .unwrap();
+
+        let mut diverged = insert_program(&mut conn, 2);
+        diverged
+            .record_kernel_maps(&mut conn, &map_ids(&[10, 20]))
+            .unwrap();
+        diesel::update(bpf_programs::table.find(ProgramId::from(2)))
+            .set(bpf_programs::kernel_map_ids.eq("[20,30]"))
+            .execute(&mut conn)
+            .unwrap();
+
+        let mut corrupt = insert_program(&mut conn, 3);
+        corrupt
+            .record_kernel_maps(&mut conn, &map_ids(&[10]))
+            .unwrap();
+        diesel::update(bpf_programs::table.find(ProgramId::from(3)))
+            .set(bpf_programs::kernel_map_ids.eq("not json"))
+            .execute(&mut conn)
+            .unwrap();
+
+        let mismatches = check_kernel_maps(&mut conn).unwrap();
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(
+            mismatches[0],
+            KernelMapMismatch {
+                program_id: 2.into(),
+                json_only: map_ids(&[30]),
+                join_only: map_ids(&[10]),
+                invalid_json: None,
+            }
+        );
+        assert_eq!(mismatches[1].program_id, 3.into());
+        assert_eq!(mismatches[1].join_only, map_ids(&[10]));
+        assert!(mismatches[1].invalid_json.is_some());
+        assert_eq!(
+            mismatches[0].to_string(),
+            "program 2: kernel_map_ids only [30] bpf_program_maps only [10]"
+        );
+    }
+}
@@ -0,0 +1,678 @@
+//! Program and link lifecycle transitions.
+//!
+//! A program is `pre_load` until the kernel loads it, then `loaded`
+//! until it is unloaded; a link is `pre_attach` until it is attached,
+//! then `attached` until it is detached. The methods here are the only
+//! transitions between those states: each checks the state stored in
+//! the database, not the caller's copy, and fails with a
+//! [`TransitionError`] if it does not allow the transition. Loading
+//! and unloading populate and clear the `kernel_*` columns together,
+//! so a loaded program always has its kernel information and a
+//! pre-load program never does.
+//!
+//! `update_record` leaves `state` and the `kernel_*` columns as
+//! stored, so these methods are the only way to change them. The
+//! schema rejects any other write, including an insert, that gives a
+//! loaded program no `kernel_name` or a pre-load program any kernel
+//! information.
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{dsl::exists, prelude::*};
+use thiserror::Error;
+
+use super::{
+    BpfLink, BpfProgram, BtfId, ByteCount, LinkId, LinkState, MapId, ProgramId, ProgramState,
+};
+use crate::schema::{bpf_links, bpf_programs};
+
+#[derive(Debug, Error)]
+pub enum TransitionError {
+    #[error("database error: {0}")]
+    Database(#[from] diesel::result::Error),
+
+    #[error("program {id} is {actual}, not {expected}")]
+    ProgramState {
+        id: ProgramId,
+        expected: ProgramState,
+        actual: ProgramState,
+    },
+
+    #[error("link {id} is {actual}, not {expected}")]
+    LinkState {
+        id: LinkId,
+        expected: LinkState,
+        actual: LinkState,
+    },
+
+    #[error("link {link_id} cannot be attached: program {program_id} is not loaded")]
+    ProgramNotLoaded {
+        link_id: LinkId,
+        program_id: ProgramId,
+    },
+
+    #[error("program {id} has attached links: {}", join_ids(links))]
+    AttachedLinks { id: ProgramId, links: Vec<LinkId> },
+}
+
+fn join_ids(ids: &[LinkId]) -> String {
+    let ids: Vec<String> = ids.iter().map(LinkId::to_string).collect();
+    ids.join(", ")
+}
+
+/// What the kernel reports about a program once it is loaded.
+///
+/// The kernel always reports the first four fields; the others are
+/// missing on older kernels.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KernelInfo {
+    pub name: String,
+    pub program_type: i32,
+    pub loaded_at: String,
+    pub tag: String,
+    pub gpl_compatible: Option<bool>,
+    pub btf_id: Option<BtfId>,
+    pub bytes_xlated: Option<ByteCount>,
+    pub jited: Option<bool>,
+    pub bytes_jited: Option<ByteCount>,
+    pub verified_insns: Option<i32>,
+    pub bytes_memlock: Option<ByteCount>,
+
+    /// The maps the program uses, recorded with
+    /// [`BpfProgram::record_kernel_maps`].
+    pub map_ids: Vec<MapId>,
+}
+
+/// The state and kernel columns, written together. `None` writes
+/// NULL, so the changeset for a pre-load program clears them all.
+#[derive(AsChangeset)]
+#[diesel(table_name = bpf_programs, treat_none_as_null = true)]
+struct KernelColumns<'a> {
+    state: ProgramState,
+    kernel_name: Option<&'a str>,
+    kernel_program_type: Option<i32>,
+    kernel_loaded_at: Option<&'a str>,
+    kernel_tag: Option<&'a str>,
+    kernel_gpl_compatible: Option<bool>,
+    kernel_btf_id: Option<BtfId>,
+    kernel_bytes_xlated: Option<ByteCount>,
+    kernel_jited: Option<bool>,
+    kernel_bytes_jited: Option<ByteCount>,
+    kernel_verified_insns: Option<i32>,
+    kernel_bytes_memlock: Option<ByteCount>,
+    updated_at: NaiveDateTime,
+}
+
+impl<'a> KernelColumns<'a> {
+    fn loaded(info: &'a KernelInfo) -> Self {
+        KernelColumns {
+            state: ProgramState::Loaded,
+            kernel_name: Some(&info.name),
+            kernel_program_type: Some(info.program_type),
+            kernel_loaded_at: Some(&info.loaded_at),
+            kernel_tag: Some(&info.tag),
+            kernel_gpl_compatible: info.gpl_compatible,
+            kernel_btf_id: info.btf_id,
+            kernel_bytes_xlated: info.bytes_xlated,
+            kernel_jited: info.jited,
+            kernel_bytes_jited: info.bytes_jited,
+            kernel_verified_insns: info.verified_insns,
+            kernel_bytes_memlock: info.bytes_memlock,
+            updated_at: Utc::now().naive_utc(),
+        }
+    }
+
+    fn unloaded() -> Self {
+        KernelColumns {
+            state: ProgramState::PreLoad,
+            kernel_name: None,
+            kernel_program_type: None,
+            kernel_loaded_at: None,
+            kernel_tag: None,
+            kernel_gpl_compatible: None,
+            kernel_btf_id: None,
+            kernel_bytes_xlated: None,
+            kernel_jited: None,
+            kernel_bytes_jited: None,
+            kernel_verified_insns: None,
+            kernel_bytes_memlock: None,
+            updated_at: Utc::now().naive_utc(),
+        }
+    }
+}
+
+impl BpfProgram {
+    /// Moves a pre-load program to loaded, recording what the kernel
+    /// reports about it, including its maps. Updates `self` and
+    /// returns the stored record.
+    ///
+    /// The program and its maps are written in one transaction, so a
+    /// failure leaves both as they were.
+    pub fn mark_loaded(
+        &mut self,
+        conn: &mut SqliteConnection,
+        info: &KernelInfo,
+    ) -> Result<BpfProgram, TransitionError> {
+        conn.transaction(|conn| {
+            let updated = diesel::update(
+                bpf_programs::table
+                    .find(self.id)
+                    .filter(bpf_programs::state.eq(ProgramState::PreLoad)),
+            )
+            .set(KernelColumns::loaded(info))
+            .get_result::<BpfProgram>(conn)
+            .optional()?;
+            let Some(mut program) = updated else {
+                return Err(TransitionError::ProgramState {
+                    id: self.id,
+                    expected: ProgramState::PreLoad,
+                    actual: self.stored_state(conn)?,
+                });
+            };
+
+            program.record_kernel_maps(conn, &info.map_ids)?;
+            *self = BpfProgram::find_record(conn, self.id)?;
+            Ok(self.clone())
+        })
+    }
+
+    /// Moves a loaded program back to pre-load, clearing its kernel
+    /// information and maps. Fails if any of its links are still
+    /// attached. Updates `self` and returns the stored record.
+    ///
+    /// The program and its maps are written in one transaction, so a
+    /// failure leaves both as they were.
+    pub fn unload(&mut self, conn: &mut SqliteConnection) -> Result<BpfProgram, TransitionError> {
+        conn.transaction(|conn| {
+            let attached_links = bpf_links::table
+                .filter(bpf_links::program_id.eq(self.id))
+                .filter(bpf_links::state.eq(LinkState::Attached));
+
+            let updated = diesel::update(
+                bpf_programs::table
+                    .find(self.id)
+                    .filter(bpf_programs::state.eq(ProgramState::Loaded))
+                    .filter(diesel::dsl::not(exists(attached_links))),
+            )
+            .set(KernelColumns::unloaded())
+            .get_result::<BpfProgram>(conn)
+            .optional()?;
+            let Some(mut program) = updated else {
+                let actual = self.stored_state(conn)?;
+                if actual != ProgramState::Loaded {
+                    return Err(TransitionError::ProgramState {
+                        id: self.id,
+                        expected: ProgramState::Loaded,
+                        actual,
+                    });
+                }
+                let links = attached_links
+                    .select(bpf_links::id)
+                    .order(bpf_links::id.asc())
+                    .load(conn)?;
+                return Err(TransitionError::AttachedLinks { id: self.id, links });
+            };
+
+            program.record_kernel_maps(conn, &[])?;
+            *self = BpfProgram::find_record(conn, self.id)?;
+            Ok(self.clone())
+        })
+    }
+
+    /// Returns the program's state as stored, which may differ from
+    /// `self.state`.
+    fn stored_state(&self, conn: &mut SqliteConnection) -> QueryResult<ProgramState> {
+        bpf_programs::table
+            .find(self.id)
+            .select(bpf_programs::state)
+            .first(conn)
+    }
+}
+
+impl BpfLink {
+    /// Moves a pre-attach link to attached. Fails if its program is
+    /// not loaded. Updates `self` and returns the stored record.
+    pub fn mark_attached(
+        &mut self,
+        conn: &mut SqliteConnection,
+    ) -> Result<BpfLink, TransitionError> {
+        let program_loaded = bpf_programs::table
+            .filter(bpf_programs::id.eq(bpf_links::program_id))
+            .filter(bpf_programs::state.eq(ProgramState::Loaded));
+
+        let updated = diesel::update(
+            bpf_links::table
+                .find(self.id)
+                .filter(bpf_links::state.eq(LinkState::PreAttach))
+                .filter(exists(program_loaded)),
+        )
+        .set((
+            bpf_links::state.eq(LinkState::Attached),
+            bpf_links::updated_at.eq(Utc::now().naive_utc()),
+        ))
+        .get_result::<BpfLink>(conn)
+        .optional()?;
+
+        match updated {
+            Some(link) => {
+                *self = link;
+                Ok(self.clone())
+            }
+            None => {
+                let stored = BpfLink::find_record(conn, self.id)?;
+                if stored.state != LinkState::PreAttach {
+                    return Err(TransitionError::LinkState {
+                        id: self.id,
+                        expected: LinkState::PreAttach,
+                        actual: stored.state,
+                    });
+                }
+                Err(TransitionError::ProgramNotLoaded {
+                    link_id: self.id,
+                    program_id: stored.program_id,
+                })
+            }
+        }
+    }
+
+    /// Moves an attached link back to pre-attach. Updates `self` and
+    /// returns the stored record.
+    pub fn detach(&mut self, conn: &mut SqliteConnection) -> Result<BpfLink, TransitionError> {
+        let updated = diesel::update(
+            bpf_links::table
+                .find(self.id)
+                .filter(bpf_links::state.eq(LinkState::Attached)),
+        )
+        .set((
+            bpf_links::state.eq(LinkState::PreAttach),
+            bpf_links::updated_at.eq(Utc::now().naive_utc()),
+        ))
+        .get_result::<BpfLink>(conn)
+        .optional()?;
+
+        match updated {
+            Some(link) => {
+                *self = link;
+                Ok(self.clone())
+            }
+            None => Err(TransitionError::LinkState {
+                id: self.id,
+                expected: LinkState::Attached,
+                actual: BpfLink::find_record(conn, self.id)?.state,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        establish_connection,
+        models::{BpfMap, LocationType, ProgramKind, check_kernel_maps},
+    };
+
+    fn kernel_info() -> KernelInfo {
+        KernelInfo {
+            name: "xdp_pass".to_string(),
+            program_type: 6,
+            loaded_at: "2025-02-17T19:14:27+0000".to_string(),
+            tag: "4b9d1b2c140e87ce".to_string(),
+            gpl_compatible: Some(true),
+            btf_id: Some(154.into()),
+            bytes_xlated: Some(96.into()),
+            jited: Some(true),
+            bytes_jited: Some(67.into()),
+            verified_insns: Some(9),
+            bytes_memlock: Some(4096.into()),
+            map_ids: vec![30.into(), 31.into()],
+        }
+    }
+
+    /// Returns the kernel columns of a program, which must be all set
+    /// when it is loaded and all clear when it is not.
+    fn kernel_columns(program: &BpfProgram) -> [bool; 11] {
+        [
+            program.kernel_name.is_some(),
+            program.kernel_program_type.is_some(),
+            program.kernel_loaded_at.is_some(),
+            program.kernel_tag.is_some(),
+            program.kernel_gpl_compatible.is_some(),
+            program.kernel_btf_id.is_some(),
+            program.kernel_bytes_xlated.is_some(),
+            program.kernel_jited.is_some(),
+            program.kernel_bytes_jited.is_some(),
+            program.kernel_verified_insns.is_some(),
+            program.kernel_bytes_memlock.is_some(),
+        ]
+    }
+
+    /// Inserts program 1 in `program_state` with link 10 in
+    /// `link_state`, writing the states directly.
+    fn setup(
+        program_state: ProgramState,
+        link_state: LinkState,
+    ) -> (SqliteConnection, BpfProgram, BpfLink) {
+        let mut conn = establish_connection(":memory:").unwrap();
+        let mut program = BpfProgram {
+            id: 1.into(),
+            name: "xdp_pass".to_string(),
+            kind: ProgramKind::Xdp,
+            location_type: LocationType::File,
+            file_path: Some("/path/to/xdp_pass.o".to_string()),
+            map_pin_path: "/run/bpfman/fs/maps/1".to_string(),
+            ..Default::default()
+        };
+        let mut program = BpfProgram::create_record(&mut conn, &mut program).unwrap();
+        if program_state == ProgramState::Loaded {
+            program.mark_loaded(&mut conn, &kernel_info()).unwrap();
+        }
+        let mut link = BpfLink {
+            id: 10.into(),
+            program_id: program.id,
+            link_type: Some(ProgramKind::Xdp),
+            state: link_state,
+            ..Default::default()
+        };
+        let link = BpfLink::create_record(&mut conn, &mut link).unwrap();
+        (conn, program, link)
+    }
+
+    #[test]
+    fn test_load_and_unload() {
+        let (mut conn, mut program, _) = setup(ProgramState::PreLoad, LinkState::PreAttach);
+        assert_eq!(kernel_columns(&program), [false; 11]);
+
+        let loaded = program.mark_loaded(&mut conn, &kernel_info()).unwrap();
+        assert_eq!(loaded, program);
+        assert_eq!(
+            loaded,
+            BpfProgram::find_record(&mut conn, 1.into()).unwrap()
+        );
+        assert_eq!(loaded.state, ProgramState::Loaded);
+        assert_eq!(kernel_columns(&loaded), [true; 11]);
+        assert_eq!(loaded.kernel_tag.as_deref(), Some("4b9d1b2c140e87ce"));
+        assert_eq!(loaded.kernel_map_ids, "[30,31]");
+        assert_eq!(loaded.maps(&mut conn).unwrap().len(), 2);
+
+        let unloaded = program.unload(&mut conn).unwrap();
+        assert_eq!(unloaded, program);
+        assert_eq!(
+            unloaded,
+            BpfProgram::find_record(&mut conn, 1.into()).unwrap()
+        );
+        assert_eq!(unloaded.state, ProgramState::PreLoad);
+        assert_eq!(kernel_columns(&unloaded), [false; 11]);
+        assert_eq!(unloaded.kernel_map_ids, "[]");
+        assert!(unloaded.maps(&mut conn).unwrap().is_empty());
+        assert!(check_kernel_maps(&mut conn).unwrap().is_empty());
+
+        // The maps themselves outlive the program's use of them.
+        assert!(BpfMap::find_record(&mut conn, 30.into()).is_ok());
+
+        // Optional kernel information stays unset if not reported.
+        let info = KernelInfo {
+            gpl_compatible: None,
+            verified_insns: None,
+            ..kernel_info()
+        };
+        let reloaded = program.mark_loaded(&mut conn, &info).unwrap();
+        assert!(reloaded.kernel_name.is_some());
+        assert_eq!(reloaded.kernel_gpl_compatible, None);
+        assert_eq!(reloaded.kernel_verified_insns, None);
+    }
+
+    #[test]
+    /// Tests that loading and unloading write the program and its maps
+    /// together, leaving both as they were if the maps fail.
+    fn test_load_and_unload_are_atomic() {
+        use diesel::connection::SimpleConnection;
+
+        let (mut conn, mut program, _) = setup(ProgramState::PreLoad, LinkState::PreAttach);
+        conn.batch_execute(
+            "CREATE TRIGGER fail_map_insert BEFORE INSERT ON bpf_program_maps \
+             BEGIN SELECT RAISE(ABORT, 'map insert failed'); END",
+        )
+        .unwrap();
+        let err = program.mark_loaded(&mut conn, &kernel_info()).unwrap_err();
+        assert!(err.to_string().contains("map insert failed"), "{err}");
+        let stored = BpfProgram::find_record(&mut conn, 1.into()).unwrap();
+        assert_eq!(stored.state, ProgramState::PreLoad);
+        assert_eq!(kernel_columns(&stored), [false; 11]);
+
+        conn.batch_execute(
+            "DROP TRIGGER fail_map_insert; \
+             CREATE TRIGGER fail_map_delete BEFORE DELETE ON bpf_program_maps \
+             BEGIN SELECT RAISE(ABORT, 'map delete failed'); END",
+        )
+        .unwrap();
+        program.mark_loaded(&mut conn, &kernel_info()).unwrap();
+        let err = program.unload(&mut conn).unwrap_err();
+        assert!(err.to_string().contains("map delete failed"), "{err}");
+        let stored = BpfProgram::find_record(&mut conn, 1.into()).unwrap();
+        assert_eq!(stored.state, ProgramState::Loaded);
+        assert_eq!(kernel_columns(&stored), [true; 11]);
+        assert_eq!(stored.maps(&mut conn).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_attach_and_detach() {
+        let (mut conn, _, mut link) = setup(ProgramState::Loaded, LinkState::PreAttach);
+
+        let attached = link.mark_attached(&mut conn).unwrap();
+        assert_eq!(attached, link);
+        assert_eq!(
+            attached,
+            BpfLink::find_record(&mut conn, 10.into()).unwrap()
+        );
+        assert_eq!(attached.state, LinkState::Attached);
+
+        let detached = link.detach(&mut conn).unwrap();
+        assert_eq!(detached, link);
+        assert_eq!(detached.state, LinkState::PreAttach);
+        assert_eq!(
+            detached,
+            BpfLink::find_record(&mut conn, 10.into()).unwrap()
+        );
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    enum Transition {
+        MarkLoaded,
+        Unload,
+        MarkAttached,
+        Detach,
+    }
+
+    #[test]
+    /// Tests every transition from every combination of program and
+    /// link state, checking that legal ones move only the expected
+    /// state and illegal ones change nothing.
+    fn test_all_transitions() {
+        use LinkState::{Attached, PreAttach};
+        use ProgramState::{Loaded, PreLoad};
+        use Transition::*;
+
+        for program_state in ProgramState::ALL.iter().copied() {
+            for link_state in LinkState::ALL.iter().copied() {
+                for transition in [MarkLoaded, Unload, MarkAttached, Detach] {
+                    let case = format!("{transition:?} from {program_state}/{link_state}");
+                    let (mut conn, mut program, mut link) = setup(program_state, link_state);
+
+                    let result = match transition {
+                        MarkLoaded => program.mark_loaded(&mut conn, &kernel_info()).map(drop),
+                        Unload => program.unload(&mut conn).map(drop),
+                        MarkAttached => link.mark_attached(&mut conn).map(drop),
+                        Detach => link.detach(&mut conn).map(drop),
+                    };
+
+                    let expected = match (transition, program_state, link_state) {
+                        (MarkLoaded, PreLoad, _) => Ok((Loaded, link_state)),
+                        (Unload, Loaded, PreAttach) => Ok((PreLoad, PreAttach)),
+                        (MarkAttached, Loaded, PreAttach) => Ok((Loaded, Attached)),
+                        (Detach, _, Attached) => Ok((program_state, PreAttach)),
+                        (MarkLoaded, Loaded, _) => {
+                            Err("program 1 is loaded, not pre_load".to_string())
+                        }
+                        (Unload, PreLoad, _) => {
+                            Err("program 1 is pre_load, not loaded".to_string())
+                        }
+                        (Unload, Loaded, Attached) => {
+                            Err("program 1 has attached links: 10".to_string())
+                        }
+                        (MarkAttached, _, Attached) => {
+                            Err("link 10 is attached, not pre_attach".to_string())
+                        }
+                        (MarkAttached, PreLoad, PreAttach) => {
+                            Err("link 10 cannot be attached: program 1 is not loaded".to_string())
+                        }
+                        (Detach, _, PreAttach) => {
+                            Err("link 10 is pre_attach, not attached".to_string())
+                        }
+                    };
+
+                    let program = BpfProgram::find_record(&mut conn, 1.into()).unwrap();
+                    let link = BpfLink::find_record(&mut conn, 10.into()).unwrap();
+                    match expected {
+                        Ok((program_state, link_state)) => {
+                            result.unwrap_or_else(|err| panic!("{case}: {err}"));
+                            assert_eq!(program.state, program_state, "{case}");
+                            assert_eq!(link.state, link_state, "{case}");
+                        }
+                        Err(message) => {
+                            let err = result.expect_err(&case);
+                            assert_eq!(err.to_string(), message, "{case}");
+                            assert_eq!(program.state, program_state, "{case}");
+                            assert_eq!(link.state, link_state, "{case}");
+                        }
+                    }
+                    let loaded = program.state == Loaded;
+                    assert_eq!(kernel_columns(&program), [loaded; 11], "{case}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    /// Tests that transitions check the stored state rather than the
+    /// caller's possibly stale copy, and report missing records.
+    fn test_transitions_use_stored_state() {
+        let (mut conn, program, link) = setup(ProgramState::PreLoad, LinkState::PreAttach);
+
+        let mut stale = program.clone();
+        stale.state = ProgramState::Loaded;
+        assert!(matches!(
+            stale.clone().unload(&mut conn),
+            Err(TransitionError::ProgramState {
+                actual: ProgramState::PreLoad,
+                ..
+            })
+        ));
+        program
+            .clone()
+            .mark_loaded(&mut conn, &kernel_info())
+            .unwrap();
+        assert!(matches!(
+            program.clone().mark_loaded(&mut conn, &kernel_info()),
+            Err(TransitionError::ProgramState {
+                actual: ProgramState::Loaded,
+                ..
+            })
+        ));
+
+        link.clone().mark_attached(&mut conn).unwrap();
+        assert!(matches!(
+            link.clone().mark_attached(&mut conn),
+            Err(TransitionError::LinkState {
+                actual: LinkState::Attached,
+                ..
+            })
+        ));
+
+        let mut missing = program.clone();
+        missing.id = 2.into();
+        assert!(matches!(
+            missing.mark_loaded(&mut conn, &kernel_info()),
+            Err(TransitionError::Database(diesel::result::Error::NotFound))
+        ));
+        let mut missing = link.clone();
+        missing.id = 11.into();
+        assert!(matches!(
+            missing.detach(&mut conn),
+            Err(TransitionError::Database(diesel::result::Error::NotFound))
+        ));
+    }
+
+    #[test]
+    /// Tests that `update_record` leaves the stored state and kernel
+    /// information alone.
+    fn test_update_record_keeps_state() {
+        let (mut conn, mut program, mut link) = setup(ProgramState::PreLoad, LinkState::PreAttach);
+
+        program.state = ProgramState::Loaded;
+        program.kernel_name = Some("bogus".to_string());
+        program.kernel_btf_id = Some(7.into());
+        program.description = Some("updated".to_string());
+        let updated = program.update_record(&mut conn).unwrap();
+        assert_eq!(updated.state, ProgramState::PreLoad);
+        assert_eq!(kernel_columns(&updated), [false; 11]);
+        assert_eq!(updated.description.as_deref(), Some("updated"));
+
+        let mut loaded = BpfProgram::find_record(&mut conn, 1.into()).unwrap();
+        loaded.mark_loaded(&mut conn, &kernel_info()).unwrap();
+        loaded.state = ProgramState::PreLoad;
+        loaded.kernel_name = None;
+        let updated = loaded.update_record(&mut conn).unwrap();
+        assert_eq!(updated.state, ProgramState::Loaded);
+        assert_eq!(updated.kernel_name.as_deref(), Some("xdp_pass"));
+
+        link.state = LinkState::Attached;
+        let updated = link.update_record(&mut conn).unwrap();
+        assert_eq!(updated.state, LinkState::PreAttach);
+    }
+
+    #[test]
+    /// Tests that the schema rejects a program whose state and kernel
+    /// information disagree, however it is written.
+    fn test_state_matches_kernel_info() {
+        use diesel::connection::SimpleConnection;
+
+        let (mut conn, program, _) = setup(ProgramState::PreLoad, LinkState::PreAttach);
+        let insert = |conn: &mut SqliteConnection, id: u32, state, kernel_name: Option<&str>| {
+            let mut program = BpfProgram {
+                id: id.into(),
+                state,
+                kernel_name: kernel_name.map(str::to_string),
+                map_pin_path: format!("/run/bpfman/fs/maps/{id}"),
+                ..program.clone()
+            };
+            BpfProgram::create_record(conn, &mut program)
+        };
+
+        for (state, kernel_name) in [
+            (ProgramState::Loaded, None),
+            (ProgramState::PreLoad, Some("xdp_pass")),
+        ] {
+            let err = insert(&mut conn, 2, state, kernel_name).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("program state does not match its kernel information"),
+                "{state}: {err}"
+            );
+        }
+        insert(&mut conn, 2, ProgramState::Loaded, Some("xdp_pass")).unwrap();
+
+        for sql in [
+            "UPDATE bpf_programs SET state = 'loaded' WHERE id = 1",
+            "UPDATE bpf_programs SET kernel_tag = 'abc' WHERE id = 1",
+            "UPDATE bpf_programs SET kernel_name = NULL WHERE id = 2",
+            "UPDATE bpf_programs SET state = 'pre_load' WHERE id = 2",
+        ] {
+            let err = conn.batch_execute(sql).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("program state does not match its kernel information"),
+                "{sql}: {err}"
+            );
+        }
+    }
+}
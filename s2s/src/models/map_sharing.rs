@@ -0,0 +1,209 @@
+//! Map ownership and sharing between programs.
+//!
+//! bpfman lets a program reuse the maps of another program instead of
+//! creating its own. The program whose maps are reused is the map
+//! owner; the programs reusing them are its dependants, and record the
+//! owner in `map_owner_id`. Ownership is one level deep, and an owner
+//! cannot be deleted while it has dependants. The schema enforces both
+//! rules with triggers, so violating them through any API fails with a
+//! database error.
+
+use std::collections::BTreeMap;
+
+use diesel::prelude::*;
+
+use super::{BpfProgram, ProgramId};
+use crate::schema::bpf_programs;
+
+/// A map owner and the programs using its maps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapSharing {
+    /// The program that owns the maps.
+    pub owner: ProgramId,
+
+    /// The programs using the owner's maps, in ID order.
+    pub dependants: Vec<ProgramId>,
+}
+
+impl BpfProgram {
+    /// Returns true if this program owns its maps, i.e. it does not
+    /// use another program's maps.
+    pub fn owns_maps(&self) -> bool {
+        self.map_owner_id.is_none()
+    }
+
+    /// Returns the program whose maps this program uses, if any.
+    pub fn map_owner(&self, conn: &mut SqliteConnection) -> QueryResult<Option<BpfProgram>> {
+        match self.map_owner_id {
+            Some(owner_id) => BpfProgram::find_record(conn, owner_id).optional(),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the programs using this program's maps, ordered by ID.
+    pub fn map_dependants(&self, conn: &mut SqliteConnection) -> QueryResult<Vec<BpfProgram>> {
+        bpf_programs::table
+            .filter(bpf_programs::map_owner_id.eq(self.id))
+            .order(bpf_programs::id.asc())
+            .load(conn)
+    }
+
+    /// Makes `program_id` use the maps of `owner_id`, or own its maps
+    /// again if `owner_id` is `None`.
+    ///
+    /// Fails if the owner does not exist, is itself a dependant, or if
+    /// `program_id` has dependants of its own.
+    pub fn set_map_owner(
+        conn: &mut SqliteConnection,
+        program_id: ProgramId,
+        owner_id: Option<ProgramId>,
+    ) -> QueryResult<BpfProgram> {
+        diesel::update(bpf_programs::table.find(program_id))
+            .set(bpf_programs::map_owner_id.eq(owner_id))
+            .get_result(conn)
+    }
+
+    /// Returns the full map sharing graph: every program that owns
+    /// maps used by at least one other program, with those programs.
+    /// Owners are in ID order.
+    pub fn map_sharing_graph(conn: &mut SqliteConnection) -> QueryResult<Vec<MapSharing>> {
+        let edges: Vec<(Option<ProgramId>, ProgramId)> = bpf_programs::table
+            .filter(bpf_programs::map_owner_id.is_not_null())
+            .select((bpf_programs::map_owner_id, bpf_programs::id))
+            .order((bpf_programs::map_owner_id.asc(), bpf_programs::id.asc()))
+            .load(conn)?;
+
+        let mut graph: BTreeMap<ProgramId, Vec<ProgramId>> = BTreeMap::new();
+        for (owner, dependant) in edges {
+            if let Some(owner) = owner {
+                graph.entry(owner).or_default().push(dependant);
+            }
+        }
+
+        Ok(graph
+            .into_iter()
+            .map(|(owner, dependants)| MapSharing { owner, dependants })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{establish_connection, models::LocationType};
+
+    fn insert_program(conn: &mut SqliteConnection, id: u32, owner: Option<u32>) -> BpfProgram {
+        let mut prog = BpfProgram {
+            id: id.into(),
+            name: format!("prog_{id}"),
+            location_type: LocationType::File,
+            file_path: Some("/path/to/prog.o".to_string()),
+            map_pin_path: format!("/run/bpfman/fs/maps/{}", owner.unwrap_or(id)),
+            map_owner_id: owner.map(ProgramId::from),
+            ..Default::default()
+        };
+        BpfProgram::create_record(conn, &mut prog).unwrap()
+    }
+
+    fn assert_aborted(result: QueryResult<impl std::fmt::Debug>, message: &str) {
+        let err = result.unwrap_err();
+        assert!(
+            err.to_string().contains(message),
+            "expected `{message}`, got `{err}`"
+        );
+    }
+
+    #[test]
+    /// Tests owner and dependant lookups and the sharing graph.
+    fn test_map_sharing_graph() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        let owner = insert_program(&mut conn, 914, None);
+        let dependant = insert_program(&mut conn, 920, Some(914));
+        insert_program(&mut conn, 921, Some(914));
+        insert_program(&mut conn, 930, None);
+        insert_program(&mut conn, 931, Some(930));
+        insert_program(&mut conn, 940, None);
+
+        assert!(owner.owns_maps());
+        assert!(!dependant.owns_maps());
+        assert_eq!(owner.map_owner(&mut conn).unwrap(), None);
+        assert_eq!(dependant.map_owner(&mut conn).unwrap(), Some(owner.clone()));
+
+        let dependants: Vec<u32> = owner
+            .map_dependants(&mut conn)
+            .unwrap()
+            .iter()
+            .map(|p| p.id.get())
+            .collect();
+        assert_eq!(dependants, vec![920, 921]);
+
+        assert_eq!(
+            BpfProgram::map_sharing_graph(&mut conn).unwrap(),
+            vec![
+                MapSharing {
+                    owner: 914.into(),
+                    dependants: vec![920.into(), 921.into()],
+                },
+                MapSharing {
+                    owner: 930.into(),
+                    dependants: vec![931.into()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    /// Tests that the schema refuses to delete an owner with
+    /// dependants, to reference a missing owner, and to build
+    /// ownership chains, and that releasing dependants lifts the
+    /// restriction.
+    fn test_map_ownership_is_enforced() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        insert_program(&mut conn, 914, None);
+        insert_program(&mut conn, 920, Some(914));
+        insert_program(&mut conn, 930, None);
+
+        assert_aborted(
+            BpfProgram::delete_record(&mut conn, 914.into()),
+            "owns maps used by other programs",
+        );
+
+        let mut orphan = BpfProgram {
+            id: 950.into(),
+            name: "orphan".to_string(),
+            location_type: LocationType::File,
+            file_path: Some("/path/to/prog.o".to_string()),
+            map_owner_id: Some(999.into()),
+            ..Default::default()
+        };
+        assert_aborted(
+            BpfProgram::create_record(&mut conn, &mut orphan),
+            "map owner does not exist",
+        );
+
+        // 920 is a dependant, so it cannot own maps for 930...
+        assert_aborted(
+            BpfProgram::set_map_owner(&mut conn, 930.into(), Some(920.into())),
+            "does not own its maps",
+        );
+        // ...and 914 has dependants, so it cannot become one.
+        assert_aborted(
+            BpfProgram::set_map_owner(&mut conn, 914.into(), Some(930.into())),
+            "owns maps used by other programs",
+        );
+        assert_aborted(
+            BpfProgram::set_map_owner(&mut conn, 930.into(), Some(930.into())),
+            "cannot own its own maps",
+        );
+
+        // Updating a dependant's other fields is unaffected.
+        let mut dependant = BpfProgram::find_record(&mut conn, 920.into()).unwrap();
+        dependant.description = Some("uses 914's maps".to_string());
+        dependant.update_record(&mut conn).unwrap();
+
+        let released = BpfProgram::set_map_owner(&mut conn, 920.into(), None).unwrap();
+        assert!(released.owns_maps());
+        assert!(BpfProgram::delete_record(&mut conn, 914.into()).unwrap());
+        assert!(BpfProgram::map_sharing_graph(&mut conn).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+//! Typed access to a program's `metadata` and `global_data`.
+//!
+//! Both columns hold JSON objects: `metadata` maps keys such as
+//! `bpfman.io/uuid` to strings, and `global_data` maps global variable
+//! names to their initial bytes, encoded as an array of integers.
+//! bpfman kept each entry as its own sled key (`metadata_<key>`,
+//! `global_data_<name>`); here the JSON object remains the stored form
+//! and metadata lookups use SQLite's JSON1 `json_each`.
+
+use std::collections::BTreeMap;
+
+use diesel::{
+    dsl::sql,
+    prelude::*,
+    sql_types::{Bool, Text},
+};
+
+use super::BpfProgram;
+use crate::schema::bpf_programs;
+
+/// Metadata key bpfman uses for a program's UUID.
+pub const UUID_METADATA_KEY: &str = "bpfman.io/uuid";
+
+/// Metadata key bpfman uses for the name of the Kubernetes program
+/// object that owns a program.
+pub const PROGRAM_NAME_METADATA_KEY: &str = "bpfman.io/ProgramName";
+
+impl BpfProgram {
+    /// Returns the decoded `metadata` column.
+    pub fn metadata(&self) -> serde_json::Result<BTreeMap<String, String>> {
+        serde_json::from_str(&self.metadata)
+    }
+
+    /// Replaces the `metadata` column. Call
+    /// [`update_record`](BpfProgram::update_record) to persist it.
+    pub fn set_metadata(&mut self, metadata: &BTreeMap<String, String>) {
+        self.metadata = serde_json::to_string(metadata).expect("string map serialises");
+    }
+
+    /// Returns the decoded `global_data` column.
+    pub fn global_data(&self) -> serde_json::Result<BTreeMap<String, Vec<u8>>> {
+        serde_json::from_str(&self.global_data)
+    }
+
+    /// Replaces the `global_data` column. Call
+    /// [`update_record`](BpfProgram::update_record) to persist it.
+    pub fn set_global_data(&mut self, global_data: &BTreeMap<String, Vec<u8>>) {
+        self.global_data = serde_json::to_string(global_data).expect("byte map serialises");
+    }
+
+    /// Returns the programs whose metadata maps `key` to `value`,
+    /// ordered by ID.
+    pub fn find_by_metadata(
+        conn: &mut SqliteConnection,
+        key: &str,
+        value: &str,
+    ) -> QueryResult<Vec<BpfProgram>> {
+        // Matching on json_each rows rather than json_extract avoids
+        // having to quote keys such as `bpfman.io/uuid` into a JSON
+        // path.
+        let has_entry = sql::<Bool>(
+            "EXISTS (SELECT 1 FROM json_each(bpf_programs.metadata) \
+             WHERE json_each.key = ",
+        )
+        .bind::<Text, _>(key)
+        .sql(" AND json_each.value = ")
+        .bind::<Text, _>(value)
+        .sql(")");
+
+        bpf_programs::table
+            .filter(has_entry)
+            .order(bpf_programs::id.asc())
+            .load(conn)
+    }
+
+    /// Finds the program with the given `bpfman.io/uuid` metadata.
+    pub fn find_by_uuid(
+        conn: &mut SqliteConnection,
+        uuid: &str,
+    ) -> QueryResult<Option<BpfProgram>> {
+        Ok(Self::find_by_metadata(conn, UUID_METADATA_KEY, uuid)?
+            .into_iter()
+            .next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{establish_connection, models::LocationType};
+
+    fn insert_program(conn: &mut SqliteConnection, id: u32, uuid: &str, owner: &str) {
+        let mut prog = BpfProgram {
+            id: id.into(),
+            name: format!("prog_{id}"),
+            location_type: LocationType::File,
+            file_path: Some("/path/to/prog.o".to_string()),
+            map_pin_path: format!("/run/bpfman/fs/maps/{id}"),
+            ..Default::default()
+        };
+        prog.set_metadata(&BTreeMap::from([
+            (UUID_METADATA_KEY.to_string(), uuid.to_string()),
+            (PROGRAM_NAME_METADATA_KEY.to_string(), owner.to_string()),
+        ]));
+        BpfProgram::create_record(conn, &mut prog).unwrap();
+    }
+
+    #[test]
+    fn test_metadata_and_global_data_roundtrip() {
+        let mut prog = BpfProgram::default();
+        assert!(prog.metadata().unwrap().is_empty());
+        assert!(prog.global_data().unwrap().is_empty());
+
+        let global_data = BTreeMap::from([("sampling".to_string(), vec![0x01, 0x02])]);
+        prog.set_global_data(&global_data);
+        assert_eq!(prog.global_data, r#"{"sampling":[1,2]}"#);
+        assert_eq!(prog.global_data().unwrap(), global_data);
+
+        prog.metadata = "[]".to_string();
+        assert!(prog.metadata().is_err());
+    }
+
+    #[test]
+    /// Tests lookup by metadata, including keys that need quoting in
+    /// a JSON path, and that a value under a different key does not
+    /// match.
+    fn test_find_by_metadata() {
+        let mut conn = establish_connection(":memory:").unwrap();
+        insert_program(&mut conn, 1, "96b58352", "go-xdp-counter-example");
+        insert_program(&mut conn, 2, "1e7a7e4d", "go-xdp-counter-example");
+        insert_program(&mut conn, 3, "go-xdp-counter-example", "other");
+
+        let ids = |progs: Vec<BpfProgram>| progs.iter().map(|p| p.id.get()).collect::<Vec<_>>();
+        assert_eq!(
+            ids(BpfProgram::find_by_metadata(
+                &mut conn,
+                PROGRAM_NAME_METADATA_KEY,
+                "go-xdp-counter-example"
+            )
+            .unwrap()),
+            vec![1, 2]
+        );
+
+        let found = BpfProgram::find_by_uuid(&mut conn, "1e7a7e4d")
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id.get(), 2);
+        assert_eq!(found.metadata().unwrap()[UUID_METADATA_KEY], "1e7a7e4d");
+        assert!(
+            BpfProgram::find_by_uuid(&mut conn, "missing")
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,353 @@
+//! Enumerations for the TEXT discriminator columns.
+//!
+//! The schema constrains `bpf_programs.kind`, `state` and
+//! `location_type`, `bpf_links.link_type` and `state`,
+//! `bpf_maps.map_type`, and `events.entity_type` and `action` with
+//! CHECK constraints. These enums mirror those values so
+//! that an invalid discriminator is a compile error when writing and
+//! a deserialisation error when reading, rather than an opaque
+//! SQLite constraint failure at insert time.
+//!
+//! Each enum is stored as its lowercase `snake_case` name, which is
+//! also its [`Display`](std::fmt::Display), [`FromStr`] and serde
+//! representation. Only the enums a model's `Default` needs implement
+//! `Default`.
+
+use std::str::FromStr;
+
+use diesel::{
+    backend::Backend,
+    deserialize::{FromSql, FromSqlRow},
+    expression::AsExpression,
+    serialize::{IsNull, Output, ToSql},
+    sql_types::Text,
+    sqlite::Sqlite,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Error returned when a string does not name a variant of one of the
+/// discriminator enums.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid {type_name} value `{value}`")]
+pub struct InvalidVariantError {
+    pub type_name: &'static str,
+    pub value: String,
+}
+
+impl From<InvalidVariantError> for diesel::result::Error {
+    fn from(err: InvalidVariantError) -> Self {
+        diesel::result::Error::DeserializationError(Box::new(err))
+    }
+}
+
+macro_rules! define_text_enum {
+    (
+        $(#[$meta:meta])*
+        $name:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident => $text:literal,
+            )+
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(
+            Debug,
+            Clone,
+            Copy,
+            PartialEq,
+            Eq,
+            Hash,
+            Serialize,
+            Deserialize,
+            AsExpression,
+            FromSqlRow,
+        )]
+        #[diesel(sql_type = Text)]
+        pub enum $name {
+            $(
+                $(#[$variant_meta])*
+                #[serde(rename = $text)]
+                $variant,
+            )+
+        }
+
+        impl $name {
+            /// All variants, in declaration order.
+            pub const ALL: &'static [$name] = &[$($name::$variant),+];
+
+            /// Returns the value stored in the database.
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $text,)+
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = InvalidVariantError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $($text => Ok($name::$variant),)+
+                    _ => Err(InvalidVariantError {
+                        type_name: stringify!($name),
+                        value: s.to_string(),
+                    }),
+                }
+            }
+        }
+
+        impl ToSql<Text, Sqlite> for $name {
+            fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+                out.set_value(self.as_str());
+                Ok(IsNull::No)
+            }
+        }
+
+        impl FromSql<Text, Sqlite> for $name {
+            fn from_sql(
+                bytes: <Sqlite as Backend>::RawValue<'_>,
+            ) -> diesel::deserialize::Result<Self> {
+                let text = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+                text.parse().map_err(|e: InvalidVariantError| e.into())
+            }
+        }
+    };
+}
+
+define_text_enum! {
+    /// Program type discriminator (`bpf_programs.kind`). There is no
+    /// default kind, so it has no `Default`.
+    ProgramKind {
+        Xdp => "xdp",
+        Tc => "tc",
+        Tcx => "tcx",
+        Tracepoint => "tracepoint",
+        Kprobe => "kprobe",
+        Uprobe => "uprobe",
+        Fentry => "fentry",
+        Fexit => "fexit",
+    }
+}
+
+define_text_enum! {
+    /// Program lifecycle state (`bpf_programs.state`).
+    #[derive(Default)]
+    ProgramState {
+        #[default]
+        PreLoad => "pre_load",
+        Loaded => "loaded",
+    }
+}
+
+define_text_enum! {
+    /// Where the program bytecode comes from
+    /// (`bpf_programs.location_type`).
+    #[derive(Default)]
+    LocationType {
+        #[default]
+        File => "file",
+        Image => "image",
+    }
+}
+
+define_text_enum! {
+    /// Link lifecycle state (`bpf_links.state`).
+    #[derive(Default)]
+    LinkState {
+        #[default]
+        PreAttach => "pre_attach",
+        Attached => "attached",
+    }
+}
+
+define_text_enum! {
+    /// Kernel map type (`bpf_maps.map_type`), named after the kernel's
+    /// `BPF_MAP_TYPE_*` constants.
+    MapType {
+        Hash => "hash",
+        Array => "array",
+        ProgramArray => "program_array",
+        PerfEventArray => "perf_event_array",
+        PerCpuHash => "per_cpu_hash",
+        PerCpuArray => "per_cpu_array",
+        StackTrace => "stack_trace",
+        CgroupArray => "cgroup_array",
+        LruHash => "lru_hash",
+        LruPerCpuHash => "lru_per_cpu_hash",
+        LpmTrie => "lpm_trie",
+        ArrayOfMaps => "array_of_maps",
+        HashOfMaps => "hash_of_maps",
+        DevMap => "dev_map",
+        SockMap => "sock_map",
+        CpuMap => "cpu_map",
+        XskMap => "xsk_map",
+        SockHash => "sock_hash",
+        CgroupStorage => "cgroup_storage",
+        ReuseportSockArray => "reuseport_sock_array",
+        PerCpuCgroupStorage => "per_cpu_cgroup_storage",
+        Queue => "queue",
+        Stack => "stack",
+        SkStorage => "sk_storage",
+        DevMapHash => "dev_map_hash",
+        StructOps => "struct_ops",
+        RingBuf => "ring_buf",
+        InodeStorage => "inode_storage",
+        TaskStorage => "task_storage",
+        BloomFilter => "bloom_filter",
+        UserRingBuf => "user_ring_buf",
+        CgrpStorage => "cgrp_storage",
+        Arena => "arena",
+    }
+}
+
+impl MapType {
+    /// Returns the map type with the kernel's `BPF_MAP_TYPE_*` value
+    /// `value`, or `None` if there is none. The variants are declared
+    /// in the kernel's order, which starts at 1 with
+    /// `BPF_MAP_TYPE_HASH`.
+    pub fn from_kernel(value: u32) -> Option<MapType> {
+        let index = usize::try_from(value).ok()?.checked_sub(1)?;
+        MapType::ALL.get(index).copied()
+    }
+}
+
+define_text_enum! {
+    /// Traffic direction for TC and TCX attachments.
+    #[derive(Default)]
+    Direction {
+        #[default]
+        Ingress => "ingress",
+        Egress => "egress",
+    }
+}
+
+define_text_enum! {
+    /// What an event describes (`events.entity_type`).
+    EntityType {
+        Program => "program",
+        Link => "link",
+        Map => "map",
+    }
+}
+
+define_text_enum! {
+    /// What happened to the entity an event describes
+    /// (`events.action`).
+    EventAction {
+        Create => "create",
+        Update => "update",
+        StateChange => "state_change",
+        Delete => "delete",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{prelude::*, sqlite::SqliteConnection};
+
+    use super::*;
+
+    macro_rules! test_text_enum_roundtrip {
+        ($name:ident, $enum:ty) => {
+            #[test]
+            fn $name() {
+                for variant in <$enum>::ALL {
+                    let text = variant.to_string();
+                    assert_eq!(text.parse::<$enum>().unwrap(), *variant);
+
+                    let json = serde_json::to_string(variant).unwrap();
+                    assert_eq!(json, format!("\"{}\"", text));
+                    assert_eq!(serde_json::from_str::<$enum>(&json).unwrap(), *variant);
+                }
+
+                let err = "bogus".parse::<$enum>().unwrap_err();
+                assert_eq!(err.type_name, stringify!($enum));
+                assert!(serde_json::from_str::<$enum>("\"bogus\"").is_err());
+            }
+        };
+    }
+
+    test_text_enum_roundtrip!(test_roundtrip_program_kind, ProgramKind);
+    test_text_enum_roundtrip!(test_roundtrip_program_state, ProgramState);
+    test_text_enum_roundtrip!(test_roundtrip_location_type, LocationType);
+    test_text_enum_roundtrip!(test_roundtrip_link_state, LinkState);
+    test_text_enum_roundtrip!(test_roundtrip_map_type, MapType);
+    test_text_enum_roundtrip!(test_roundtrip_direction, Direction);
+    test_text_enum_roundtrip!(test_roundtrip_entity_type, EntityType);
+    test_text_enum_roundtrip!(test_roundtrip_event_action, EventAction);
+
+    table! {
+        enum_test (id) {
+            id -> Integer,
+            kind -> Text,
+        }
+    }
+
+    #[derive(Debug, PartialEq, Queryable, Insertable)]
+    #[diesel(table_name = enum_test)]
+    struct EnumTest {
+        id: i32,
+        kind: ProgramKind,
+    }
+
+    #[test]
+    fn test_diesel_roundtrip_and_invalid_value() {
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        diesel::sql_query("CREATE TABLE enum_test (id INTEGER PRIMARY KEY, kind TEXT NOT NULL)")
+            .execute(&mut conn)
+            .unwrap();
+
+        let row = EnumTest {
+            id: 1,
+            kind: ProgramKind::Tcx,
+        };
+        diesel::insert_into(enum_test::table)
+            .values(&row)
+            .execute(&mut conn)
+            .unwrap();
+
+        let stored: String = enum_test::table
+            .select(enum_test::kind)
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(stored, "tcx");
+        assert_eq!(
+            enum_test::table
+                .find(1)
+                .first::<EnumTest>(&mut conn)
+                .unwrap(),
+            row
+        );
+
+        diesel::sql_query("INSERT INTO enum_test (id, kind) VALUES (2, 'socket_filter')")
+            .execute(&mut conn)
+            .unwrap();
+        let err = enum_test::table
+            .find(2)
+            .first::<EnumTest>(&mut conn)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("invalid ProgramKind value `socket_filter`")
+        );
+    }
+
+    #[test]
+    fn test_map_type_from_kernel() {
+        assert_eq!(MapType::from_kernel(0), None);
+        assert_eq!(MapType::from_kernel(1), Some(MapType::Hash));
+        assert_eq!(MapType::from_kernel(6), Some(MapType::PerCpuArray));
+        assert_eq!(MapType::from_kernel(27), Some(MapType::RingBuf));
+        assert_eq!(MapType::from_kernel(33), Some(MapType::Arena));
+        assert_eq!(MapType::from_kernel(34), None);
+    }
+}
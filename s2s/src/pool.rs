@@ -0,0 +1,189 @@
+//! Connection pooling.
+//!
+//! A long-running service answering requests on several threads needs
+//! more than one connection. [`ConnectionOptions::pool`] builds an
+//! [r2d2](diesel::r2d2) pool whose connections are each configured as
+//! [`ConnectionOptions::open`] would, after applying any pending
+//! migrations once. With WAL enabled, as it is by default, readers on
+//! pooled connections proceed while another writes; writers still take
+//! turns, waiting up to the busy timeout.
+//!
+//! Every connection to `:memory:` opens its own empty database, so
+//! pool a database file.
+
+use diesel::{
+    r2d2::{ConnectionManager, CustomizeConnection},
+    sqlite::SqliteConnection,
+};
+
+use crate::{ConnectionError, ConnectionOptions};
+
+/// A pool of configured connections to one database.
+pub type Pool = diesel::r2d2::Pool<ConnectionManager<SqliteConnection>>;
+
+/// A connection checked out of a [`Pool`], returned to it on drop.
+pub type PooledConnection = diesel::r2d2::PooledConnection<ConnectionManager<SqliteConnection>>;
+
+/// Applies the [`ConnectionOptions`] to each connection the pool
+/// opens.
+#[derive(Debug)]
+struct Configure(ConnectionOptions);
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for Configure {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        self.0
+            .configure(conn)
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+impl ConnectionOptions {
+    /// Applies any pending migrations to the database, then returns a
+    /// pool of up to `max_size` connections to it with these options.
+    ///
+    /// The pool opens its connections up front and fails if it cannot.
+    pub fn pool(&self, database_url: &str, max_size: u32) -> Result<Pool, ConnectionError> {
+        drop(self.establish(database_url)?);
+
+        Ok(Pool::builder()
+            .max_size(max_size)
+            .connection_customizer(Box::new(Configure(self.clone())))
+            .build(ConnectionManager::new(database_url))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        thread,
+    };
+
+    use diesel::{dsl::sql, prelude::*, sql_types::Text};
+
+    use super::*;
+    use crate::{
+        migrations,
+        models::{BpfLink, BpfProgram, LocationType, ProgramKind},
+        schema::{bpf_links, bpf_programs},
+        test_utils::TempDatabase,
+    };
+
+    /// Inserts a program and its link in one transaction.
+    fn insert_program_and_link(conn: &mut SqliteConnection, id: u32) {
+        conn.immediate_transaction(|conn| {
+            let mut prog = BpfProgram {
+                id: id.into(),
+                name: format!("prog_{id}"),
+                kind: ProgramKind::Xdp,
+                location_type: LocationType::File,
+                file_path: Some("/path/to/prog.o".to_string()),
+                map_pin_path: format!("/run/bpfman/fs/maps/{id}"),
+                ..Default::default()
+            };
+            BpfProgram::create_record(conn, &mut prog)?;
+            let mut link = BpfLink {
+                id: id.into(),
+                program_id: prog.id,
+                link_type: Some(ProgramKind::Xdp),
+                ..Default::default()
+            };
+            BpfLink::create_record(conn, &mut link).map(|_| ())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    /// Tests that pooled connections share one migrated database and
+    /// are each configured.
+    fn test_pool_connections_are_configured() {
+        let db = TempDatabase::new("pool-configured");
+        let pool = ConnectionOptions::new().pool(db.url(), 3).unwrap();
+        assert_eq!(pool.state().connections, 3);
+
+        let mut first = pool.get().unwrap();
+        let mut second = pool.get().unwrap();
+        assert!(
+            migrations::status(&mut first)
+                .unwrap()
+                .iter()
+                .all(|m| m.applied)
+        );
+
+        insert_program_and_link(&mut first, 1);
+        assert_eq!(BpfLink::find_all(&mut second).unwrap().len(), 1);
+
+        for conn in [&mut first, &mut second] {
+            let (foreign_keys, text): (bool, String) = diesel::select((
+                sql::<diesel::sql_types::Bool>("(SELECT foreign_keys FROM pragma_foreign_keys)"),
+                sql::<Text>("ublob_to_text(x'ff')"),
+            ))
+            .get_result(&mut **conn)
+            .unwrap();
+            assert!(foreign_keys);
+            assert_eq!(text, "255");
+        }
+    }
+
+    #[test]
+    /// Tests that readers on pooled connections keep reading, and see
+    /// each program together with its link, while a writer inserts.
+    fn test_concurrent_reads_with_writer() {
+        const PROGRAMS: u32 = 200;
+
+        let db = TempDatabase::new("pool-concurrent");
+        let pool = ConnectionOptions::new().pool(db.url(), 4).unwrap();
+        let done = Arc::new(AtomicBool::new(false));
+
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let pool = pool.clone();
+                let done = Arc::clone(&done);
+                thread::spawn(move || {
+                    let mut conn = pool.get().unwrap();
+                    let mut reads = 0;
+                    let mut last = 0;
+                    while !done.load(Ordering::Acquire) || reads == 0 {
+                        let (programs, links): (i64, i64) = conn
+                            .transaction(|conn| {
+                                Ok::<_, diesel::result::Error>((
+                                    bpf_programs::table.count().get_result(conn)?,
+                                    bpf_links::table.count().get_result(conn)?,
+                                ))
+                            })
+                            .unwrap();
+                        assert_eq!(programs, links);
+                        assert!(programs >= last);
+                        last = programs;
+                        reads += 1;
+                    }
+                    reads
+                })
+            })
+            .collect();
+
+        let writer = {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                for id in 1..=PROGRAMS {
+                    insert_program_and_link(&mut pool.get().unwrap(), id);
+                }
+            })
+        };
+
+        writer.join().unwrap();
+        done.store(true, Ordering::Release);
+        for reader in readers {
+            assert!(reader.join().unwrap() > 0);
+        }
+
+        let mut conn = pool.get().unwrap();
+        assert_eq!(
+            BpfProgram::find_all(&mut conn).unwrap().len(),
+            PROGRAMS as usize
+        );
+    }
+}
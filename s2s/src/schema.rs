@@ -43,11 +43,9 @@ diesel::table! {
         file_path -> Nullable<Text>,
         image_url -> Nullable<Text>,
         image_pull_policy -> Nullable<Text>,
-        username -> Nullable<Text>,
-        password -> Nullable<Text>,
         map_pin_path -> Text,
-        map_owner_id -> Nullable<Integer>,
-        program_bytes -> Binary,
+        map_owner_id -> Nullable<BigInt>,
+        bytecode_sha256 -> Nullable<Text>,
         metadata -> Text,
         global_data -> Text,
         retprobe -> Nullable<Bool>,
@@ -57,13 +55,183 @@ diesel::table! {
         kernel_loaded_at -> Nullable<Text>,
         kernel_tag -> Nullable<Text>,
         kernel_gpl_compatible -> Nullable<Bool>,
-        kernel_btf_id -> Nullable<Integer>,
-        kernel_bytes_xlated -> Nullable<Integer>,
+        kernel_btf_id -> Nullable<BigInt>,
+        kernel_bytes_xlated -> Nullable<BigInt>,
         kernel_jited -> Nullable<Bool>,
-        kernel_bytes_jited -> Nullable<Integer>,
+        kernel_bytes_jited -> Nullable<BigInt>,
         kernel_verified_insns -> Nullable<Integer>,
         kernel_map_ids -> Text,
-        kernel_bytes_memlock -> Nullable<Integer>,
+        kernel_bytes_memlock -> Nullable<BigInt>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        credentials_ref -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    bytecode_objects (sha256) {
+        sha256 -> Text,
+        bytes -> Binary,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    event_actor (id) {
+        id -> BigInt,
+        actor -> Text,
+    }
+}
+
+diesel::table! {
+    events (id) {
+        id -> BigInt,
+        occurred_at -> Timestamp,
+        actor -> Nullable<Text>,
+        entity_type -> Text,
+        entity_id -> BigInt,
+        program_id -> Nullable<BigInt>,
+        action -> Text,
+        old_values -> Nullable<Text>,
+        new_values -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    fentry_attachments (link_id) {
+        link_id -> BigInt,
+        fn_name -> Text,
+    }
+}
+
+diesel::table! {
+    fexit_attachments (link_id) {
+        link_id -> BigInt,
+        fn_name -> Text,
+    }
+}
+
+diesel::table! {
+    image_maps (image_url, name) {
+        image_url -> Text,
+        name -> Text,
+        map_type -> Text,
+    }
+}
+
+diesel::table! {
+    image_programs (image_url, name) {
+        image_url -> Text,
+        name -> Text,
+        kind -> Text,
+    }
+}
+
+diesel::table! {
+    images (url) {
+        url -> Text,
+        digest -> Text,
+        tag -> Nullable<Text>,
+        pulled_at -> Timestamp,
+        manifest -> Text,
+        config -> Text,
+        layer -> Nullable<Binary>,
+    }
+}
+
+diesel::table! {
+    kprobe_attachments (link_id) {
+        link_id -> BigInt,
+        fn_name -> Text,
+        fn_offset -> BigInt,
+        retprobe -> Bool,
+        container_pid -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    tc_attachments (link_id) {
+        link_id -> BigInt,
+        iface -> Text,
+        if_index -> Nullable<Integer>,
+        direction -> Text,
+        priority -> Integer,
+        proceed_on -> Text,
+        current_position -> Nullable<Integer>,
+        attached -> Bool,
+        nsid -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    tc_dispatchers (nsid, if_index, direction) {
+        nsid -> BigInt,
+        if_index -> Integer,
+        direction -> Text,
+        revision -> Integer,
+        if_name -> Text,
+        priority -> Integer,
+        handle -> Nullable<Integer>,
+        num_extensions -> Integer,
+        program_name -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    tcx_attachments (link_id) {
+        link_id -> BigInt,
+        iface -> Text,
+        if_index -> Nullable<Integer>,
+        direction -> Text,
+        priority -> Integer,
+        current_position -> Nullable<Integer>,
+        nsid -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    tracepoint_attachments (link_id) {
+        link_id -> BigInt,
+        tracepoint -> Text,
+    }
+}
+
+diesel::table! {
+    uprobe_attachments (link_id) {
+        link_id -> BigInt,
+        target -> Text,
+        fn_name -> Nullable<Text>,
+        fn_offset -> BigInt,
+        retprobe -> Bool,
+        pid -> Nullable<Integer>,
+        container_pid -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    xdp_attachments (link_id) {
+        link_id -> BigInt,
+        iface -> Text,
+        if_index -> Nullable<Integer>,
+        priority -> Integer,
+        proceed_on -> Text,
+        current_position -> Nullable<Integer>,
+        attached -> Bool,
+        nsid -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    xdp_dispatchers (nsid, if_index) {
+        nsid -> BigInt,
+        if_index -> Integer,
+        revision -> Integer,
+        if_name -> Text,
+        mode -> Integer,
+        num_extensions -> Integer,
+        program_name -> Nullable<Text>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
     }
@@ -72,5 +240,37 @@ diesel::table! {
 diesel::joinable!(bpf_links -> bpf_programs (program_id));
 diesel::joinable!(bpf_program_maps -> bpf_maps (map_id));
 diesel::joinable!(bpf_program_maps -> bpf_programs (program_id));
+diesel::joinable!(bpf_programs -> bytecode_objects (bytecode_sha256));
+diesel::joinable!(fentry_attachments -> bpf_links (link_id));
+diesel::joinable!(fexit_attachments -> bpf_links (link_id));
+diesel::joinable!(image_maps -> images (image_url));
+diesel::joinable!(image_programs -> images (image_url));
+diesel::joinable!(kprobe_attachments -> bpf_links (link_id));
+diesel::joinable!(tc_attachments -> bpf_links (link_id));
+diesel::joinable!(tcx_attachments -> bpf_links (link_id));
+diesel::joinable!(tracepoint_attachments -> bpf_links (link_id));
+diesel::joinable!(uprobe_attachments -> bpf_links (link_id));
+diesel::joinable!(xdp_attachments -> bpf_links (link_id));
 
-diesel::allow_tables_to_appear_in_same_query!(bpf_links, bpf_maps, bpf_program_maps, bpf_programs,);
+diesel::allow_tables_to_appear_in_same_query!(
+    bpf_links,
+    bpf_maps,
+    bpf_program_maps,
+    bpf_programs,
+    bytecode_objects,
+    event_actor,
+    events,
+    fentry_attachments,
+    fexit_attachments,
+    image_maps,
+    image_programs,
+    images,
+    kprobe_attachments,
+    tc_attachments,
+    tc_dispatchers,
+    tcx_attachments,
+    tracepoint_attachments,
+    uprobe_attachments,
+    xdp_attachments,
+    xdp_dispatchers,
+);
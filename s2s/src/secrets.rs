@@ -0,0 +1,269 @@
+//! Storage for image registry credentials.
+//!
+//! Credentials are kept out of the database: a program records only
+//! [`BpfProgram::credentials_ref`](crate::models::BpfProgram), the key
+//! under which its credentials live in a [`SecretStore`]. This keeps
+//! registry passwords out of database dumps, serialised programs and
+//! debug output.
+//!
+//! [`FileSecretStore`] keeps one owner-readable file per key in a
+//! directory; [`MemorySecretStore`] keeps them in memory.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SecretStoreError {
+    #[error("invalid secret key `{0}`")]
+    InvalidKey(String),
+
+    #[error("secret `{key}`: {source}")]
+    Io { key: String, source: io::Error },
+
+    #[error("secret `{key}`: {source}")]
+    Json {
+        key: String,
+        source: serde_json::Error,
+    },
+}
+
+/// Registry credentials for pulling an image.
+///
+/// The [`Debug`] output redacts the password.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// A keyed store of [`Credentials`].
+///
+/// Keys are restricted to ASCII letters, digits, `-`, `_` and `.` (but
+/// not `.` or `..`) so that every implementation can use them as file
+/// names.
+pub trait SecretStore {
+    /// Stores `credentials` under `key`, replacing any existing entry.
+    fn put(&mut self, key: &str, credentials: &Credentials) -> Result<(), SecretStoreError>;
+
+    /// Returns the credentials stored under `key`, if any.
+    fn get(&self, key: &str) -> Result<Option<Credentials>, SecretStoreError>;
+
+    /// Removes the credentials stored under `key`. Returns true if an
+    /// entry was removed.
+    fn delete(&mut self, key: &str) -> Result<bool, SecretStoreError>;
+}
+
+fn validate_key(key: &str) -> Result<(), SecretStoreError> {
+    let valid = !key.is_empty()
+        && key != "."
+        && key != ".."
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(SecretStoreError::InvalidKey(key.to_string()))
+    }
+}
+
+/// A [`SecretStore`] holding one JSON file per key in a directory.
+///
+/// On Unix the directory is created mode 0700 and each file mode
+/// 0600.
+#[derive(Debug, Clone)]
+pub struct FileSecretStore {
+    dir: PathBuf,
+}
+
+impl FileSecretStore {
+    /// Opens the store in `dir`, creating the directory if needed.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        builder.create(&dir)?;
+
+        Ok(FileSecretStore { dir })
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, SecretStoreError> {
+        validate_key(key)?;
+        Ok(self.dir.join(key))
+    }
+}
+
+impl SecretStore for FileSecretStore {
+    fn put(&mut self, key: &str, credentials: &Credentials) -> Result<(), SecretStoreError> {
+        let path = self.path(key)?;
+        let json = serde_json::to_vec(credentials).expect("credentials serialise");
+        let io_err = |source| SecretStoreError::Io {
+            key: key.to_string(),
+            source,
+        };
+
+        // Write to a temporary file created with restricted permissions
+        // and rename it into place, so the secret is never readable by
+        // others and a crash cannot leave it truncated.
+        let tmp = self.dir.join(format!(".{key}.tmp"));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        io::Write::write_all(&mut options.open(&tmp).map_err(io_err)?, &json).map_err(io_err)?;
+        fs::rename(&tmp, &path).map_err(io_err)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Credentials>, SecretStoreError> {
+        let path = self.path(key)?;
+        match fs::read(&path) {
+            Ok(json) => {
+                serde_json::from_slice(&json)
+                    .map(Some)
+                    .map_err(|source| SecretStoreError::Json {
+                        key: key.to_string(),
+                        source,
+                    })
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(source) => Err(SecretStoreError::Io {
+                key: key.to_string(),
+                source,
+            }),
+        }
+    }
+
+    fn delete(&mut self, key: &str) -> Result<bool, SecretStoreError> {
+        let path = self.path(key)?;
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(source) => Err(SecretStoreError::Io {
+                key: key.to_string(),
+                source,
+            }),
+        }
+    }
+}
+
+/// A [`SecretStore`] that keeps credentials in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySecretStore {
+    secrets: BTreeMap<String, Credentials>,
+}
+
+impl SecretStore for MemorySecretStore {
+    fn put(&mut self, key: &str, credentials: &Credentials) -> Result<(), SecretStoreError> {
+        validate_key(key)?;
+        self.secrets.insert(key.to_string(), credentials.clone());
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Credentials>, SecretStoreError> {
+        validate_key(key)?;
+        Ok(self.secrets.get(key).cloned())
+    }
+
+    fn delete(&mut self, key: &str) -> Result<bool, SecretStoreError> {
+        validate_key(key)?;
+        Ok(self.secrets.remove(key).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials() -> Credentials {
+        Credentials {
+            username: "robot".to_string(),
+            password: "hunter2".to_string(),
+        }
+    }
+
+    /// Exercises the SecretStore contract against any implementation.
+    fn check_store(store: &mut dyn SecretStore) {
+        assert_eq!(store.get("program-1").unwrap(), None);
+
+        store.put("program-1", &credentials()).unwrap();
+        assert_eq!(store.get("program-1").unwrap(), Some(credentials()));
+
+        let replaced = Credentials {
+            password: "correct horse".to_string(),
+            ..credentials()
+        };
+        store.put("program-1", &replaced).unwrap();
+        assert_eq!(store.get("program-1").unwrap(), Some(replaced));
+
+        assert!(store.delete("program-1").unwrap());
+        assert!(!store.delete("program-1").unwrap());
+        assert_eq!(store.get("program-1").unwrap(), None);
+
+        for key in ["", ".", "..", "../etc/passwd", "a/b"] {
+            assert!(matches!(
+                store.put(key, &credentials()),
+                Err(SecretStoreError::InvalidKey(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_memory_secret_store() {
+        check_store(&mut MemorySecretStore::default());
+    }
+
+    #[test]
+    fn test_file_secret_store() {
+        let dir = std::env::temp_dir().join(format!("s2s-secrets-{}", std::process::id()));
+        let mut store = FileSecretStore::open(&dir).unwrap();
+        check_store(&mut store);
+
+        store.put("program-2", &credentials()).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join("program-2"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // A second handle on the same directory sees the same secrets.
+        let reopened = FileSecretStore::open(&dir).unwrap();
+        assert_eq!(reopened.get("program-2").unwrap(), Some(credentials()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_credentials_debug_redacts_password() {
+        let debug = format!("{:?}", credentials());
+        assert!(debug.contains("robot"));
+        assert!(!debug.contains("hunter2"));
+    }
+}
@@ -0,0 +1,370 @@
+//! Operations spanning several tables, each in one transaction.
+//!
+//! The model layer is deliberately thin and leaves transactions to its
+//! callers. The functions here are those callers: each performs one
+//! bpfman operation as a single unit of work, so a failure part way
+//! through, whether a constraint violation or any other database
+//! error, rolls back everything it had written.
+//!
+//! A transaction nested in one the caller already holds becomes a
+//! savepoint, so these compose with larger units of work.
+
+use diesel::{prelude::*, sqlite::SqliteConnection};
+use thiserror::Error;
+
+use crate::models::{Attachment, BpfLink, BpfMap, BpfProgram, LinkId, MapId};
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("database error: {0}")]
+    Database(#[from] diesel::result::Error),
+
+    #[error("link {link_id}: {reason}")]
+    InvalidLink { link_id: LinkId, reason: String },
+}
+
+/// A program as bpfman records it on load.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadProgram {
+    /// The program, with the ID and kernel information assigned on
+    /// load.
+    pub program: BpfProgram,
+
+    /// The program's bytecode, stored as a
+    /// [`BytecodeObject`](crate::models::BytecodeObject) unless an
+    /// identical one is already stored. Empty if it has none.
+    pub bytecode: Vec<u8>,
+
+    /// The maps the kernel reports the program using. Maps already
+    /// recorded, such as those shared with a map owner, are left as
+    /// they are.
+    pub maps: Vec<BpfMap>,
+
+    /// The program's links, each with its attachment details.
+    pub links: Vec<LoadLink>,
+}
+
+/// A link to record with its attachment details.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadLink {
+    pub link: BpfLink,
+    pub attachment: Attachment,
+}
+
+impl LoadLink {
+    fn validate(&self, program: &BpfProgram) -> Result<(), LoadError> {
+        let invalid = |reason: String| {
+            Err(LoadError::InvalidLink {
+                link_id: self.link.id,
+                reason,
+            })
+        };
+
+        if self.link.program_id != program.id {
+            return invalid(format!(
+                "belongs to program {}, not {}",
+                self.link.program_id, program.id
+            ));
+        }
+        if self.attachment.link_id() != self.link.id {
+            return invalid(format!(
+                "attachment details are for link {}",
+                self.attachment.link_id()
+            ));
+        }
+        if self.attachment.kind() != program.kind {
+            return invalid(format!(
+                "{} attachment for a {} program",
+                self.attachment.kind(),
+                program.kind
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Records a loaded program: stores its bytecode, inserts the
+/// program, any of its maps not already recorded, its map
+/// associations, and its links with their attachment details.
+/// Returns everything as stored, with maps in ID order and links in
+/// the order given.
+///
+/// All of it is written in one transaction; if any part fails,
+/// nothing is.
+pub fn load_program(
+    conn: &mut SqliteConnection,
+    load: &LoadProgram,
+) -> Result<LoadProgram, LoadError> {
+    for link in &load.links {
+        link.validate(&load.program)?;
+    }
+
+    conn.transaction(|conn| {
+        let mut program = load.program.clone();
+        program.set_bytecode(conn, &load.bytecode)?;
+        BpfProgram::create_record(conn, &mut program)?;
+
+        for map in &load.maps {
+            if BpfMap::find_record(conn, map.id).optional()?.is_none() {
+                let mut map = map.clone();
+                BpfMap::create_record(conn, &mut map)?;
+            }
+        }
+        let map_ids: Vec<MapId> = load.maps.iter().map(|m| m.id).collect();
+        program.record_kernel_maps(conn, &map_ids)?;
+
+        let mut links = Vec::with_capacity(load.links.len());
+        for LoadLink { link, attachment } in &load.links {
+            let mut link = link.clone();
+            let link = BpfLink::create_record(conn, &mut link)?;
+            let attachment = attachment.create_record(conn)?;
+            links.push(LoadLink { link, attachment });
+        }
+
+        // Recording the maps touched `updated_at`, so read the program back.
+        let program = BpfProgram::find_record(conn, program.id)?;
+        Ok(LoadProgram {
+            maps: program.maps(conn)?,
+            program,
+            bytecode: load.bytecode.clone(),
+            links,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::connection::SimpleConnection;
+
+    use super::*;
+    use crate::{
+        establish_connection,
+        models::{
+            LinkState, LocationType, MapType, ProgramKind, TcAttachment, XdpAttachment,
+            check_kernel_maps,
+        },
+        schema::{bpf_links, bpf_maps, bpf_program_maps, bpf_programs, xdp_attachments},
+    };
+
+    const SHARED_MAP: u32 = 10;
+
+    fn xdp_link(program_id: u32, id: u32, iface: &str) -> LoadLink {
+        LoadLink {
+            link: BpfLink {
+                id: id.into(),
+                program_id: program_id.into(),
+                link_type: Some(ProgramKind::Xdp),
+                target: Some(iface.to_string()),
+                state: LinkState::Attached,
+                ..Default::default()
+            },
+            attachment: Attachment::Xdp(XdpAttachment {
+                link_id: id.into(),
+                iface: iface.to_string(),
+                if_index: Some(2),
+                priority: 50,
+                proceed_on: "[2]".to_string(),
+                current_position: Some(0),
+                attached: true,
+                nsid: Some(4026531840.into()),
+            }),
+        }
+    }
+
+    fn map(id: u32, name: &str) -> BpfMap {
+        BpfMap {
+            id: id.into(),
+            name: name.to_string(),
+            map_type: Some(MapType::PerCpuArray),
+            key_size: Some(4),
+            value_size: Some(16),
+            max_entries: Some(5),
+            ..Default::default()
+        }
+    }
+
+    /// An XDP program using the shared map and one of its own, with
+    /// links on two interfaces.
+    fn xdp_load(id: u32) -> LoadProgram {
+        LoadProgram {
+            program: BpfProgram {
+                id: id.into(),
+                name: "xdp_stats".to_string(),
+                kind: ProgramKind::Xdp,
+                location_type: LocationType::File,
+                file_path: Some("/path/to/xdp_stats.o".to_string()),
+                map_pin_path: format!("/run/bpfman/fs/maps/{id}"),
+                ..Default::default()
+            },
+            bytecode: vec![0xAA, 0xBB],
+            maps: vec![map(100 + id, "xdp_stats_map"), map(SHARED_MAP, "shared")],
+            links: vec![
+                xdp_link(id, id * 10, "eth0"),
+                xdp_link(id, id * 10 + 1, "eth1"),
+            ],
+        }
+    }
+
+    /// Returns the number of rows in each table a load writes to.
+    fn row_counts(conn: &mut SqliteConnection) -> [i64; 5] {
+        [
+            bpf_programs::table.count().get_result(conn).unwrap(),
+            bpf_maps::table.count().get_result(conn).unwrap(),
+            bpf_program_maps::table.count().get_result(conn).unwrap(),
+            bpf_links::table.count().get_result(conn).unwrap(),
+            xdp_attachments::table.count().get_result(conn).unwrap(),
+        ]
+    }
+
+    /// Returns a database holding one loaded program, 1, which owns
+    /// the shared map.
+    fn setup() -> SqliteConnection {
+        let mut conn = establish_connection(":memory:").unwrap();
+        load_program(&mut conn, &xdp_load(1)).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_load_program() {
+        let mut conn = setup();
+        let mut shared = map(SHARED_MAP, "renamed");
+        shared.max_entries = Some(99);
+        let mut load = xdp_load(2);
+        load.maps[1] = shared;
+
+        let loaded = load_program(&mut conn, &load).unwrap();
+        assert_eq!(loaded.program.kernel_map_ids, "[10,102]");
+        assert_eq!(
+            loaded.program,
+            BpfProgram::find_record(&mut conn, 2.into()).unwrap()
+        );
+
+        // The shared map already existed, so it is not overwritten.
+        let names: Vec<&str> = loaded.maps.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["shared", "xdp_stats_map"]);
+        assert_eq!(loaded.maps[0].max_entries, Some(5));
+
+        assert_eq!(loaded.links.len(), 2);
+        assert_eq!(
+            loaded.program.links(&mut conn).unwrap(),
+            loaded
+                .links
+                .iter()
+                .map(|l| BpfLink::find_record(&mut conn, l.link.id).unwrap())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Attachment::find_for_link(&mut conn, ProgramKind::Xdp, 21.into()).unwrap(),
+            loaded.links[1].attachment
+        );
+        assert_eq!(row_counts(&mut conn), [2, 3, 4, 4, 4]);
+        assert!(check_kernel_maps(&mut conn).unwrap().is_empty());
+    }
+
+    #[test]
+    /// Tests that a failure at each step of a load, injected with a
+    /// temporary trigger, leaves the database as it was.
+    fn test_load_program_rolls_back_injected_failures() {
+        for (table, when) in [
+            ("bpf_programs", "1"),
+            ("bpf_maps", "NEW.id = 102"),
+            ("bpf_program_maps", "NEW.map_id = 10"),
+            ("bpf_links", "NEW.id = 21"),
+            ("xdp_attachments", "NEW.link_id = 21"),
+        ] {
+            let mut conn = setup();
+            let before = row_counts(&mut conn);
+            conn.batch_execute(&format!(
+                "CREATE TEMP TRIGGER inject BEFORE INSERT ON main.{table} \
+                 WHEN {when} BEGIN SELECT RAISE(ABORT, 'injected failure'); END"
+            ))
+            .unwrap();
+
+            let err = load_program(&mut conn, &xdp_load(2)).unwrap_err();
+            assert!(
+                err.to_string().contains("injected failure"),
+                "{table}: {err}"
+            );
+            assert_eq!(row_counts(&mut conn), before, "{table}");
+            assert!(BpfProgram::find_record(&mut conn, 2.into()).is_err());
+            assert_eq!(
+                BpfMap::find_record(&mut conn, SHARED_MAP.into())
+                    .unwrap()
+                    .programs(&mut conn)
+                    .unwrap()
+                    .len(),
+                1
+            );
+
+            // Once the fault is gone, the same load succeeds.
+            conn.batch_execute("DROP TRIGGER inject").unwrap();
+            load_program(&mut conn, &xdp_load(2)).unwrap();
+        }
+    }
+
+    #[test]
+    /// Tests that a link the schema rejects, here one whose type does
+    /// not match its program, rolls back the links written before it.
+    fn test_load_program_rolls_back_on_constraint_violation() {
+        let mut conn = setup();
+        let before = row_counts(&mut conn);
+
+        let mut load = xdp_load(2);
+        load.links[1].link.link_type = Some(ProgramKind::Tc);
+        let err = load_program(&mut conn, &load).unwrap_err();
+        assert!(matches!(err, LoadError::Database(_)));
+        assert!(
+            err.to_string()
+                .contains("link type does not match the kind of its program")
+        );
+        assert_eq!(row_counts(&mut conn), before);
+
+        // A duplicate program fails on the first insert.
+        let err = load_program(&mut conn, &xdp_load(1)).unwrap_err();
+        assert!(
+            err.to_string().contains("UNIQUE constraint failed"),
+            "{err}"
+        );
+        assert_eq!(row_counts(&mut conn), before);
+    }
+
+    #[test]
+    /// Tests that inconsistent links are rejected before anything is
+    /// written.
+    fn test_load_program_validates_links() {
+        let mut conn = setup();
+        let before = row_counts(&mut conn);
+
+        let mut load = xdp_load(2);
+        load.links[0].link.program_id = 1.into();
+        assert!(matches!(
+            load_program(&mut conn, &load),
+            Err(LoadError::InvalidLink { link_id, .. }) if link_id == 20.into()
+        ));
+
+        let mut load = xdp_load(2);
+        load.links[1].attachment = xdp_link(2, 20, "eth1").attachment;
+        let err = load_program(&mut conn, &load).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "link 21: attachment details are for link 20"
+        );
+
+        let mut load = xdp_load(2);
+        load.links[0].attachment = Attachment::Tc(TcAttachment {
+            link_id: 20.into(),
+            iface: "eth0".to_string(),
+            if_index: None,
+            direction: Default::default(),
+            priority: 50,
+            proceed_on: "[]".to_string(),
+            current_position: None,
+            attached: false,
+            nsid: None,
+        });
+        let err = load_program(&mut conn, &load).unwrap_err();
+        assert_eq!(err.to_string(), "link 20: tc attachment for a xdp program");
+
+        assert_eq!(row_counts(&mut conn), before);
+    }
+}
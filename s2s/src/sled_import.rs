@@ -0,0 +1,1258 @@
+//! Migrates a bpfman sled database into the SQLite schema.
+//!
+//! bpfman keeps one sled tree per object, named after what it holds:
+//!
+//! - `program_<id>`: a bpfman-managed program, its location, kernel
+//!   information and the attach parameters for its kind (`xdp_*`,
+//!   `tc_*`, `kprobe_*`, ...).
+//! - `map_<id>`: the programs (`map_used_by_<n>`) sharing the maps
+//!   owned by program `<id>`.
+//! - `tc_dispatcher_*` / `xdp_dispatcher_*`: dispatcher state.
+//! - `__sled__default`: the OCI image cache. Each image is stored
+//!   under a key prefix derived from its URL, with `/` and `:`
+//!   replaced by `_`: `<prefix>manifest.json`, `<prefix><config hex>`
+//!   and `<prefix><layer hex>`.
+//! - `<id>`: kernel programs observed, but not managed, by bpfman.
+//!
+//! This is the same layout the `bsd` dumper walks. [`migrate`] reads
+//! every tree, writes the equivalent `bpf_programs`, `bpf_links`
+//! (with their per-kind attachment details), `bpf_maps`,
+//! `bpf_program_maps`, dispatcher and `images` rows inside a single
+//! transaction, and returns a [`MigrationReport`] describing what
+//! happened to each tree. Any decoding or database error rolls the
+//! whole migration back.
+//!
+//! Registry credentials (`location_username` / `location_password`)
+//! are written to a [`SecretStore`] rather than the database. The
+//! store is not part of the transaction, so a migration that rolls
+//! back may leave credentials behind; they are keyed by program ID
+//! and overwritten by the next attempt.
+//!
+//! Values are decoded the way bpfman encodes them: integers in native
+//! byte order (2 bytes for `u16`, 4 bytes for `u32`/`i32`, 8 bytes for
+//! `u64`/`usize`),
+//! booleans as a single byte and strings as raw UTF-8.
+
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use diesel::{prelude::*, sqlite::SqliteConnection};
+use thiserror::Error;
+
+use crate::{
+    models::{
+        Attachment, BpfLink, BpfProgram, CredentialsError, Direction, FentryAttachment,
+        FexitAttachment, Image, ImageError, InvalidVariantError, KprobeAttachment, LinkId,
+        LinkState, LocationType, MapId, OutOfRangeError, ProgramId, ProgramKind, ProgramState,
+        TcAttachment, TcDispatcher, TcxAttachment, TracepointAttachment, UprobeAttachment,
+        XdpAttachment, XdpDispatcher,
+    },
+    secrets::{Credentials, SecretStore, SecretStoreError},
+};
+
+/// bpfman's TC `Direction` discriminants.
+const TC_DIRECTION_INGRESS: u64 = 1;
+const TC_DIRECTION_EGRESS: u64 = 2;
+
+/// bpfman's `ProgramType` discriminants for the kinds we migrate.
+const PROG_TYPE_PROBE: u64 = 2;
+const PROG_TYPE_TC: u64 = 3;
+const PROG_TYPE_TRACEPOINT: u64 = 5;
+const PROG_TYPE_XDP: u64 = 6;
+const PROG_TYPE_TRACING: u64 = 26;
+
+#[derive(Debug, Error)]
+pub enum SledMigrationError {
+    #[error("sled error: {0}")]
+    Sled(#[from] sled::Error),
+
+    #[error("database error: {0}")]
+    Database(#[from] diesel::result::Error),
+
+    #[error("secret store error: {0}")]
+    Secrets(#[from] SecretStoreError),
+
+    #[error("tree `{tree}`: key `{key}`: {reason}")]
+    InvalidValue {
+        tree: String,
+        key: String,
+        reason: String,
+    },
+}
+
+impl From<CredentialsError> for SledMigrationError {
+    fn from(err: CredentialsError) -> Self {
+        match err {
+            CredentialsError::Database(err) => SledMigrationError::Database(err),
+            CredentialsError::Secrets(err) => SledMigrationError::Secrets(err),
+        }
+    }
+}
+
+/// What happened to a single sled tree during migration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeOutcome {
+    /// The tree was written to SQLite; `detail` summarises the rows.
+    Migrated { detail: String },
+
+    /// The tree was deliberately not migrated.
+    Skipped { reason: String },
+}
+
+/// Per-tree migration outcome.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeReport {
+    pub tree: String,
+    pub outcome: TreeOutcome,
+}
+
+/// The outcome of a complete sled migration, one entry per tree in
+/// the order they were processed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    pub trees: Vec<TreeReport>,
+}
+
+impl MigrationReport {
+    /// Returns the number of trees that were migrated.
+    pub fn migrated(&self) -> usize {
+        self.trees
+            .iter()
+            .filter(|t| matches!(t.outcome, TreeOutcome::Migrated { .. }))
+            .count()
+    }
+
+    /// Returns the number of trees that were skipped.
+    pub fn skipped(&self) -> usize {
+        self.trees.len() - self.migrated()
+    }
+
+    fn migrated_tree(&mut self, tree: &str, detail: String) {
+        self.trees.push(TreeReport {
+            tree: tree.to_string(),
+            outcome: TreeOutcome::Migrated { detail },
+        });
+    }
+
+    fn skipped_tree(&mut self, tree: &str, reason: impl Into<String>) {
+        self.trees.push(TreeReport {
+            tree: tree.to_string(),
+            outcome: TreeOutcome::Skipped {
+                reason: reason.into(),
+            },
+        });
+    }
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for report in &self.trees {
+            match &report.outcome {
+                TreeOutcome::Migrated { detail } => {
+                    writeln!(f, "migrated {}: {}", report.tree, detail)?
+                }
+                TreeOutcome::Skipped { reason } => {
+                    writeln!(f, "skipped  {}: {}", report.tree, reason)?
+                }
+            }
+        }
+        write!(
+            f,
+            "{} trees migrated, {} skipped",
+            self.migrated(),
+            self.skipped()
+        )
+    }
+}
+
+/// Opens the sled database at `path` and migrates it into `conn`.
+pub fn migrate_path(
+    path: impl AsRef<Path>,
+    conn: &mut SqliteConnection,
+    secrets: &mut dyn SecretStore,
+) -> Result<MigrationReport, SledMigrationError> {
+    let db = sled::open(path)?;
+    migrate(&db, conn, secrets)
+}
+
+/// Migrates every tree in `db` into `conn` in a single transaction.
+///
+/// Programs are migrated, and their map owners applied, before the
+/// other trees so that map sharing can be resolved against the
+/// programs already written.
+pub fn migrate(
+    db: &sled::Db,
+    conn: &mut SqliteConnection,
+    secrets: &mut dyn SecretStore,
+) -> Result<MigrationReport, SledMigrationError> {
+    let mut tree_names: Vec<String> = db
+        .tree_names()
+        .iter()
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect();
+    tree_names.sort();
+
+    let (programs, rest): (Vec<_>, Vec<_>) = tree_names
+        .into_iter()
+        .partition(|name| name.starts_with("program_"));
+
+    conn.transaction(|conn| {
+        let mut report = MigrationReport::default();
+
+        let mut map_owners = Vec::new();
+        for name in &programs {
+            let tree = SledTree::load(db, name)?;
+            map_owners.extend(migrate_program(conn, secrets, &tree, &mut report)?);
+        }
+        apply_map_owners(conn, &map_owners)?;
+
+        for name in &rest {
+            let tree = SledTree::load(db, name)?;
+
+            if let Some(owner) = name.strip_prefix("map_") {
+                migrate_map_owner(conn, &tree, owner, &mut report)?;
+            } else if name.starts_with("tc_dispatcher_") {
+                migrate_tc_dispatcher(conn, &tree, &mut report)?;
+            } else if name.starts_with("xdp_dispatcher_") {
+                migrate_xdp_dispatcher(conn, &tree, &mut report)?;
+            } else if name == "__sled__default" {
+                migrate_images(conn, &tree, &mut report)?;
+            } else if name.chars().all(|c| c.is_ascii_digit()) {
+                report.skipped_tree(name, "kernel program not managed by bpfman");
+            } else {
+                report.skipped_tree(name, "unrecognised tree");
+            }
+        }
+
+        Ok(report)
+    })
+}
+
+/// A sled tree loaded into memory, with typed accessors that decode
+/// values the way bpfman encoded them.
+struct SledTree {
+    name: String,
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl SledTree {
+    fn load(db: &sled::Db, name: &str) -> Result<Self, SledMigrationError> {
+        let mut entries = BTreeMap::new();
+        for item in db.open_tree(name)?.iter() {
+            let (key, value) = item?;
+            entries.insert(String::from_utf8_lossy(&key).into_owned(), value.to_vec());
+        }
+        Ok(SledTree {
+            name: name.to_string(),
+            entries,
+        })
+    }
+
+    fn invalid(&self, key: &str, reason: impl Into<String>) -> SledMigrationError {
+        SledMigrationError::InvalidValue {
+            tree: self.name.clone(),
+            key: key.to_string(),
+            reason: reason.into(),
+        }
+    }
+
+    fn has_prefix(&self, prefix: &str) -> bool {
+        self.entries.keys().any(|k| k.starts_with(prefix))
+    }
+
+    fn string(&self, key: &str) -> Result<Option<String>, SledMigrationError> {
+        self.entries
+            .get(key)
+            .map(|v| String::from_utf8(v.clone()).map_err(|e| self.invalid(key, e.to_string())))
+            .transpose()
+    }
+
+    fn required_string(&self, key: &str) -> Result<String, SledMigrationError> {
+        self.string(key)?
+            .ok_or_else(|| self.invalid(key, "missing required key"))
+    }
+
+    fn u64(&self, key: &str) -> Result<Option<u64>, SledMigrationError> {
+        self.entries
+            .get(key)
+            .map(|v| match v.len() {
+                2 => Ok(u16::from_ne_bytes(v[..].try_into().unwrap()) as u64),
+                4 => Ok(u32::from_ne_bytes(v[..].try_into().unwrap()) as u64),
+                8 => Ok(u64::from_ne_bytes(v[..].try_into().unwrap())),
+                n => Err(self.invalid(
+                    key,
+                    format!("expected 2, 4 or 8 byte integer, got {n} bytes"),
+                )),
+            })
+            .transpose()
+    }
+
+    fn i32(&self, key: &str) -> Result<Option<i32>, SledMigrationError> {
+        self.u64(key)?
+            .map(|v| i32::try_from(v).map_err(|_| self.invalid(key, format!("{v} exceeds i32"))))
+            .transpose()
+    }
+
+    fn i64(&self, key: &str) -> Result<Option<i64>, SledMigrationError> {
+        self.u64(key)?
+            .map(|v| i64::try_from(v).map_err(|_| self.invalid(key, format!("{v} exceeds i64"))))
+            .transpose()
+    }
+
+    fn required_i32(&self, key: &str) -> Result<i32, SledMigrationError> {
+        self.i32(key)?
+            .ok_or_else(|| self.invalid(key, "missing required key"))
+    }
+
+    /// Reads an unsigned kernel ID or size into its newtype, such as
+    /// a [`ProgramId`] or [`NamespaceId`].
+    fn id<T>(&self, key: &str) -> Result<Option<T>, SledMigrationError>
+    where
+        T: TryFrom<u64, Error = OutOfRangeError>,
+    {
+        self.u64(key)?
+            .map(|v| T::try_from(v).map_err(|e| self.invalid(key, e.to_string())))
+            .transpose()
+    }
+
+    fn required_id<T>(&self, key: &str) -> Result<T, SledMigrationError>
+    where
+        T: TryFrom<u64, Error = OutOfRangeError>,
+    {
+        self.id(key)?
+            .ok_or_else(|| self.invalid(key, "missing required key"))
+    }
+
+    fn direction(&self, key: &str) -> Result<Direction, SledMigrationError> {
+        self.required_string(key)?
+            .parse()
+            .map_err(|e: InvalidVariantError| self.invalid(key, e.to_string()))
+    }
+
+    fn bool(&self, key: &str) -> Result<Option<bool>, SledMigrationError> {
+        self.entries
+            .get(key)
+            .map(|v| match v.as_slice() {
+                [b] => Ok(*b != 0),
+                _ => Err(self.invalid(key, format!("expected 1 byte bool, got {} bytes", v.len()))),
+            })
+            .transpose()
+    }
+
+    /// Collects `<prefix><n>` integer entries ordered by `n`.
+    fn indexed_u64s(&self, prefix: &str) -> Result<Vec<u64>, SledMigrationError> {
+        Ok(self
+            .indexed_u64_entries(prefix)?
+            .into_iter()
+            .map(|(_, v)| v)
+            .collect())
+    }
+
+    /// Like [`SledTree::indexed_u64s`], but with the key of each value.
+    fn indexed_u64_entries<'a>(
+        &'a self,
+        prefix: &str,
+    ) -> Result<Vec<(&'a str, u64)>, SledMigrationError> {
+        let mut indexed = Vec::new();
+        for key in self.entries.keys().filter(|k| k.starts_with(prefix)) {
+            let index: usize = key[prefix.len()..]
+                .parse()
+                .map_err(|_| self.invalid(key, "non-numeric index"))?;
+            indexed.push((index, key.as_str(), self.u64(key)?.unwrap_or_default()));
+        }
+        indexed.sort();
+        Ok(indexed.into_iter().map(|(_, k, v)| (k, v)).collect())
+    }
+
+    /// Collects `<prefix><name>` entries keyed by `name`.
+    fn prefixed(&self, prefix: &str) -> impl Iterator<Item = (&str, &Vec<u8>)> {
+        self.entries
+            .iter()
+            .filter_map(move |(k, v)| k.strip_prefix(prefix).map(|name| (name, v)))
+    }
+}
+
+/// Infers the s2s program kind from bpfman's program type and the
+/// attach keys present in the tree, since bpfman uses a single type
+/// for tc/tcx, kprobe/uprobe and fentry/fexit.
+fn program_kind(tree: &SledTree) -> Result<Option<ProgramKind>, SledMigrationError> {
+    let kind = match tree.u64("kind")? {
+        Some(PROG_TYPE_XDP) => ProgramKind::Xdp,
+        Some(PROG_TYPE_TC) if tree.has_prefix("tcx_") => ProgramKind::Tcx,
+        Some(PROG_TYPE_TC) => ProgramKind::Tc,
+        Some(PROG_TYPE_PROBE) if tree.has_prefix("uprobe_") => ProgramKind::Uprobe,
+        Some(PROG_TYPE_PROBE) => ProgramKind::Kprobe,
+        Some(PROG_TYPE_TRACEPOINT) => ProgramKind::Tracepoint,
+        Some(PROG_TYPE_TRACING) if tree.entries.contains_key("fentry_fn_name") => {
+            ProgramKind::Fentry
+        }
+        Some(PROG_TYPE_TRACING) if tree.entries.contains_key("fexit_fn_name") => ProgramKind::Fexit,
+        _ => return Ok(None),
+    };
+    Ok(Some(kind))
+}
+
+/// Builds the attachment details for the single attachment a
+/// sled-era program carries, or `None` if the tree holds no attach
+/// parameters.
+fn program_attachment(
+    tree: &SledTree,
+    kind: ProgramKind,
+    link_id: LinkId,
+) -> Result<Option<Attachment>, SledMigrationError> {
+    let attachment = match kind {
+        ProgramKind::Xdp => {
+            let Some(iface) = tree.string("xdp_iface")? else {
+                return Ok(None);
+            };
+            Attachment::Xdp(XdpAttachment {
+                link_id,
+                iface,
+                if_index: tree.i32("xdp_if_index")?,
+                priority: tree.i32("xdp_priority")?.unwrap_or_default(),
+                proceed_on: json_ids(&tree.indexed_u64s("xdp_proceed_on_")?),
+                current_position: tree.i32("xdp_current_position")?,
+                attached: tree.bool("xdp_attached")?.unwrap_or(false),
+                nsid: tree.id("xdp_nsid")?,
+            })
+        }
+        ProgramKind::Tc => {
+            let Some(iface) = tree.string("tc_iface")? else {
+                return Ok(None);
+            };
+            Attachment::Tc(TcAttachment {
+                link_id,
+                iface,
+                if_index: tree.i32("tc_if_index")?,
+                direction: tree.direction("tc_direction")?,
+                priority: tree.i32("tc_priority")?.unwrap_or_default(),
+                proceed_on: json_ids(&tree.indexed_u64s("tc_proceed_on_")?),
+                current_position: tree.i32("tc_current_position")?,
+                attached: tree.bool("tc_attached")?.unwrap_or(false),
+                nsid: tree.id("tc_nsid")?,
+            })
+        }
+        ProgramKind::Tcx => {
+            let Some(iface) = tree.string("tcx_iface")? else {
+                return Ok(None);
+            };
+            Attachment::Tcx(TcxAttachment {
+                link_id,
+                iface,
+                if_index: tree.i32("tcx_if_index")?,
+                direction: tree.direction("tcx_direction")?,
+                priority: tree.i32("tcx_priority")?.unwrap_or_default(),
+                current_position: tree.i32("tcx_current_position")?,
+                nsid: tree.id("tcx_nsid")?,
+            })
+        }
+        ProgramKind::Kprobe => {
+            let Some(fn_name) = tree.string("kprobe_fn_name")? else {
+                return Ok(None);
+            };
+            Attachment::Kprobe(KprobeAttachment {
+                link_id,
+                fn_name,
+                fn_offset: tree.i64("kprobe_offset")?.unwrap_or_default(),
+                retprobe: tree.bool("kprobe_retprobe")?.unwrap_or(false),
+                container_pid: tree.i32("kprobe_container_pid")?,
+            })
+        }
+        ProgramKind::Uprobe => {
+            let Some(target) = tree.string("uprobe_target")? else {
+                return Ok(None);
+            };
+            Attachment::Uprobe(UprobeAttachment {
+                link_id,
+                target,
+                fn_name: tree.string("uprobe_fn_name")?,
+                fn_offset: tree.i64("uprobe_offset")?.unwrap_or_default(),
+                retprobe: tree.bool("uprobe_retprobe")?.unwrap_or(false),
+                pid: tree.i32("uprobe_pid")?,
+                container_pid: tree.i32("uprobe_container_pid")?,
+            })
+        }
+        ProgramKind::Tracepoint => {
+            let Some(tracepoint) = tree.string("tracepoint_name")? else {
+                return Ok(None);
+            };
+            Attachment::Tracepoint(TracepointAttachment {
+                link_id,
+                tracepoint,
+            })
+        }
+        ProgramKind::Fentry => {
+            let Some(fn_name) = tree.string("fentry_fn_name")? else {
+                return Ok(None);
+            };
+            Attachment::Fentry(FentryAttachment { link_id, fn_name })
+        }
+        ProgramKind::Fexit => {
+            let Some(fn_name) = tree.string("fexit_fn_name")? else {
+                return Ok(None);
+            };
+            Attachment::Fexit(FexitAttachment { link_id, fn_name })
+        }
+    };
+    Ok(Some(attachment))
+}
+
+fn json_ids(ids: &[u64]) -> String {
+    serde_json::to_string(ids).expect("id list serialises")
+}
+
+/// Migrates a `program_<id>` tree, returning the program's ID and map
+/// owner if it uses another program's maps.
+///
+/// The owner is not written here: the schema requires it to exist
+/// already, and programs are not necessarily migrated in dependency
+/// order.
+fn migrate_program(
+    conn: &mut SqliteConnection,
+    secrets: &mut dyn SecretStore,
+    tree: &SledTree,
+    report: &mut MigrationReport,
+) -> Result<Option<(ProgramId, ProgramId)>, SledMigrationError> {
+    let Some(kind) = program_kind(tree)? else {
+        report.skipped_tree(
+            &tree.name,
+            format!("unsupported program type {:?}", tree.u64("kind")?),
+        );
+        return Ok(None);
+    };
+
+    let id: ProgramId = tree.required_id("id")?;
+    let loaded = tree.entries.contains_key("kernel_name");
+
+    let (location_type, file_path, image_url) = match (
+        tree.string("location_filename")?,
+        tree.string("location_image_url")?,
+    ) {
+        (Some(path), _) => (LocationType::File, Some(path), None),
+        (None, Some(url)) => (LocationType::Image, None, Some(url)),
+        (None, None) => return Err(tree.invalid("location_*", "program has no location")),
+    };
+
+    let metadata: BTreeMap<String, String> = tree
+        .prefixed("metadata_")
+        .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v).into_owned()))
+        .collect();
+    let global_data: BTreeMap<String, Vec<u8>> = tree
+        .prefixed("global_data_")
+        .map(|(k, v)| (k.to_string(), v.clone()))
+        .collect();
+    let kernel_map_ids = tree
+        .indexed_u64s("kernel_map_ids_")?
+        .into_iter()
+        .map(|id| MapId::try_from(id).map_err(|e| tree.invalid("kernel_map_ids_*", e.to_string())))
+        .collect::<Result<Vec<_>, _>>()?;
+    let map_owner_id: Option<ProgramId> = tree.id("map_owner_id")?;
+
+    let mut program = BpfProgram {
+        id,
+        name: tree.required_string("name")?,
+        kind,
+        state: if loaded {
+            ProgramState::Loaded
+        } else {
+            ProgramState::PreLoad
+        },
+        location_type,
+        file_path,
+        image_url,
+        image_pull_policy: tree.string("location_image_pull_policy")?,
+        map_pin_path: tree.required_string("map_pin_path")?,
+        retprobe: match kind {
+            ProgramKind::Kprobe | ProgramKind::Uprobe => {
+                Some(tree.bool(&format!("{kind}_retprobe"))?.unwrap_or(false))
+            }
+            _ => None,
+        },
+        fn_name: match kind {
+            ProgramKind::Fentry | ProgramKind::Fexit => {
+                Some(tree.required_string(&format!("{kind}_fn_name"))?)
+            }
+            _ => None,
+        },
+        kernel_name: tree.string("kernel_name")?,
+        kernel_program_type: tree.i32("kernel_program_type")?,
+        kernel_loaded_at: tree.string("kernel_loaded_at")?,
+        kernel_tag: tree.string("kernel_tag")?,
+        kernel_gpl_compatible: tree.bool("kernel_gpl_compatible")?,
+        kernel_btf_id: tree.id("kernel_btf_id")?,
+        kernel_bytes_xlated: tree.id("kernel_bytes_xlated")?,
+        kernel_jited: tree.bool("kernel_jited")?,
+        kernel_bytes_jited: tree.id("kernel_bytes_jited")?,
+        kernel_verified_insns: tree.i32("kernel_verified_insns")?,
+        kernel_bytes_memlock: tree.id("kernel_bytes_memlock")?,
+        ..Default::default()
+    };
+    program.set_metadata(&metadata);
+    program.set_global_data(&global_data);
+    let credentials = match (
+        tree.string("location_username")?,
+        tree.string("location_password")?,
+    ) {
+        (Some(username), Some(password)) => Some(Credentials { username, password }),
+        (None, None) => None,
+        _ => {
+            return Err(tree.invalid(
+                "location_username",
+                "username and password must be given together",
+            ));
+        }
+    };
+    program.set_credentials(conn, secrets, credentials.as_ref())?;
+    if let Some(bytes) = tree.entries.get("program_bytes") {
+        program.set_bytecode(conn, bytes)?;
+    }
+    BpfProgram::create_record(conn, &mut program)?;
+
+    program.record_kernel_maps(conn, &kernel_map_ids)?;
+
+    let mut detail = format!("program {id} ({kind}), {} maps", kernel_map_ids.len());
+
+    // sled-era bpfman holds at most one attachment per program, so the
+    // link reuses the program's ID.
+    let link_id = LinkId::from(program.id.get());
+    if let Some(attachment) = program_attachment(tree, kind, link_id)? {
+        // Only xdp and tc record an explicit attached flag; every
+        // other kind is attached as part of a successful load.
+        let attached = match &attachment {
+            Attachment::Xdp(a) => a.attached,
+            Attachment::Tc(a) => a.attached,
+            _ => loaded,
+        };
+
+        let mut link = BpfLink {
+            id: link_id,
+            program_id: program.id,
+            link_type: Some(kind),
+            target: Some(attachment.target()),
+            state: if attached {
+                LinkState::Attached
+            } else {
+                LinkState::PreAttach
+            },
+            ..Default::default()
+        };
+        BpfLink::create_record(conn, &mut link)?;
+        attachment.create_record(conn)?;
+
+        detail.push_str(&format!(", link to {}", attachment.target()));
+    }
+
+    report.migrated_tree(&tree.name, detail);
+    Ok(map_owner_id.map(|owner| (program.id, owner)))
+}
+
+fn migrate_tc_dispatcher(
+    conn: &mut SqliteConnection,
+    tree: &SledTree,
+    report: &mut MigrationReport,
+) -> Result<(), SledMigrationError> {
+    let direction = match tree.u64("direction")? {
+        Some(TC_DIRECTION_INGRESS) => Direction::Ingress,
+        Some(TC_DIRECTION_EGRESS) => Direction::Egress,
+        other => return Err(tree.invalid("direction", format!("unknown direction {other:?}"))),
+    };
+
+    let mut dispatcher = TcDispatcher {
+        nsid: tree.required_id("nsid")?,
+        if_index: tree.required_i32("if_index")?,
+        direction,
+        revision: tree.required_i32("revision")?,
+        if_name: tree.required_string("if_name")?,
+        priority: tree.i32("priority")?.unwrap_or_default(),
+        handle: tree.i32("handle")?,
+        num_extensions: tree.i32("num_extension")?.unwrap_or_default(),
+        program_name: tree.string("program_name")?,
+        ..Default::default()
+    };
+    TcDispatcher::create_record(conn, &mut dispatcher)?;
+
+    report.migrated_tree(
+        &tree.name,
+        format!(
+            "tc dispatcher on {} {}, revision {}, {} extensions",
+            dispatcher.if_name, direction, dispatcher.revision, dispatcher.num_extensions
+        ),
+    );
+    Ok(())
+}
+
+fn migrate_xdp_dispatcher(
+    conn: &mut SqliteConnection,
+    tree: &SledTree,
+    report: &mut MigrationReport,
+) -> Result<(), SledMigrationError> {
+    let mut dispatcher = XdpDispatcher {
+        nsid: tree.required_id("nsid")?,
+        if_index: tree.required_i32("if_index")?,
+        revision: tree.required_i32("revision")?,
+        if_name: tree.required_string("if_name")?,
+        mode: tree.i32("mode")?.unwrap_or_default(),
+        num_extensions: tree.i32("num_extension")?.unwrap_or_default(),
+        program_name: tree.string("program_name")?,
+        ..Default::default()
+    };
+    XdpDispatcher::create_record(conn, &mut dispatcher)?;
+
+    report.migrated_tree(
+        &tree.name,
+        format!(
+            "xdp dispatcher on {}, revision {}, {} extensions",
+            dispatcher.if_name, dispatcher.revision, dispatcher.num_extensions
+        ),
+    );
+    Ok(())
+}
+
+/// Replaces the characters bpfman cannot use in an image store key.
+fn image_key_prefix(url: &str) -> String {
+    url.replace(['/', ':'], "_")
+}
+
+/// Recovers an image URL from its store key prefix.
+///
+/// The key prefix is lossy, so prefer the URL of a migrated program
+/// whose key prefix matches. Otherwise assume the first `_` ends the
+/// registry, the last `_` starts the tag, and any others were `/`.
+fn image_url(prefix: &str, program_urls: &[String]) -> String {
+    let prefix = prefix.strip_suffix('_').unwrap_or(prefix);
+    if let Some(url) = program_urls.iter().find(|url| {
+        let key = image_key_prefix(url);
+        key == prefix || format!("{key}_latest") == prefix
+    }) {
+        return url.clone();
+    }
+
+    match (prefix.split_once('_'), prefix.rsplit_once('_')) {
+        (Some((registry, _)), Some((repo, tag))) if repo.len() > registry.len() => format!(
+            "{registry}/{}:{tag}",
+            repo[registry.len() + 1..].replace('_', "/")
+        ),
+        _ => prefix.to_string(),
+    }
+}
+
+/// Migrates the OCI image cache into `images`, one image per
+/// `<prefix>manifest.json` key.
+fn migrate_images(
+    conn: &mut SqliteConnection,
+    tree: &SledTree,
+    report: &mut MigrationReport,
+) -> Result<(), SledMigrationError> {
+    use crate::schema::bpf_programs::dsl::*;
+
+    let program_urls: Vec<String> = bpf_programs
+        .select(image_url)
+        .filter(image_url.is_not_null())
+        .distinct()
+        .load::<Option<String>>(conn)?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let blob = |prefix: &str, digest: Option<&str>| -> Option<Vec<u8>> {
+        let hex = digest?.strip_prefix("sha256:")?;
+        tree.entries.get(&format!("{prefix}{hex}")).cloned()
+    };
+
+    let mut urls = Vec::new();
+    for key in tree.entries.keys() {
+        let Some(prefix) = key.strip_suffix("manifest.json") else {
+            continue;
+        };
+        let manifest = tree.required_string(key)?;
+        let parsed: serde_json::Value =
+            serde_json::from_str(&manifest).map_err(|e| tree.invalid(key, e.to_string()))?;
+
+        let config_digest = parsed.pointer("/config/digest").and_then(|d| d.as_str());
+        let config = blob(prefix, config_digest)
+            .ok_or_else(|| tree.invalid(key, "config blob is missing"))?;
+        let config = String::from_utf8(config).map_err(|e| tree.invalid(key, e.to_string()))?;
+        let layer = blob(
+            prefix,
+            parsed.pointer("/layers/0/digest").and_then(|d| d.as_str()),
+        );
+
+        let url = self::image_url(prefix, &program_urls);
+        let image = Image::from_oci(&url, &manifest, &config, layer)
+            .and_then(|image| Image::create_record(conn, &image))
+            .map_err(|e| match e {
+                ImageError::Database(e) => SledMigrationError::Database(e),
+                e => tree.invalid(key, e.to_string()),
+            })?;
+        urls.push(image.url);
+    }
+
+    if urls.is_empty() {
+        // sled always has a default tree, even if bpfman never pulled
+        // an image.
+        report.skipped_tree(&tree.name, "image store is empty");
+    } else {
+        report.migrated_tree(&tree.name, format!("images: {}", urls.join(", ")));
+    }
+    Ok(())
+}
+
+/// Records each program's map owner. A program whose owner was not
+/// migrated keeps its own maps; the owner's `map_<id>` tree is then
+/// reported as skipped.
+fn apply_map_owners(
+    conn: &mut SqliteConnection,
+    map_owners: &[(ProgramId, ProgramId)],
+) -> Result<(), SledMigrationError> {
+    for &(program_id, owner_id) in map_owners {
+        if BpfProgram::find_record(conn, owner_id)
+            .optional()?
+            .is_some()
+        {
+            BpfProgram::set_map_owner(conn, program_id, Some(owner_id))?;
+        }
+    }
+    Ok(())
+}
+
+/// Reconciles a `map_<owner>` tree with the migrated programs.
+///
+/// Every program listed in `map_used_by_<n>` other than the owner
+/// shares the owner's maps, which the schema records as
+/// `bpf_programs.map_owner_id`. Listed programs that were not migrated
+/// are skipped. Fails if the owner uses another program's maps, or if
+/// a listed program has dependants of its own, as ownership is one
+/// level deep.
+fn migrate_map_owner(
+    conn: &mut SqliteConnection,
+    tree: &SledTree,
+    owner: &str,
+    report: &mut MigrationReport,
+) -> Result<(), SledMigrationError> {
+    let owner_id: ProgramId = owner
+        .parse()
+        .map_err(|_| tree.invalid(&tree.name, format!("invalid map owner ID `{owner}`")))?;
+
+    let Some(owner) = BpfProgram::find_record(conn, owner_id).optional()? else {
+        report.skipped_tree(
+            &tree.name,
+            format!("owner program {owner_id} was not migrated"),
+        );
+        return Ok(());
+    };
+    if let Some(other) = owner.map_owner_id {
+        return Err(tree.invalid(
+            &tree.name,
+            format!("owner program {owner_id} uses the maps of program {other}"),
+        ));
+    }
+
+    let mut shared = Vec::new();
+    let mut missing = Vec::new();
+    for (key, user) in tree.indexed_u64_entries("map_used_by_")? {
+        let user = ProgramId::try_from(user).map_err(|e| tree.invalid(key, e.to_string()))?;
+        if user == owner_id {
+            continue;
+        }
+        let Some(sharer) = BpfProgram::find_record(conn, user).optional()? else {
+            missing.push(user.to_string());
+            continue;
+        };
+        if !sharer.map_dependants(conn)?.is_empty() {
+            return Err(tree.invalid(
+                key,
+                format!("program {user} owns maps used by other programs"),
+            ));
+        }
+        BpfProgram::set_map_owner(conn, user, Some(owner_id))?;
+        shared.push(user);
+    }
+
+    let mut detail = format!(
+        "maps owned by {owner_id}, used by {} programs",
+        shared.len()
+    );
+    if !missing.is_empty() {
+        detail.push_str(&format!(
+            ", skipped programs {} which were not migrated",
+            missing.join(", ")
+        ));
+    }
+    report.migrated_tree(&tree.name, detail);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{establish_connection, secrets::MemorySecretStore};
+
+    fn setup_sled() -> sled::Db {
+        sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("Failed to open temporary sled database")
+    }
+
+    fn insert(db: &sled::Db, tree: &str, entries: &[(&str, &[u8])]) {
+        let tree = db.open_tree(tree).unwrap();
+        for (key, value) in entries {
+            tree.insert(key, *value).unwrap();
+        }
+    }
+
+    /// Populates a tree the way bpfman does for the go-xdp-counter
+    /// example in the sample dump.
+    fn insert_xdp_program(db: &sled::Db, id: u32, map_owner: Option<u32>) {
+        let tree = format!("program_{id}");
+        let map_id = 500 + id;
+        insert(
+            db,
+            &tree,
+            &[
+                ("id", &id.to_ne_bytes()),
+                ("kind", &(PROG_TYPE_XDP as u32).to_ne_bytes()),
+                ("name", b"xdp_stats"),
+                (
+                    "location_image_url",
+                    b"quay.io/bpfman-bytecode/go-xdp-counter:latest",
+                ),
+                ("location_image_pull_policy", b"IfNotPresent"),
+                ("map_pin_path", b"/run/bpfman/fs/maps/914"),
+                ("metadata_bpfman.io/uuid", b"96b58352"),
+                ("global_data_sampling", &[0x01, 0x02]),
+                ("program_bytes", &[0x7F, 0x45, 0x4C, 0x46]),
+                ("kernel_name", b"xdp_stats"),
+                ("kernel_program_type", &6u32.to_ne_bytes()),
+                ("kernel_loaded_at", b"2025-01-28T18:03:34+0000"),
+                ("kernel_gpl_compatible", &[1]),
+                ("kernel_map_ids_0", &map_id.to_ne_bytes()),
+                ("xdp_iface", b"eth0"),
+                ("xdp_if_index", &10u32.to_ne_bytes()),
+                ("xdp_nsid", &4026533525u64.to_ne_bytes()),
+                ("xdp_priority", &55i32.to_ne_bytes()),
+                ("xdp_proceed_on_0", &2i32.to_ne_bytes()),
+                ("xdp_proceed_on_1", &31i32.to_ne_bytes()),
+                ("xdp_attached", &[1]),
+            ],
+        );
+        if let Some(owner) = map_owner {
+            insert(db, &tree, &[("map_owner_id", &owner.to_ne_bytes())]);
+        }
+    }
+
+    #[test]
+    fn test_migrate_programs_links_and_maps() {
+        let db = setup_sled();
+        insert_xdp_program(&db, 914, None);
+        insert_xdp_program(&db, 920, Some(914));
+        insert(
+            &db,
+            "map_914",
+            &[
+                ("map_used_by_0", &914u32.to_ne_bytes()),
+                ("map_used_by_1", &920u32.to_ne_bytes()),
+            ],
+        );
+
+        let mut conn = establish_connection(":memory:").unwrap();
+        let report =
+            migrate(&db, &mut conn, &mut MemorySecretStore::default()).expect("Migration failed");
+
+        assert_eq!(report.migrated(), 3);
+
+        let prog = BpfProgram::find_record(&mut conn, 914.into()).unwrap();
+        assert_eq!(prog.kind, ProgramKind::Xdp);
+        assert_eq!(prog.state, ProgramState::Loaded);
+        assert_eq!(prog.location_type, LocationType::Image);
+        assert_eq!(
+            prog.bytecode(&mut conn).unwrap(),
+            Some(vec![0x7F, 0x45, 0x4C, 0x46])
+        );
+        assert_eq!(prog.metadata, r#"{"bpfman.io/uuid":"96b58352"}"#);
+        assert_eq!(prog.global_data, r#"{"sampling":[1,2]}"#);
+        assert_eq!(prog.kernel_map_ids, "[1414]");
+        assert_eq!(prog.map_owner_id, None);
+
+        let sharer = BpfProgram::find_record(&mut conn, 920.into()).unwrap();
+        assert_eq!(sharer.map_owner_id, Some(914.into()));
+        // Both programs came from the same image, so share one object.
+        assert_eq!(sharer.bytecode_sha256, prog.bytecode_sha256);
+        let objects: i64 = crate::schema::bytecode_objects::table
+            .count()
+            .get_result(&mut conn)
+            .unwrap();
+        assert_eq!(objects, 1);
+
+        let links: Vec<BpfLink> = crate::schema::bpf_links::table.load(&mut conn).unwrap();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].program_id, 914.into());
+        assert_eq!(links[0].target.as_deref(), Some("eth0"));
+        assert_eq!(links[0].state, LinkState::Attached);
+
+        let xdp = XdpAttachment::find_record(&mut conn, links[0].id).unwrap();
+        assert_eq!(xdp.iface, "eth0");
+        assert_eq!(xdp.if_index, Some(10));
+        assert_eq!(xdp.priority, 55);
+        assert_eq!(xdp.proceed_on, "[2,31]");
+        assert!(xdp.attached);
+        assert_eq!(xdp.nsid, Some(4026533525.into()));
+
+        let program_maps: i64 = crate::schema::bpf_program_maps::table
+            .count()
+            .get_result(&mut conn)
+            .unwrap();
+        assert_eq!(program_maps, 2);
+    }
+
+    #[test]
+    /// Tests that a program sharing the maps of an owner migrated
+    /// after it (program_1000 sorts before program_914) still records
+    /// its owner, and that a missing owner leaves the program owning
+    /// its maps.
+    fn test_migrate_map_owner_order() {
+        let db = setup_sled();
+        insert_xdp_program(&db, 914, None);
+        insert_xdp_program(&db, 1000, Some(914));
+        insert_xdp_program(&db, 1001, Some(77));
+
+        let mut conn = establish_connection(":memory:").unwrap();
+        migrate(&db, &mut conn, &mut MemorySecretStore::default()).expect("Migration failed");
+
+        let dependant = BpfProgram::find_record(&mut conn, 1000.into()).unwrap();
+        assert_eq!(dependant.map_owner_id, Some(914.into()));
+        let orphan = BpfProgram::find_record(&mut conn, 1001.into()).unwrap();
+        assert_eq!(orphan.map_owner_id, None);
+    }
+
+    #[test]
+    /// Tests that a map owner tree reports only the programs it
+    /// updated, and rejects an invalid owner ID and a sharer that owns
+    /// maps itself as invalid values of the keys they came from.
+    fn test_migrate_map_owner_tree() {
+        let db = setup_sled();
+        insert_xdp_program(&db, 914, None);
+        insert_xdp_program(&db, 920, None);
+        insert(
+            &db,
+            "map_914",
+            &[
+                ("map_used_by_0", &914u32.to_ne_bytes()),
+                ("map_used_by_1", &920u32.to_ne_bytes()),
+                ("map_used_by_2", &921u32.to_ne_bytes()),
+            ],
+        );
+        let mut conn = establish_connection(":memory:").unwrap();
+        let report =
+            migrate(&db, &mut conn, &mut MemorySecretStore::default()).expect("Migration failed");
+        let tree = report.trees.iter().find(|t| t.tree == "map_914").unwrap();
+        assert_eq!(
+            tree.outcome,
+            TreeOutcome::Migrated {
+                detail: "maps owned by 914, used by 1 programs, \
+                         skipped programs 921 which were not migrated"
+                    .to_string()
+            }
+        );
+        let sharer = BpfProgram::find_record(&mut conn, 920.into()).unwrap();
+        assert_eq!(sharer.map_owner_id, Some(914.into()));
+
+        let invalid = |db: &sled::Db| {
+            let mut conn = establish_connection(":memory:").unwrap();
+            match migrate(db, &mut conn, &mut MemorySecretStore::default()) {
+                Err(SledMigrationError::InvalidValue { tree, key, .. }) => (tree, key),
+                other => panic!("expected an invalid value, got {other:?}"),
+            }
+        };
+
+        // 914 owns the maps 920 uses, so cannot use those of 930.
+        insert_xdp_program(&db, 930, None);
+        insert(&db, "map_930", &[("map_used_by_0", &914u32.to_ne_bytes())]);
+        assert_eq!(
+            invalid(&db),
+            ("map_930".to_string(), "map_used_by_0".to_string())
+        );
+
+        let db = setup_sled();
+        insert(&db, "map_abc", &[("map_used_by_0", &1u32.to_ne_bytes())]);
+        assert_eq!(invalid(&db), ("map_abc".to_string(), "map_abc".to_string()));
+    }
+
+    #[test]
+    fn test_migrate_reports_skipped_trees() {
+        let db = setup_sled();
+        insert(&db, "100", &[("kernel_name", b"sd_fw_egress")]);
+        insert(&db, "map_999", &[("map_used_by_0", &999u32.to_ne_bytes())]);
+        insert(
+            &db,
+            "program_1",
+            &[("id", &1u32.to_ne_bytes()), ("kind", &1u32.to_ne_bytes())],
+        );
+
+        let mut conn = establish_connection(":memory:").unwrap();
+        let report =
+            migrate(&db, &mut conn, &mut MemorySecretStore::default()).expect("Migration failed");
+
+        assert_eq!(report.migrated(), 0);
+        let skipped: Vec<&str> = report.trees.iter().map(|t| t.tree.as_str()).collect();
+        assert!(skipped.contains(&"program_1"));
+        assert!(skipped.contains(&"map_999"));
+        assert!(skipped.contains(&"100"));
+    }
+
+    #[test]
+    /// Tests that the image cache becomes `images` rows, recovering the
+    /// URL from a program that was loaded from the image when there is
+    /// one and from the key prefix otherwise, and that the program's
+    /// registry credentials go to the secret store.
+    fn test_migrate_images() {
+        let db = setup_sled();
+        let manifest = |config: &str, layer: &str| {
+            format!(
+                r#"{{"config":{{"digest":"sha256:{config}"}},"layers":[{{"digest":"sha256:{layer}"}}]}}"#
+            )
+        };
+        let config = r#"{"config":{"Labels":{"io.ebpf.programs":"{\"stats\":\"tc\"}","io.ebpf.maps":"{\"tc_stats_map\":\"per_cpu_array\"}"}}}"#;
+
+        let tc = "quay.io_bpfman-bytecode_go_tc-counter_latest";
+        let xdp = "quay.io_bpfman-bytecode_go-xdp-counter_v1";
+        insert(
+            &db,
+            "__sled__default",
+            &[
+                (
+                    &format!("{tc}manifest.json"),
+                    manifest("aa", "bb").as_bytes(),
+                ),
+                (&format!("{tc}aa"), config.as_bytes()),
+                (&format!("{tc}bb"), &[0x1f, 0x8b]),
+                (
+                    &format!("{xdp}manifest.json"),
+                    manifest("cc", "dd").as_bytes(),
+                ),
+                (&format!("{xdp}cc"), b"{}"),
+            ],
+        );
+        insert(
+            &db,
+            "program_7",
+            &[
+                ("id", &7u32.to_ne_bytes()),
+                ("kind", &PROG_TYPE_TC.to_ne_bytes()),
+                ("name", b"stats"),
+                (
+                    "location_image_url",
+                    b"quay.io/bpfman-bytecode/go_tc-counter:latest",
+                ),
+                ("location_username", b"robot"),
+                ("location_password", b"hunter2"),
+                ("map_pin_path", b"/run/bpfman/fs/maps/7"),
+                ("tc_iface", b"eth0"),
+                ("tc_priority", &50i32.to_ne_bytes()),
+                ("tc_direction", b"ingress"),
+            ],
+        );
+
+        let mut secrets = MemorySecretStore::default();
+        let mut conn = establish_connection(":memory:").unwrap();
+        let report = migrate(&db, &mut conn, &mut secrets).expect("Migration failed");
+        assert_eq!(report.migrated(), 2, "{report}");
+
+        let tc_image =
+            Image::find_by_url(&mut conn, "quay.io/bpfman-bytecode/go_tc-counter:latest").unwrap();
+        assert_eq!(tc_image.digest, "sha256:aa");
+        assert_eq!(tc_image.layer, Some(vec![0x1f, 0x8b]));
+        assert_eq!(tc_image.programs(&mut conn).unwrap()[0].name, "stats");
+        assert_eq!(
+            tc_image.maps(&mut conn).unwrap()[0].map_type,
+            "per_cpu_array"
+        );
+
+        let program = BpfProgram::find_record(&mut conn, 7.into()).unwrap();
+        assert_eq!(program.image(&mut conn).unwrap(), Some(tc_image));
+        assert_eq!(
+            program.credentials(&secrets).unwrap(),
+            Some(Credentials {
+                username: "robot".to_string(),
+                password: "hunter2".to_string(),
+            })
+        );
+
+        let xdp_image =
+            Image::find_by_url(&mut conn, "quay.io/bpfman-bytecode/go-xdp-counter:v1").unwrap();
+        assert_eq!(xdp_image.tag.as_deref(), Some("v1"));
+        assert_eq!(xdp_image.layer, None);
+    }
+
+    #[test]
+    fn test_migrate_dispatchers() {
+        let db = setup_sled();
+        insert(
+            &db,
+            "tc_dispatcher_4026533525_10_ingress_2",
+            &[
+                ("direction", &(TC_DIRECTION_INGRESS as u32).to_ne_bytes()),
+                ("handle", &2u32.to_ne_bytes()),
+                ("if_index", &10u32.to_ne_bytes()),
+                ("if_name", b"eth0"),
+                ("nsid", &4026533525u64.to_ne_bytes()),
+                ("num_extension", &2u64.to_ne_bytes()),
+                ("priority", &50u16.to_ne_bytes()),
+                ("program_name", b"tc_dispatcher"),
+                ("revision", &2u32.to_ne_bytes()),
+            ],
+        );
+        insert(
+            &db,
+            "xdp_dispatcher_4026533525_10_3",
+            &[
+                ("if_index", &10u32.to_ne_bytes()),
+                ("if_name", b"eth0"),
+                ("mode", &1u32.to_ne_bytes()),
+                ("nsid", &4026533525u64.to_ne_bytes()),
+                ("num_extension", &3u64.to_ne_bytes()),
+                ("program_name", b"xdp_dispatcher"),
+                ("revision", &3u32.to_ne_bytes()),
+            ],
+        );
+
+        let mut conn = establish_connection(":memory:").unwrap();
+        let report =
+            migrate(&db, &mut conn, &mut MemorySecretStore::default()).expect("Migration failed");
+        assert_eq!(report.migrated(), 2);
+
+        let tc = TcDispatcher::find_record(&mut conn, 4026533525.into(), 10, Direction::Ingress)
+            .unwrap();
+        assert_eq!(tc.revision, 2);
+        assert_eq!(tc.priority, 50);
+        assert_eq!(tc.handle, Some(2));
+        assert_eq!(tc.num_extensions, 2);
+
+        let xdp = XdpDispatcher::find_record(&mut conn, 4026533525.into(), 10).unwrap();
+        assert_eq!(xdp.revision, 3);
+        assert_eq!(xdp.mode, 1);
+        assert_eq!(xdp.program_name.as_deref(), Some("xdp_dispatcher"));
+    }
+
+    #[test]
+    fn test_migrate_rolls_back_on_invalid_tree() {
+        let db = setup_sled();
+        insert_xdp_program(&db, 914, None);
+        insert(
+            &db,
+            "program_915",
+            &[
+                ("id", &915u32.to_ne_bytes()),
+                ("kind", &(PROG_TYPE_XDP as u32).to_ne_bytes()),
+                ("name", b"no_location"),
+                ("map_pin_path", b"/run/bpfman/fs/maps/915"),
+            ],
+        );
+
+        let mut conn = establish_connection(":memory:").unwrap();
+        let err = migrate(&db, &mut conn, &mut MemorySecretStore::default())
+            .expect_err("Migration should fail");
+        assert!(matches!(err, SledMigrationError::InvalidValue { .. }));
+
+        assert!(BpfProgram::find_all(&mut conn).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,682 @@
+//! Portable snapshots of the whole database.
+//!
+//! A [`Snapshot`] holds every row of every table, with binaries such
+//! as bytecode base64-encoded, and serialises to a single JSON
+//! document for `s2s export` and `s2s import`. Importing a snapshot
+//! into an empty database and exporting it again gives the same
+//! document, timestamps and event history included, so a snapshot
+//! attached to a bug report reproduces the state it was taken from.
+//!
+//! A snapshot records its format version and the last migration
+//! applied to the database it came from, and is only imported into a
+//! database at that same migration. Image registry credentials are
+//! kept outside the database, so are not included, and neither is
+//! `event_actor`, which only has a row inside a
+//! [`with_actor`](crate::models::with_actor) transaction.
+
+use diesel::{connection::SimpleConnection, prelude::*, sql_types::Text, sqlite::SqliteConnection};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    migrations::{self, MigrationError},
+    models::{
+        Attachment, BpfLink, BpfMap, BpfProgram, BpfProgramMap, BytecodeObject, Event,
+        FentryAttachment, FexitAttachment, Image, ImageMap, ImageProgram, KprobeAttachment,
+        TcAttachment, TcDispatcher, TcxAttachment, TracepointAttachment, UprobeAttachment,
+        XdpAttachment, XdpDispatcher,
+    },
+    schema::{
+        bpf_links, bpf_maps, bpf_program_maps, bpf_programs, bytecode_objects, events,
+        fentry_attachments, fexit_attachments, image_maps, image_programs, images,
+        kprobe_attachments, tc_attachments, tc_dispatchers, tcx_attachments,
+        tracepoint_attachments, uprobe_attachments, xdp_attachments, xdp_dispatchers,
+    },
+};
+
+/// The version of the snapshot format this build reads and writes.
+/// It changes when the document's layout does, not with the schema.
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("database error: {0}")]
+    Database(#[from] diesel::result::Error),
+
+    #[error("migration error: {0}")]
+    Migration(#[from] MigrationError),
+
+    #[error("snapshot format version {0} is not supported, expected {FORMAT_VERSION}")]
+    UnsupportedFormat(u32),
+
+    #[error("snapshot is of schema {snapshot}, but the database is at {database}")]
+    SchemaMismatch { snapshot: String, database: String },
+
+    #[error("the database is not empty: {0} has rows")]
+    NotEmpty(&'static str),
+
+    #[error("bytecode object {sha256} has bytes whose SHA-256 is {actual}")]
+    BytecodeDigest { sha256: String, actual: String },
+
+    #[error("event {id} has {field} that is not a JSON object: {source}")]
+    InvalidEvent {
+        id: i64,
+        field: &'static str,
+        source: serde_json::Error,
+    },
+}
+
+/// Every row of every table. Each table's rows are ordered by primary
+/// key, and attachments, which span a table per kind, by link ID.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Always [`FORMAT_VERSION`] when exported by this build.
+    pub format_version: u32,
+
+    /// Name of the last migration applied to the exported database,
+    /// e.g. `2026-10-16-200902_bytecode_objects`.
+    pub schema_version: String,
+
+    pub bytecode_objects: Vec<BytecodeObject>,
+    pub images: Vec<Image>,
+    pub image_programs: Vec<ImageProgram>,
+    pub image_maps: Vec<ImageMap>,
+    pub programs: Vec<BpfProgram>,
+    pub maps: Vec<BpfMap>,
+    pub program_maps: Vec<BpfProgramMap>,
+    pub links: Vec<BpfLink>,
+    pub attachments: Vec<Attachment>,
+    pub tc_dispatchers: Vec<TcDispatcher>,
+    pub xdp_dispatchers: Vec<XdpDispatcher>,
+    pub events: Vec<Event>,
+}
+
+/// Returns the name of the last migration applied to the database.
+fn schema_version(conn: &mut SqliteConnection) -> Result<String, SnapshotError> {
+    migrations::status(conn)?
+        .into_iter()
+        .rev()
+        .find(|m| m.applied)
+        .map(|m| m.name)
+        .ok_or(SnapshotError::Migration(MigrationError::NothingApplied))
+}
+
+#[derive(QueryableByName)]
+struct Trigger {
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Text)]
+    sql: String,
+}
+
+/// The triggers that record events, as the events migration names
+/// them.
+const EVENT_TRIGGERS: [&str; 9] = [
+    "bpf_links_events_delete",
+    "bpf_links_events_insert",
+    "bpf_links_events_update",
+    "bpf_maps_events_delete",
+    "bpf_maps_events_insert",
+    "bpf_maps_events_update",
+    "bpf_programs_events_delete",
+    "bpf_programs_events_insert",
+    "bpf_programs_events_update",
+];
+
+/// Returns the [`EVENT_TRIGGERS`] with their SQL, ordered by name.
+fn event_triggers(conn: &mut SqliteConnection) -> QueryResult<Vec<Trigger>> {
+    let names = EVENT_TRIGGERS.map(|name| format!("'{name}'")).join(", ");
+    diesel::sql_query(format!(
+        "SELECT name, sql FROM sqlite_schema \
+         WHERE type = 'trigger' AND name IN ({names}) ORDER BY name"
+    ))
+    .load(conn)
+}
+
+/// Drops the triggers that record events, runs `f`, then recreates
+/// them as they were, so that rows can be written without adding to
+/// the event history. Must be called inside a transaction, so that a
+/// failure leaves the triggers in place.
+fn without_event_triggers<T>(
+    conn: &mut SqliteConnection,
+    f: impl FnOnce(&mut SqliteConnection) -> Result<T, SnapshotError>,
+) -> Result<T, SnapshotError> {
+    let triggers = event_triggers(conn)?;
+    for trigger in &triggers {
+        conn.batch_execute(&format!("DROP TRIGGER \"{}\"", trigger.name))?;
+    }
+    let result = f(conn)?;
+    for trigger in &triggers {
+        conn.batch_execute(&trigger.sql)?;
+    }
+    Ok(result)
+}
+
+/// Rows per INSERT, which keeps the widest table, `bpf_programs`,
+/// within SQLite's limit of 32766 bound parameters per statement.
+const ROWS_PER_INSERT: usize = 500;
+
+/// Inserts `rows` into `table` as they are, a batch at a time.
+macro_rules! insert_rows {
+    ($conn:expr, $table:ident, $rows:expr) => {
+        for rows in $rows.chunks(ROWS_PER_INSERT) {
+            diesel::insert_into($table::table)
+                .values(rows)
+                .execute($conn)?;
+        }
+    };
+}
+
+impl Snapshot {
+    /// Reads every table, in one transaction so that the snapshot is
+    /// consistent.
+    pub fn export(conn: &mut SqliteConnection) -> Result<Snapshot, SnapshotError> {
+        conn.transaction(|conn| {
+            let mut attachments: Vec<Attachment> = Vec::new();
+            attachments.extend(
+                xdp_attachments::table
+                    .load::<XdpAttachment>(conn)?
+                    .into_iter()
+                    .map(Attachment::Xdp),
+            );
+            attachments.extend(
+                tc_attachments::table
+                    .load::<TcAttachment>(conn)?
+                    .into_iter()
+                    .map(Attachment::Tc),
+            );
+            attachments.extend(
+                tcx_attachments::table
+                    .load::<TcxAttachment>(conn)?
+                    .into_iter()
+                    .map(Attachment::Tcx),
+            );
+            attachments.extend(
+                kprobe_attachments::table
+                    .load::<KprobeAttachment>(conn)?
+                    .into_iter()
+                    .map(Attachment::Kprobe),
+            );
+            attachments.extend(
+                uprobe_attachments::table
+                    .load::<UprobeAttachment>(conn)?
+                    .into_iter()
+                    .map(Attachment::Uprobe),
+            );
+            attachments.extend(
+                tracepoint_attachments::table
+                    .load::<TracepointAttachment>(conn)?
+                    .into_iter()
+                    .map(Attachment::Tracepoint),
+            );
+            attachments.extend(
+                fentry_attachments::table
+                    .load::<FentryAttachment>(conn)?
+                    .into_iter()
+                    .map(Attachment::Fentry),
+            );
+            attachments.extend(
+                fexit_attachments::table
+                    .load::<FexitAttachment>(conn)?
+                    .into_iter()
+                    .map(Attachment::Fexit),
+            );
+            attachments.sort_by_key(Attachment::link_id);
+
+            Ok(Snapshot {
+                format_version: FORMAT_VERSION,
+                schema_version: schema_version(conn)?,
+                bytecode_objects: bytecode_objects::table
+                    .order(bytecode_objects::sha256.asc())
+                    .load(conn)?,
+                images: images::table.order(images::url.asc()).load(conn)?,
+                image_programs: image_programs::table
+                    .order((image_programs::image_url.asc(), image_programs::name.asc()))
+                    .load(conn)?,
+                image_maps: image_maps::table
+                    .order((image_maps::image_url.asc(), image_maps::name.asc()))
+                    .load(conn)?,
+                programs: bpf_programs::table
+                    .order(bpf_programs::id.asc())
+                    .load(conn)?,
+                maps: bpf_maps::table.order(bpf_maps::id.asc()).load(conn)?,
+                program_maps: bpf_program_maps::table
+                    .order((
+                        bpf_program_maps::program_id.asc(),
+                        bpf_program_maps::map_id.asc(),
+                    ))
+                    .load(conn)?,
+                links: bpf_links::table.order(bpf_links::id.asc()).load(conn)?,
+                attachments,
+                tc_dispatchers: tc_dispatchers::table
+                    .order((
+                        tc_dispatchers::nsid.asc(),
+                        tc_dispatchers::if_index.asc(),
+                        tc_dispatchers::direction.asc(),
+                    ))
+                    .load(conn)?,
+                xdp_dispatchers: xdp_dispatchers::table
+                    .order((xdp_dispatchers::nsid.asc(), xdp_dispatchers::if_index.asc()))
+                    .load(conn)?,
+                events: events::table.order(events::id.asc()).load(conn)?,
+            })
+        })
+    }
+
+    /// Writes every row of the snapshot, as it was exported, into an
+    /// empty database at the same schema version. Rows are written
+    /// with their stored timestamps, and without recording events, so
+    /// the event history is the snapshot's own.
+    ///
+    /// Fails before writing anything if a bytecode object's bytes do
+    /// not match its digest or an event's values are not JSON objects.
+    /// Everything is written in one transaction; if any row is
+    /// rejected, nothing is.
+    pub fn import(&self, conn: &mut SqliteConnection) -> Result<(), SnapshotError> {
+        if self.format_version != FORMAT_VERSION {
+            return Err(SnapshotError::UnsupportedFormat(self.format_version));
+        }
+
+        conn.transaction(|conn| {
+            let database = schema_version(conn)?;
+            if database != self.schema_version {
+                return Err(SnapshotError::SchemaMismatch {
+                    snapshot: self.schema_version.clone(),
+                    database,
+                });
+            }
+            ensure_empty(conn)?;
+            without_event_triggers(conn, |conn| self.insert(conn))
+        })
+    }
+
+    /// Checks what the schema cannot: that each bytecode object's
+    /// digest is that of its bytes, and that each event's values are
+    /// JSON objects, as [`Event::old_values`] and [`Event::new_values`]
+    /// expect.
+    fn validate(&self) -> Result<(), SnapshotError> {
+        for object in &self.bytecode_objects {
+            let actual = BytecodeObject::digest(&object.bytes);
+            if actual != object.sha256 {
+                return Err(SnapshotError::BytecodeDigest {
+                    sha256: object.sha256.clone(),
+                    actual,
+                });
+            }
+        }
+        for event in &self.events {
+            let invalid = |field| {
+                move |source| SnapshotError::InvalidEvent {
+                    id: event.id,
+                    field,
+                    source,
+                }
+            };
+            event.old_values().map_err(invalid("old_values"))?;
+            event.new_values().map_err(invalid("new_values"))?;
+        }
+        Ok(())
+    }
+
+    fn insert(&self, conn: &mut SqliteConnection) -> Result<(), SnapshotError> {
+        self.validate()?;
+        insert_rows!(conn, bytecode_objects, self.bytecode_objects);
+        insert_rows!(conn, images, self.images);
+        insert_rows!(conn, image_programs, self.image_programs);
+        insert_rows!(conn, image_maps, self.image_maps);
+        // Map owners must exist before the programs sharing their maps,
+        // whatever their IDs.
+        let (owners, sharers): (Vec<BpfProgram>, Vec<BpfProgram>) = self
+            .programs
+            .iter()
+            .cloned()
+            .partition(|p| p.map_owner_id.is_none());
+        insert_rows!(conn, bpf_programs, owners);
+        insert_rows!(conn, bpf_programs, sharers);
+        insert_rows!(conn, bpf_maps, self.maps);
+        insert_rows!(conn, bpf_program_maps, self.program_maps);
+        insert_rows!(conn, bpf_links, self.links);
+        for attachment in &self.attachments {
+            attachment.create_record(conn)?;
+        }
+        insert_rows!(conn, tc_dispatchers, self.tc_dispatchers);
+        insert_rows!(conn, xdp_dispatchers, self.xdp_dispatchers);
+        insert_rows!(conn, events, self.events);
+        Ok(())
+    }
+}
+
+/// Fails with the first table that has rows.
+fn ensure_empty(conn: &mut SqliteConnection) -> Result<(), SnapshotError> {
+    let counts: [(&'static str, i64); 19] = [
+        (
+            "bytecode_objects",
+            bytecode_objects::table.count().get_result(conn)?,
+        ),
+        ("images", images::table.count().get_result(conn)?),
+        (
+            "image_programs",
+            image_programs::table.count().get_result(conn)?,
+        ),
+        ("image_maps", image_maps::table.count().get_result(conn)?),
+        (
+            "bpf_programs",
+            bpf_programs::table.count().get_result(conn)?,
+        ),
+        ("bpf_maps", bpf_maps::table.count().get_result(conn)?),
+        (
+            "bpf_program_maps",
+            bpf_program_maps::table.count().get_result(conn)?,
+        ),
+        ("bpf_links", bpf_links::table.count().get_result(conn)?),
+        (
+            "xdp_attachments",
+            xdp_attachments::table.count().get_result(conn)?,
+        ),
+        (
+            "tc_attachments",
+            tc_attachments::table.count().get_result(conn)?,
+        ),
+        (
+            "tcx_attachments",
+            tcx_attachments::table.count().get_result(conn)?,
+        ),
+        (
+            "kprobe_attachments",
+            kprobe_attachments::table.count().get_result(conn)?,
+        ),
+        (
+            "uprobe_attachments",
+            uprobe_attachments::table.count().get_result(conn)?,
+        ),
+        (
+            "tracepoint_attachments",
+            tracepoint_attachments::table.count().get_result(conn)?,
+        ),
+        (
+            "fentry_attachments",
+            fentry_attachments::table.count().get_result(conn)?,
+        ),
+        (
+            "fexit_attachments",
+            fexit_attachments::table.count().get_result(conn)?,
+        ),
+        (
+            "tc_dispatchers",
+            tc_dispatchers::table.count().get_result(conn)?,
+        ),
+        (
+            "xdp_dispatchers",
+            xdp_dispatchers::table.count().get_result(conn)?,
+        ),
+        ("events", events::table.count().get_result(conn)?),
+    ];
+    match counts.iter().find(|(_, count)| *count > 0) {
+        Some((table, _)) => Err(SnapshotError::NotEmpty(table)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        establish_connection,
+        models::{LinkState, LocationType, MapType, ProgramKind, with_actor},
+        service::{LoadLink, LoadProgram, load_program},
+    };
+
+    const BYTECODE: &[u8] = &[0x7F, 0x45, 0x4C, 0x46, 0x02, 0x01, 0x01, 0xFF];
+
+    fn program(id: u32, name: &str, kind: ProgramKind) -> BpfProgram {
+        BpfProgram {
+            id: id.into(),
+            name: name.to_string(),
+            kind,
+            location_type: LocationType::File,
+            file_path: Some("/path/to/prog.o".to_string()),
+            map_pin_path: format!("/run/bpfman/fs/maps/{id}"),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a database with a row in every table,
+    /// including a map owner whose ID is higher than a program sharing
+    /// its maps, and events made by a named actor.
+    fn populated() -> SqliteConnection {
+        let mut conn = establish_connection(":memory:").unwrap();
+
+        let link = BpfLink {
+            id: 50.into(),
+            program_id: 5.into(),
+            link_type: Some(ProgramKind::Xdp),
+            target: Some("eth0".to_string()),
+            state: LinkState::Attached,
+            ..Default::default()
+        };
+        let attachment = Attachment::Xdp(XdpAttachment {
+            link_id: 50.into(),
+            iface: "eth0".to_string(),
+            if_index: Some(2),
+            priority: 50,
+            proceed_on: "[2]".to_string(),
+            current_position: Some(0),
+            attached: true,
+            nsid: Some(4026531840.into()),
+        });
+        load_program(
+            &mut conn,
+            &LoadProgram {
+                program: program(5, "xdp_stats", ProgramKind::Xdp),
+                bytecode: BYTECODE.to_vec(),
+                maps: vec![BpfMap {
+                    id: 51.into(),
+                    name: "xdp_stats_map".to_string(),
+                    map_type: Some(MapType::PerCpuArray),
+                    key_size: Some(4),
+                    value_size: Some(16),
+                    max_entries: Some(5),
+                    ..Default::default()
+                }],
+                links: vec![LoadLink { link, attachment }],
+            },
+        )
+        .unwrap();
+
+        let mut sharer = program(3, "trace_open", ProgramKind::Tracepoint);
+        sharer.map_owner_id = Some(5.into());
+        sharer.set_bytecode(&mut conn, BYTECODE).unwrap();
+        BpfProgram::create_record(&mut conn, &mut sharer).unwrap();
+        let mut link = BpfLink {
+            id: 30.into(),
+            program_id: 3.into(),
+            link_type: Some(ProgramKind::Tracepoint),
+            ..Default::default()
+        };
+        BpfLink::create_record(&mut conn, &mut link).unwrap();
+        Attachment::Tracepoint(TracepointAttachment {
+            link_id: 30.into(),
+            tracepoint: "syscalls/sys_enter_openat".to_string(),
+        })
+        .create_record(&mut conn)
+        .unwrap();
+
+        with_actor(&mut conn, "alice", |conn| {
+            let mut map = BpfMap::find_record(conn, 51.into())?;
+            map.max_entries = Some(10);
+            map.update_record(conn)
+        })
+        .unwrap();
+
+        let image = Image::from_oci(
+            "quay.io/bpfman-bytecode/xdp_stats:latest",
+            r#"{"config": {"digest": "sha256:00"}}"#,
+            r#"{"config": {"Labels": {"io.ebpf.programs": "{\"xdp_stats\":\"xdp\"}", "io.ebpf.maps": "{\"xdp_stats_map\":\"per_cpu_array\"}"}}}"#,
+            Some(vec![0x1F, 0x8B, 0x08, 0x00]),
+        )
+        .unwrap();
+        Image::create_record(&mut conn, &image).unwrap();
+
+        TcDispatcher::create_record(
+            &mut conn,
+            &mut TcDispatcher {
+                nsid: 4026531840.into(),
+                if_index: 2,
+                if_name: "eth0".to_string(),
+                priority: 50,
+                num_extensions: 1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        XdpDispatcher::create_record(
+            &mut conn,
+            &mut XdpDispatcher {
+                nsid: 4026531840.into(),
+                if_index: 2,
+                if_name: "eth0".to_string(),
+                num_extensions: 1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        conn
+    }
+
+    fn event_trigger_sql(conn: &mut SqliteConnection) -> Vec<String> {
+        let triggers = event_triggers(conn).unwrap();
+        assert_eq!(triggers.len(), EVENT_TRIGGERS.len());
+        triggers.into_iter().map(|t| t.sql).collect()
+    }
+
+    #[test]
+    /// Tests that a snapshot imported into an empty database exports
+    /// as the same document, without adding events, and that events
+    /// are recorded again afterwards.
+    fn test_export_import_roundtrip() {
+        let mut conn = populated();
+        let snapshot = Snapshot::export(&mut conn).unwrap();
+        assert_eq!(snapshot.programs.len(), 2);
+        assert_eq!(snapshot.bytecode_objects.len(), 1);
+        assert_eq!(snapshot.attachments.len(), 2);
+        assert!(
+            snapshot
+                .events
+                .iter()
+                .any(|e| e.actor.as_deref() == Some("alice"))
+        );
+        let json = serde_json::to_string_pretty(&snapshot).unwrap();
+        assert!(json.contains(r#""bytes": "f0VMRgIBAf8=""#), "{json}");
+        assert!(json.contains(r#""layer": "H4sIAA==""#), "{json}");
+
+        let mut imported = establish_connection(":memory:").unwrap();
+        let triggers = event_trigger_sql(&mut imported);
+        serde_json::from_str::<Snapshot>(&json)
+            .unwrap()
+            .import(&mut imported)
+            .unwrap();
+        let exported = Snapshot::export(&mut imported).unwrap();
+        assert_eq!(exported, snapshot);
+        assert_eq!(serde_json::to_string_pretty(&exported).unwrap(), json);
+
+        assert_eq!(event_trigger_sql(&mut imported), triggers);
+        let mut map = BpfMap {
+            id: 52.into(),
+            name: "new_map".to_string(),
+            ..Default::default()
+        };
+        BpfMap::create_record(&mut imported, &mut map).unwrap();
+        let events: i64 = events::table.count().get_result(&mut imported).unwrap();
+        assert_eq!(events as usize, snapshot.events.len() + 1);
+    }
+
+    #[test]
+    fn test_import_is_checked() {
+        let mut conn = populated();
+        let snapshot = Snapshot::export(&mut conn).unwrap();
+
+        assert!(matches!(
+            snapshot.import(&mut conn),
+            Err(SnapshotError::NotEmpty("bytecode_objects"))
+        ));
+
+        let mut empty = establish_connection(":memory:").unwrap();
+        let mut other = snapshot.clone();
+        other.format_version = FORMAT_VERSION + 1;
+        assert!(matches!(
+            other.import(&mut empty),
+            Err(SnapshotError::UnsupportedFormat(_))
+        ));
+
+        let mut other = snapshot.clone();
+        other.schema_version = "2025-02-12-140102_create_initial_schema".to_string();
+        let err = other.import(&mut empty).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "snapshot is of schema 2025-02-12-140102_create_initial_schema, \
+                 but the database is at {}",
+                snapshot.schema_version
+            )
+        );
+    }
+
+    #[test]
+    /// Tests that bytecode whose digest is wrong and event values that
+    /// are not JSON objects are rejected before anything is written.
+    fn test_import_validates_rows() {
+        let snapshot = Snapshot::export(&mut populated()).unwrap();
+        let mut conn = establish_connection(":memory:").unwrap();
+
+        let mut other = snapshot.clone();
+        other.bytecode_objects[0].bytes.push(0);
+        let actual = BytecodeObject::digest(&other.bytecode_objects[0].bytes);
+        let err = other.import(&mut conn).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "bytecode object {} has bytes whose SHA-256 is {actual}",
+                snapshot.bytecode_objects[0].sha256
+            )
+        );
+        ensure_empty(&mut conn).unwrap();
+
+        for field in ["old_values", "new_values"] {
+            let mut other = snapshot.clone();
+            let event = other.events.last_mut().unwrap();
+            let values = match field {
+                "old_values" => &mut event.old_values,
+                _ => &mut event.new_values,
+            };
+            *values = Some("[1, 2]".to_string());
+            let id = event.id;
+            assert!(matches!(
+                other.import(&mut conn),
+                Err(SnapshotError::InvalidEvent { id: i, field: f, .. }) if i == id && f == field
+            ));
+            ensure_empty(&mut conn).unwrap();
+        }
+
+        snapshot.import(&mut conn).unwrap();
+    }
+
+    #[test]
+    /// Tests that a snapshot the schema rejects part way through
+    /// leaves the database empty, with its event triggers in place.
+    fn test_failed_import_rolls_back() {
+        let mut snapshot = Snapshot::export(&mut populated()).unwrap();
+        snapshot.links[1].program_id = 999.into();
+
+        let mut conn = establish_connection(":memory:").unwrap();
+        let triggers = event_trigger_sql(&mut conn);
+        let err = snapshot.import(&mut conn).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("link type does not match the kind of its program"),
+            "{err}"
+        );
+        ensure_empty(&mut conn).unwrap();
+        assert_eq!(event_trigger_sql(&mut conn), triggers);
+    }
+}
@@ -0,0 +1,27 @@
+//! Helpers shared by the unit tests.
+
+use std::path::PathBuf;
+
+/// A database file in the temp directory, removed with its WAL files
+/// on drop so that a failing test does not leave them behind.
+pub(crate) struct TempDatabase(PathBuf);
+
+impl TempDatabase {
+    /// Names the file after `name` and the process ID, so `name` must
+    /// be unique among the crate's tests.
+    pub(crate) fn new(name: &str) -> Self {
+        TempDatabase(std::env::temp_dir().join(format!("s2s-{name}-{}.db", std::process::id())))
+    }
+
+    pub(crate) fn url(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempDatabase {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", self.url()));
+        }
+    }
+}
@@ -12,6 +12,49 @@
 //! not exactly match the type’s byte width. This eliminates silent
 //! truncation or padding errors.
 //!
+//! ## Signed integers
+//!
+//! [`I32Blob`], [`I64Blob`] and [`I128Blob`] store signed integers the
+//! same way, except that the sign bit is flipped before writing. Plain
+//! two's complement would sort every negative value after every
+//! positive one; with the sign bit flipped, `MIN` encodes as all
+//! zeroes, `-1` as `0x7f..ff`, `0` as `0x80..00` and `MAX` as all
+//! ones, so BLOB comparison again matches numeric order.
+//!
+//! ## Arithmetic in SQL
+//!
+//! SQLite's own arithmetic and aggregates treat BLOBs as opaque, so
+//! [`register_functions`] adds SQL functions that understand unsigned
+//! blobs of any width from 1 to 16 bytes: [`ublob_add`], the
+//! aggregate [`ublob_sum`] and [`ublob_to_text`]. Sums are as wide as
+//! the widest input, so the sum of a `U64Blob` column reads back as a
+//! `U64Blob`; a sum that does not fit fails the query. The functions
+//! do not understand the signed wrappers' encoding.
+//! [`establish_connection`](crate::establish_connection) registers
+//! them.
+//!
+//! ## Changing width
+//!
+//! Widening conversions between wrappers are [`From`] impls and
+//! narrowing ones [`TryFrom`], failing if the value does not fit. To
+//! change the width of a stored column, [`resize_blob_column`]
+//! rewrites every value with the `ublob_resize(blob, width)` SQL
+//! function, which fails, leaving the column untouched, if any value
+//! does not fit. Signed columns need their sign bit moved, so are
+//! resized with [`resize_signed_blob_column`] and `iblob_resize`
+//! instead. Migrations run by
+//! [`establish_connection`](crate::establish_connection) can call
+//! both SQL functions directly.
+//!
+//! ## Other backends
+//!
+//! With the `postgres` or `mysql` feature the wrappers also implement
+//! [`ToSql`] and [`FromSql`] for Diesel's `Pg` and `Mysql` backends,
+//! for `bytea` and `BINARY`/`VARBINARY`/`BLOB` columns respectively.
+//! Both compare binary values byte by byte, so the encoding keeps its
+//! ordering guarantees there. The `ublob_*` SQL functions are SQLite
+//! only.
+//!
 //! ## Diesel Integration
 //!
 //! These types integrate seamlessly with Diesel's SQLite backend.
@@ -93,19 +136,22 @@
 
 use diesel::{
     backend::Backend,
+    define_sql_function,
     deserialize::{FromSql, FromSqlRow},
     expression::AsExpression,
+    query_dsl::RunQueryDsl,
+    result::QueryResult,
     serialize::{IsNull, Output, ToSql},
-    sql_types::Binary,
-    sqlite::Sqlite,
+    sql_types::{Binary, Integer, Nullable, Text},
+    sqlite::{Sqlite, SqliteAggregateFunction, SqliteConnection},
 };
 use serde::{Deserialize, Serialize};
 
-/// Error type for decoding byte slices into unsigned integer wrappers.
+/// Error type for decoding byte slices into integer wrappers.
 ///
 /// These errors occur when validating binary input, typically read
 /// from SQLite BLOB columns, during deserialisation into typed
-/// wrappers like [`U32Blob`].
+/// wrappers like [`U32Blob`] or [`I64Blob`].
 #[derive(Debug, Clone, PartialEq)]
 pub enum UnsignedIntBlobError {
     /// Error when the byte slice has an invalid size for the
@@ -119,12 +165,21 @@ pub enum UnsignedIntBlobError {
     ///
     /// * `expected` - The number of bytes expected for the requested type
     /// * `actual` - The actual number of bytes in the provided slice
-    /// * `type_name` - The name of the requested type (e.g., "u16", "u32")
+    /// * `type_name` - The name of the requested type (e.g., "u16", "i64")
     InvalidSize {
         expected: usize,
         actual: usize,
         type_name: String,
     },
+
+    /// Error when a `ublob_*` SQL function is given a BLOB that is
+    /// empty or wider than 16 bytes.
+    UnsupportedSize { actual: usize },
+
+    /// Error when a value does not fit in `width` bytes: the result
+    /// of a `ublob_*` SQL function wider than its widest argument, or
+    /// a narrowing conversion of a value that is too large.
+    Overflow { width: usize },
 }
 
 impl std::fmt::Display for UnsignedIntBlobError {
@@ -141,6 +196,16 @@ impl std::fmt::Display for UnsignedIntBlobError {
                     expected, type_name, actual
                 )
             }
+            Self::UnsupportedSize { actual } => {
+                write!(
+                    f,
+                    "Invalid input size: expected 1 to 16 bytes, got {}",
+                    actual
+                )
+            }
+            Self::Overflow { width } => {
+                write!(f, "Integer overflow: value does not fit in {} bytes", width)
+            }
         }
     }
 }
@@ -230,6 +295,83 @@ macro_rules! define_uint_blob {
             }
         }
 
+        impl_blob_common!($name, $type);
+    };
+}
+
+macro_rules! define_int_blob {
+    ($name:ident, $type:ty, $unsigned:ty) => {
+        /// A wrapper that stores a signed integer as a fixed-size
+        /// big-endian byte array with the sign bit flipped.
+        ///
+        /// Flipping the sign bit maps `MIN..=MAX` onto `0..=MAX` of
+        /// the unsigned type of the same width, so numeric ordering is
+        /// preserved when values are stored as BLOBs and compared
+        /// lexicographically, such as in SQLite.
+        ///
+        /// Internally, this wrapper has the same memory layout as the
+        /// underlying integer due to `#[repr(transparent)]`.
+        #[derive(
+            Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow,
+        )]
+        #[diesel(sql_type = Binary)]
+        #[repr(transparent)]
+        pub struct $name($type);
+
+        impl $name {
+            /// The sign bit of the underlying type.
+            const SIGN_BIT: $unsigned = 1 << (<$unsigned>::BITS - 1);
+
+            /// Returns a copy of the inner value.
+            pub fn get(&self) -> $type {
+                self.0
+            }
+
+            /// Consumes the blob, returning the inner value.
+            pub fn into_inner(self) -> $type {
+                self.0
+            }
+
+            /// Converts the inner value to a byte vector in
+            /// big-endian order with the sign bit flipped.
+            ///
+            /// See the unsigned wrappers' `to_bytes` for why this
+            /// allocates.
+            fn to_bytes(self) -> Vec<u8> {
+                ((self.0 as $unsigned) ^ Self::SIGN_BIT)
+                    .to_be_bytes()
+                    .to_vec()
+            }
+
+            /// Decodes a big-endian, sign-flipped byte slice into the
+            /// wrapped integer type, validating its length as the
+            /// unsigned wrappers do.
+            fn from_bytes(bytes: &[u8]) -> Result<Self, UnsignedIntBlobError> {
+                const EXPECTED_SIZE: usize = std::mem::size_of::<$type>();
+
+                let array: Result<[u8; EXPECTED_SIZE], _> = bytes.try_into();
+
+                match array {
+                    Ok(byte_array) => Ok($name(
+                        (<$unsigned>::from_be_bytes(byte_array) ^ Self::SIGN_BIT) as $type,
+                    )),
+                    Err(_) => Err(UnsignedIntBlobError::InvalidSize {
+                        expected: EXPECTED_SIZE,
+                        actual: bytes.len(),
+                        type_name: std::any::type_name::<$type>().to_string(),
+                    }),
+                }
+            }
+        }
+
+        impl_blob_common!($name, $type);
+    };
+}
+
+/// Implements the conversions and Diesel traits shared by every blob
+/// wrapper in terms of its `to_bytes` and `from_bytes`.
+macro_rules! impl_blob_common {
+    ($name:ident, $type:ty) => {
         /// Allows constructing this wrapper from the underlying type
         /// via [`From`].
         impl From<$type> for $name {
@@ -257,6 +399,39 @@ macro_rules! define_uint_blob {
                 Self::from_bytes(&blob).map_err(|e| e.into())
             }
         }
+
+        #[cfg(feature = "postgres")]
+        impl_blob_backend!($name, diesel::pg::Pg);
+
+        #[cfg(feature = "mysql")]
+        impl_blob_backend!($name, diesel::mysql::Mysql);
+    };
+}
+
+/// Implements [`ToSql`] and [`FromSql`] for a backend whose [`Output`]
+/// takes the encoded bytes through [`std::io::Write`], as every
+/// backend other than SQLite does.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+macro_rules! impl_blob_backend {
+    ($name:ident, $backend:ty) => {
+        impl ToSql<Binary, $backend> for $name {
+            fn to_sql<'b>(
+                &'b self,
+                out: &mut Output<'b, '_, $backend>,
+            ) -> diesel::serialize::Result {
+                std::io::Write::write_all(out, &self.to_bytes())?;
+                Ok(IsNull::No)
+            }
+        }
+
+        impl FromSql<Binary, $backend> for $name {
+            fn from_sql(
+                bytes: <$backend as Backend>::RawValue<'_>,
+            ) -> diesel::deserialize::Result<Self> {
+                let blob = <Vec<u8> as FromSql<Binary, $backend>>::from_sql(bytes)?;
+                Self::from_bytes(&blob).map_err(|e| e.into())
+            }
+        }
     };
 }
 
@@ -266,11 +441,319 @@ define_uint_blob!(U32Blob, u32);
 define_uint_blob!(U64Blob, u64);
 define_uint_blob!(U128Blob, u128);
 
+define_int_blob!(I32Blob, i32, u32);
+define_int_blob!(I64Blob, i64, u64);
+define_int_blob!(I128Blob, i128, u128);
+
+/// Implements lossless [`From`] conversions from a blob wrapper to
+/// each wider one, and checked [`TryFrom`] conversions back.
+macro_rules! impl_blob_conversions {
+    ($narrow:ident, $narrow_type:ty => $($wide:ident, $wide_type:ty);+) => {
+        $(
+            impl From<$narrow> for $wide {
+                fn from(value: $narrow) -> Self {
+                    $wide(<$wide_type>::from(value.0))
+                }
+            }
+
+            impl TryFrom<$wide> for $narrow {
+                type Error = UnsignedIntBlobError;
+
+                fn try_from(value: $wide) -> Result<Self, Self::Error> {
+                    <$narrow_type>::try_from(value.0)
+                        .map($narrow)
+                        .map_err(|_| UnsignedIntBlobError::Overflow {
+                            width: std::mem::size_of::<$narrow_type>(),
+                        })
+                }
+            }
+        )+
+    };
+}
+
+impl_blob_conversions!(U8Blob, u8 => U16Blob, u16; U32Blob, u32; U64Blob, u64; U128Blob, u128);
+impl_blob_conversions!(U16Blob, u16 => U32Blob, u32; U64Blob, u64; U128Blob, u128);
+impl_blob_conversions!(U32Blob, u32 => U64Blob, u64; U128Blob, u128);
+impl_blob_conversions!(U64Blob, u64 => U128Blob, u128);
+impl_blob_conversions!(I32Blob, i32 => I64Blob, i64; I128Blob, i128);
+impl_blob_conversions!(I64Blob, i64 => I128Blob, i128);
+
+define_sql_function! {
+    /// Adds two unsigned integer blobs, returning a blob as wide as
+    /// the wider argument.
+    fn ublob_add(a: Binary, b: Binary) -> Binary;
+}
+
+define_sql_function! {
+    /// Sums an unsigned integer blob column, returning a blob as wide
+    /// as the widest value, or NULL if there are no rows.
+    ///
+    /// NULLs are not skipped; filter them out of nullable columns and
+    /// use `assume_not_null()`.
+    #[aggregate]
+    fn ublob_sum(expr: Binary) -> Nullable<Binary>;
+}
+
+define_sql_function! {
+    /// Formats an unsigned integer blob as decimal text.
+    fn ublob_to_text(a: Binary) -> Text;
+}
+
+define_sql_function! {
+    /// Re-encodes an unsigned integer blob as `width` bytes, failing if
+    /// the value does not fit.
+    fn ublob_resize(a: Binary, width: Integer) -> Binary;
+}
+
+define_sql_function! {
+    /// Re-encodes a signed integer blob, as written by [`I32Blob`] and
+    /// the other signed wrappers, as `width` bytes, failing if the
+    /// value does not fit.
+    fn iblob_resize(a: Binary, width: Integer) -> Binary;
+}
+
+/// Registers the `ublob_*` SQL functions on a connection. Must be
+/// called on every connection that uses them.
+pub fn register_functions(conn: &mut SqliteConnection) -> QueryResult<()> {
+    ublob_add_utils::register_impl(conn, |a: Vec<u8>, b: Vec<u8>| {
+        SqlResult((|| {
+            let width = blob_width(&a)?.max(blob_width(&b)?);
+            let sum = decode_blob(&a)?
+                .checked_add(decode_blob(&b)?)
+                .ok_or(UnsignedIntBlobError::Overflow { width })?;
+            encode_blob(sum, width)
+        })())
+    })?;
+    ublob_sum_utils::register_impl::<BlobSum, Vec<u8>>(conn)?;
+    ublob_to_text_utils::register_impl(conn, |a: Vec<u8>| {
+        SqlResult(decode_blob(&a).map(|value| value.to_string()))
+    })?;
+    ublob_resize_utils::register_impl(conn, |a: Vec<u8>, width: i32| {
+        SqlResult((|| {
+            let width = resize_width(width)?;
+            encode_blob(decode_blob(&a)?, width)
+        })())
+    })?;
+    iblob_resize_utils::register_impl(conn, |a: Vec<u8>, width: i32| {
+        SqlResult((|| {
+            let width = resize_width(width)?;
+            encode_signed_blob(decode_signed_blob(&a)?, width)
+        })())
+    })?;
+    Ok(())
+}
+
+/// Rewrites every non-NULL value of the BLOB column `table.column` as
+/// an unsigned integer blob of `width` bytes, e.g. 8 to turn a
+/// `U32Blob` column into a `U64Blob` one. Returns the number of rows
+/// rewritten.
+///
+/// The rewrite is a single statement: if any value does not fit in
+/// `width` bytes, or is not a valid unsigned blob, it fails with
+/// [`UnsignedIntBlobError`] and no row is changed. Requires
+/// [`register_functions`].
+///
+/// This is for unsigned columns only: it would read a signed
+/// wrapper's flipped sign bit as part of the value, so widening an
+/// `I32Blob` column with it turns every value into a different
+/// positive one. Use [`resize_signed_blob_column`] for those.
+pub fn resize_blob_column(
+    conn: &mut SqliteConnection,
+    table: &str,
+    column: &str,
+    width: usize,
+) -> QueryResult<usize> {
+    resize_column(conn, "ublob_resize", table, column, width)
+}
+
+/// Rewrites every non-NULL value of the BLOB column `table.column` as
+/// a signed integer blob of `width` bytes, e.g. 8 to turn an
+/// `I32Blob` column into an `I64Blob` one, as [`resize_blob_column`]
+/// does for unsigned columns.
+pub fn resize_signed_blob_column(
+    conn: &mut SqliteConnection,
+    table: &str,
+    column: &str,
+    width: usize,
+) -> QueryResult<usize> {
+    resize_column(conn, "iblob_resize", table, column, width)
+}
+
+/// Rewrites `table.column` with the resize SQL function `function`.
+fn resize_column(
+    conn: &mut SqliteConnection,
+    function: &str,
+    table: &str,
+    column: &str,
+    width: usize,
+) -> QueryResult<usize> {
+    if !(1..=16).contains(&width) {
+        let err = UnsignedIntBlobError::UnsupportedSize { actual: width };
+        return Err(diesel::result::Error::QueryBuilderError(Box::new(err)));
+    }
+    let table = quote_identifier(table);
+    let column = quote_identifier(column);
+
+    diesel::sql_query(format!(
+        "UPDATE {table} SET {column} = {function}({column}, {width}) WHERE {column} IS NOT NULL"
+    ))
+    .execute(conn)
+}
+
+/// Validates a width given to `ublob_resize`.
+fn resize_width(width: i32) -> Result<usize, UnsignedIntBlobError> {
+    match width {
+        1..=16 => Ok(width as usize),
+        _ => Err(UnsignedIntBlobError::UnsupportedSize {
+            actual: width.max(0) as usize,
+        }),
+    }
+}
+
+/// Quotes an SQL identifier.
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Returns the width of a blob accepted by the `ublob_*` functions.
+fn blob_width(bytes: &[u8]) -> Result<usize, UnsignedIntBlobError> {
+    match bytes.len() {
+        1..=16 => Ok(bytes.len()),
+        actual => Err(UnsignedIntBlobError::UnsupportedSize { actual }),
+    }
+}
+
+/// Decodes a big-endian unsigned blob of 1 to 16 bytes.
+fn decode_blob(bytes: &[u8]) -> Result<u128, UnsignedIntBlobError> {
+    let width = blob_width(bytes)?;
+    let mut array = [0u8; 16];
+    array[16 - width..].copy_from_slice(bytes);
+    Ok(u128::from_be_bytes(array))
+}
+
+/// Encodes `value` as a big-endian unsigned blob of `width` bytes.
+fn encode_blob(value: u128, width: usize) -> Result<Vec<u8>, UnsignedIntBlobError> {
+    if width < 16 && value >> (width * 8) != 0 {
+        return Err(UnsignedIntBlobError::Overflow { width });
+    }
+    Ok(value.to_be_bytes()[16 - width..].to_vec())
+}
+
+/// Returns the sign bit of a `width`-byte value, which the signed
+/// wrappers flip before writing.
+fn sign_bit(width: usize) -> u128 {
+    1 << (width * 8 - 1)
+}
+
+/// Decodes a big-endian signed blob of 1 to 16 bytes with its sign
+/// bit flipped.
+fn decode_signed_blob(bytes: &[u8]) -> Result<i128, UnsignedIntBlobError> {
+    let width = blob_width(bytes)?;
+    let shift = 128 - width * 8;
+    let bits = decode_blob(bytes)? ^ sign_bit(width);
+    // Sign-extend from the blob's width.
+    Ok(((bits << shift) as i128) >> shift)
+}
+
+/// Encodes `value` as a big-endian signed blob of `width` bytes with
+/// its sign bit flipped.
+fn encode_signed_blob(value: i128, width: usize) -> Result<Vec<u8>, UnsignedIntBlobError> {
+    let shift = 128 - width * 8;
+    if (value << shift) >> shift != value {
+        return Err(UnsignedIntBlobError::Overflow { width });
+    }
+    let bits = ((value << shift) as u128) >> shift;
+    encode_blob(bits ^ sign_bit(width), width)
+}
+
+/// The result of a `ublob_*` SQL function. An error is reported to
+/// SQLite, failing the statement.
+#[derive(Debug)]
+struct SqlResult<T>(Result<T, UnsignedIntBlobError>);
+
+impl ToSql<Binary, Sqlite> for SqlResult<Vec<u8>> {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        match &self.0 {
+            Ok(bytes) => <Vec<u8> as ToSql<Binary, Sqlite>>::to_sql(bytes, out),
+            Err(e) => Err(Box::new(e.clone())),
+        }
+    }
+}
+
+impl ToSql<Nullable<Binary>, Sqlite> for SqlResult<Option<Vec<u8>>> {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        match &self.0 {
+            Ok(Some(bytes)) => <Vec<u8> as ToSql<Binary, Sqlite>>::to_sql(bytes, out),
+            Ok(None) => Ok(IsNull::Yes),
+            Err(e) => Err(Box::new(e.clone())),
+        }
+    }
+}
+
+impl ToSql<Text, Sqlite> for SqlResult<String> {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        match &self.0 {
+            Ok(text) => <String as ToSql<Text, Sqlite>>::to_sql(text, out),
+            Err(e) => Err(Box::new(e.clone())),
+        }
+    }
+}
+
+/// State of the `ublob_sum` aggregate. `step` cannot fail, so the
+/// first error is kept and reported by `finalize`.
+///
+/// SQLite only guarantees 8-byte alignment for aggregate state, so
+/// the running sum is kept as big-endian bytes rather than a `u128`.
+#[derive(Debug, Default)]
+struct BlobSum {
+    sum: [u8; 16],
+    width: usize,
+    error: Option<UnsignedIntBlobError>,
+}
+
+impl SqliteAggregateFunction<Vec<u8>> for BlobSum {
+    type Output = SqlResult<Option<Vec<u8>>>;
+
+    fn step(&mut self, bytes: Vec<u8>) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = blob_width(&bytes).and_then(|width| {
+            self.width = self.width.max(width);
+            u128::from_be_bytes(self.sum)
+                .checked_add(decode_blob(&bytes)?)
+                .ok_or(UnsignedIntBlobError::Overflow { width: self.width })
+        });
+        match result {
+            Ok(sum) => self.sum = sum.to_be_bytes(),
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    fn finalize(aggregator: Option<Self>) -> Self::Output {
+        SqlResult(match aggregator {
+            None => Ok(None),
+            Some(BlobSum { error: Some(e), .. }) => Err(e),
+            Some(BlobSum { sum, width, .. }) => {
+                encode_blob(u128::from_be_bytes(sum), width).map(Some)
+            }
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
 
     use diesel::{dsl::count_star, prelude::*, sqlite::SqliteConnection};
+    #[cfg(all(
+        feature = "backend-encoding-tests",
+        any(feature = "postgres", feature = "mysql")
+    ))]
+    use diesel::{
+        query_builder::bind_collector::{BindCollector, RawBytesBindCollector},
+        sql_types::HasSqlType,
+    };
 
     use super::*;
 
@@ -302,7 +785,7 @@ mod tests {
                 diesel::insert_into(boundary_test::table)
                     .values((
                         boundary_test::id.eq(1),
-                        boundary_test::value.eq(<$blob_type>::from(0 as $type)),
+                        boundary_test::value.eq(<$blob_type>::from(<$type>::MIN)),
                     ))
                     .execute(&mut conn)
                     .unwrap();
@@ -325,8 +808,15 @@ mod tests {
                     .first::<BoundaryTest>(&mut conn)
                     .unwrap();
 
-                assert_eq!(min_value.value.get(), 0 as $type);
+                assert_eq!(min_value.value.get(), <$type>::MIN);
                 assert_eq!(max_value.value.get(), <$type>::MAX);
+
+                let ordered: Vec<i32> = boundary_test::table
+                    .select(boundary_test::id)
+                    .order(boundary_test::value.desc())
+                    .load(&mut conn)
+                    .unwrap();
+                assert_eq!(ordered, vec![2, 1]);
             }
         };
     }
@@ -343,12 +833,48 @@ mod tests {
 
     test_blob_generic!(roundtrip_u8, U8Blob, 42u8);
     test_blob_generic!(roundtrip_u128, U128Blob, u128::MAX);
+    test_blob_generic!(roundtrip_i32_negative, I32Blob, -42i32);
+    test_blob_generic!(roundtrip_i64_min, I64Blob, i64::MIN);
+    test_blob_generic!(roundtrip_i128_max, I128Blob, i128::MAX);
 
     test_diesel_boundary_values!(test_u8_boundary_values, u8, U8Blob);
     test_diesel_boundary_values!(test_u16_boundary_values, u16, U16Blob);
     test_diesel_boundary_values!(test_u32_boundary_values, u32, U32Blob);
     test_diesel_boundary_values!(test_u64_boundary_values, u64, U64Blob);
     test_diesel_boundary_values!(test_u128_boundary_values, u128, U128Blob);
+    test_diesel_boundary_values!(test_i32_boundary_values, i32, I32Blob);
+    test_diesel_boundary_values!(test_i64_boundary_values, i64, I64Blob);
+    test_diesel_boundary_values!(test_i128_boundary_values, i128, I128Blob);
+
+    #[test]
+    fn test_signed_encoding_flips_sign_bit() {
+        assert_eq!(I32Blob::from(i32::MIN).to_bytes(), [0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(I32Blob::from(-1).to_bytes(), [0x7f, 0xff, 0xff, 0xff]);
+        assert_eq!(I32Blob::from(0).to_bytes(), [0x80, 0x00, 0x00, 0x00]);
+        assert_eq!(I32Blob::from(i32::MAX).to_bytes(), [0xff, 0xff, 0xff, 0xff]);
+
+        let mut values = [i64::MAX, -1, 0, i64::MIN, 1, -1000, 1000];
+        let mut encoded: Vec<Vec<u8>> = values
+            .iter()
+            .map(|&v| I64Blob::from(v).to_bytes())
+            .collect();
+        values.sort();
+        encoded.sort();
+        let decoded: Vec<i64> = encoded
+            .iter()
+            .map(|b| I64Blob::from_bytes(b).unwrap().get())
+            .collect();
+        assert_eq!(decoded, values);
+
+        assert_eq!(
+            I64Blob::from_bytes(&[0x80; 4]),
+            Err(UnsignedIntBlobError::InvalidSize {
+                expected: 8,
+                actual: 4,
+                type_name: "i64".to_string(),
+            })
+        );
+    }
 
     #[cfg(test)]
     mod diesel_crud_operations {
@@ -975,6 +1501,563 @@ mod tests {
         }
     }
 
+    #[cfg(test)]
+    mod diesel_signed_query_operations {
+        use super::*;
+
+        table! {
+            signed_query (id) {
+                id -> Integer,
+                value_i32 -> Binary,
+                value_i64 -> Binary,
+                value_i128 -> Binary,
+            }
+        }
+
+        #[derive(Debug, PartialEq, Queryable, Insertable)]
+        #[diesel(table_name = signed_query)]
+        struct SignedEntry {
+            id: i32,
+            value_i32: I32Blob,
+            value_i64: I64Blob,
+            value_i128: I128Blob,
+        }
+
+        fn setup_signed_data() -> SqliteConnection {
+            let mut conn = SqliteConnection::establish(":memory:").unwrap();
+
+            diesel::sql_query(
+                "CREATE TABLE signed_query (
+                id INTEGER PRIMARY KEY,
+                value_i32 BLOB NOT NULL,
+                value_i64 BLOB NOT NULL,
+                value_i128 BLOB NOT NULL
+            )",
+            )
+            .execute(&mut conn)
+            .unwrap();
+
+            let entries: Vec<SignedEntry> = [
+                (1, 0),
+                (2, -1),
+                (3, 1),
+                (4, i32::MIN),
+                (5, i32::MAX),
+                (6, -300),
+                (7, 300),
+            ]
+            .into_iter()
+            .map(|(id, v)| SignedEntry {
+                id,
+                value_i32: v.into(),
+                value_i64: (v as i64 * 1_000_000_000).into(),
+                value_i128: (-(v as i128)).into(),
+            })
+            .collect();
+
+            diesel::insert_into(signed_query::table)
+                .values(&entries)
+                .execute(&mut conn)
+                .unwrap();
+
+            conn
+        }
+
+        fn ids(entries: Vec<SignedEntry>) -> Vec<i32> {
+            entries.iter().map(|e| e.id).collect()
+        }
+
+        #[test]
+        fn test_order_ascending_i32() {
+            let entries = signed_query::table
+                .order(signed_query::value_i32.asc())
+                .load(&mut setup_signed_data())
+                .unwrap();
+            assert_eq!(ids(entries), vec![4, 6, 2, 1, 3, 7, 5]);
+        }
+
+        #[test]
+        fn test_order_descending_i128() {
+            // value_i128 is the negation of value_i32, so descending
+            // order matches value_i32 ascending.
+            let entries = signed_query::table
+                .order(signed_query::value_i128.desc())
+                .load(&mut setup_signed_data())
+                .unwrap();
+            assert_eq!(ids(entries), vec![4, 6, 2, 1, 3, 7, 5]);
+        }
+
+        #[test]
+        fn test_filter_range_across_zero() {
+            let entries = signed_query::table
+                .filter(signed_query::value_i64.gt(I64Blob::from(-1_000_000_000)))
+                .filter(signed_query::value_i64.lt(I64Blob::from(300_000_000_000)))
+                .order(signed_query::id.asc())
+                .load(&mut setup_signed_data())
+                .unwrap();
+            assert_eq!(ids(entries), vec![1, 3]);
+        }
+
+        #[test]
+        fn test_filter_negative() {
+            let count: i64 = signed_query::table
+                .filter(signed_query::value_i32.lt(I32Blob::from(0)))
+                .count()
+                .get_result(&mut setup_signed_data())
+                .unwrap();
+            assert_eq!(count, 3);
+        }
+    }
+
+    #[cfg(test)]
+    mod diesel_sql_functions {
+        use super::*;
+
+        table! {
+            run_stats (id) {
+                id -> Integer,
+                category -> Text,
+                run_time_ns -> Binary,
+                run_count -> Binary,
+            }
+        }
+
+        #[derive(Debug, PartialEq, Queryable, Insertable)]
+        #[diesel(table_name = run_stats)]
+        struct RunStats {
+            id: i32,
+            category: String,
+            run_time_ns: U64Blob,
+            run_count: U8Blob,
+        }
+
+        fn setup_stats() -> SqliteConnection {
+            let mut conn = SqliteConnection::establish(":memory:").unwrap();
+            register_functions(&mut conn).unwrap();
+
+            diesel::sql_query(
+                "CREATE TABLE run_stats (
+                id INTEGER PRIMARY KEY,
+                category TEXT NOT NULL,
+                run_time_ns BLOB NOT NULL,
+                run_count BLOB NOT NULL
+            )",
+            )
+            .execute(&mut conn)
+            .unwrap();
+
+            let rows = vec![
+                RunStats {
+                    id: 1,
+                    category: "xdp".into(),
+                    run_time_ns: (i64::MAX as u64).into(),
+                    run_count: 200.into(),
+                },
+                RunStats {
+                    id: 2,
+                    category: "xdp".into(),
+                    run_time_ns: 1_000.into(),
+                    run_count: 55.into(),
+                },
+                RunStats {
+                    id: 3,
+                    category: "tc".into(),
+                    run_time_ns: 7.into(),
+                    run_count: 1.into(),
+                },
+            ];
+            diesel::insert_into(run_stats::table)
+                .values(&rows)
+                .execute(&mut conn)
+                .unwrap();
+
+            conn
+        }
+
+        #[test]
+        fn test_ublob_sum_grouped() {
+            // The xdp total exceeds i64::MAX, which SQLite's SUM could
+            // not hold.
+            let totals: Vec<(String, Option<U64Blob>)> = run_stats::table
+                .group_by(run_stats::category)
+                .select((run_stats::category, ublob_sum(run_stats::run_time_ns)))
+                .order(run_stats::category.asc())
+                .load(&mut setup_stats())
+                .unwrap();
+            assert_eq!(
+                totals,
+                vec![
+                    ("tc".into(), Some(7.into())),
+                    ("xdp".into(), Some((i64::MAX as u64 + 1_000).into())),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_ublob_sum_empty_is_null() {
+            let total: Option<U64Blob> = run_stats::table
+                .filter(run_stats::category.eq("kprobe"))
+                .select(ublob_sum(run_stats::run_time_ns))
+                .get_result(&mut setup_stats())
+                .unwrap();
+            assert_eq!(total, None);
+        }
+
+        #[test]
+        fn test_ublob_sum_overflow_fails() {
+            // 200 + 55 + 1 does not fit in the column's single byte.
+            let err = run_stats::table
+                .select(ublob_sum(run_stats::run_count))
+                .get_result::<Option<U8Blob>>(&mut setup_stats())
+                .unwrap_err();
+            assert!(
+                err.to_string().contains("value does not fit in 1 bytes"),
+                "{err}"
+            );
+        }
+
+        #[test]
+        fn test_ublob_sum_reports_widest_input() {
+            let mut sum = BlobSum::default();
+            sum.step(vec![0xff; 16]);
+            sum.step(vec![1; 4]);
+            assert!(matches!(
+                BlobSum::finalize(Some(sum)).0,
+                Err(UnsignedIntBlobError::Overflow { width: 16 })
+            ));
+
+            let mut sum = BlobSum::default();
+            sum.step(vec![0xff; 2]);
+            sum.step(vec![1; 4]);
+            sum.step(vec![0xff; 4]);
+            assert!(matches!(
+                BlobSum::finalize(Some(sum)).0,
+                Err(UnsignedIntBlobError::Overflow { width: 4 })
+            ));
+        }
+
+        #[test]
+        fn test_ublob_add() {
+            let mut conn = setup_stats();
+
+            let bumped: Vec<U64Blob> = run_stats::table
+                .select(ublob_add(run_stats::run_time_ns, U64Blob::from(1)))
+                .order(run_stats::id.asc())
+                .load(&mut conn)
+                .unwrap();
+            assert_eq!(
+                bumped,
+                vec![(i64::MAX as u64 + 1).into(), 1_001.into(), 8.into()]
+            );
+
+            // The result is as wide as the wider argument and can be
+            // compared with other blobs of that width.
+            let ids: Vec<i32> = run_stats::table
+                .filter(
+                    ublob_add(U64Blob::from(1_000), run_stats::run_count).gt(U64Blob::from(1_100)),
+                )
+                .select(run_stats::id)
+                .order(run_stats::id.asc())
+                .load(&mut conn)
+                .unwrap();
+            assert_eq!(ids, vec![1]);
+
+            let err = run_stats::table
+                .select(ublob_add(run_stats::run_count, U8Blob::from(100)))
+                .load::<U8Blob>(&mut conn)
+                .unwrap_err();
+            assert!(err.to_string().contains("Integer overflow"), "{err}");
+        }
+
+        #[test]
+        fn test_ublob_to_text() {
+            let text: Vec<String> = run_stats::table
+                .select(ublob_to_text(run_stats::run_time_ns))
+                .order(run_stats::id.asc())
+                .load(&mut setup_stats())
+                .unwrap();
+            assert_eq!(text, vec!["9223372036854775807", "1000", "7"]);
+
+            let max: String = diesel::select(ublob_to_text(U128Blob::from(u128::MAX)))
+                .get_result(&mut setup_stats())
+                .unwrap();
+            assert_eq!(max, u128::MAX.to_string());
+        }
+
+        #[test]
+        fn test_unsupported_width_fails() {
+            let mut conn = setup_stats();
+            for sql in [
+                "SELECT ublob_to_text(X'')",
+                "SELECT ublob_add(X'01', X'000102030405060708090a0b0c0d0e0f10')",
+            ] {
+                let err = diesel::sql_query(sql).execute(&mut conn).unwrap_err();
+                assert!(err.to_string().contains("expected 1 to 16 bytes"), "{err}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_blob_conversions() {
+        assert_eq!(
+            U64Blob::from(U32Blob::from(u32::MAX)).get(),
+            u32::MAX as u64
+        );
+        assert_eq!(U128Blob::from(U8Blob::from(7)).get(), 7);
+        assert_eq!(
+            I64Blob::from(I32Blob::from(i32::MIN)).get(),
+            i32::MIN as i64
+        );
+
+        assert_eq!(
+            U16Blob::try_from(U64Blob::from(65_535)),
+            Ok(U16Blob::from(65_535))
+        );
+        assert_eq!(
+            U16Blob::try_from(U64Blob::from(65_536)),
+            Err(UnsignedIntBlobError::Overflow { width: 2 })
+        );
+        assert_eq!(I32Blob::try_from(I128Blob::from(-5)), Ok(I32Blob::from(-5)));
+        assert_eq!(
+            I32Blob::try_from(I64Blob::from(i32::MIN as i64 - 1)),
+            Err(UnsignedIntBlobError::Overflow { width: 4 })
+        );
+    }
+
+    #[cfg(test)]
+    mod diesel_resize_column {
+        use super::*;
+
+        table! {
+            counters (id) {
+                id -> Integer,
+                value -> Binary,
+                optional_value -> Nullable<Binary>,
+            }
+        }
+
+        #[derive(Debug, PartialEq, Queryable, Insertable)]
+        #[diesel(table_name = counters)]
+        struct Counter32 {
+            id: i32,
+            value: U32Blob,
+            optional_value: Option<U32Blob>,
+        }
+
+        #[derive(Debug, PartialEq, Queryable)]
+        struct Counter64 {
+            id: i32,
+            value: U64Blob,
+            optional_value: Option<U64Blob>,
+        }
+
+        #[derive(Debug, PartialEq, Queryable)]
+        struct Counter16 {
+            id: i32,
+            value: U16Blob,
+            optional_value: Option<U32Blob>,
+        }
+
+        fn setup_counters(values: &[u32]) -> SqliteConnection {
+            let mut conn = SqliteConnection::establish(":memory:").unwrap();
+            register_functions(&mut conn).unwrap();
+
+            diesel::sql_query(
+                "CREATE TABLE counters (
+                id INTEGER PRIMARY KEY,
+                value BLOB NOT NULL,
+                optional_value BLOB
+            )",
+            )
+            .execute(&mut conn)
+            .unwrap();
+
+            let rows: Vec<Counter32> = values
+                .iter()
+                .enumerate()
+                .map(|(i, &v)| Counter32 {
+                    id: i as i32 + 1,
+                    value: v.into(),
+                    optional_value: (i % 2 == 0).then(|| v.into()),
+                })
+                .collect();
+            diesel::insert_into(counters::table)
+                .values(&rows)
+                .execute(&mut conn)
+                .unwrap();
+
+            conn
+        }
+
+        #[test]
+        fn test_widen_column() {
+            let mut conn = setup_counters(&[u32::MAX, 0, 70_000]);
+
+            assert_eq!(resize_blob_column(&mut conn, "counters", "value", 8), Ok(3));
+            assert_eq!(
+                resize_blob_column(&mut conn, "counters", "optional_value", 8),
+                Ok(2)
+            );
+
+            let rows: Vec<Counter64> = counters::table
+                .order(counters::value.asc())
+                .load(&mut conn)
+                .unwrap();
+            assert_eq!(
+                rows,
+                vec![
+                    Counter64 {
+                        id: 2,
+                        value: 0.into(),
+                        optional_value: None,
+                    },
+                    Counter64 {
+                        id: 3,
+                        value: 70_000.into(),
+                        optional_value: Some(70_000.into()),
+                    },
+                    Counter64 {
+                        id: 1,
+                        value: (u32::MAX as u64).into(),
+                        optional_value: Some((u32::MAX as u64).into()),
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn test_narrow_column() {
+            let mut conn = setup_counters(&[1, 65_535]);
+
+            assert_eq!(resize_blob_column(&mut conn, "counters", "value", 2), Ok(2));
+            let rows: Vec<Counter16> = counters::table
+                .order(counters::id.asc())
+                .load(&mut conn)
+                .unwrap();
+            assert_eq!(rows[1].value.get(), 65_535);
+        }
+
+        #[test]
+        fn test_narrow_column_overflow_changes_nothing() {
+            let mut conn = setup_counters(&[1, 65_536, 2]);
+
+            let err = resize_blob_column(&mut conn, "counters", "value", 2).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("Integer overflow: value does not fit in 2 bytes"),
+                "{err}"
+            );
+
+            let rows: Vec<Counter32> = counters::table
+                .order(counters::id.asc())
+                .load(&mut conn)
+                .unwrap();
+            let values: Vec<u32> = rows.iter().map(|r| r.value.get()).collect();
+            assert_eq!(values, vec![1, 65_536, 2]);
+        }
+
+        #[derive(Debug, PartialEq, Queryable, Insertable)]
+        #[diesel(table_name = counters)]
+        struct SignedCounter32 {
+            id: i32,
+            value: I32Blob,
+            optional_value: Option<I32Blob>,
+        }
+
+        #[derive(Debug, PartialEq, Queryable)]
+        struct SignedCounter64 {
+            id: i32,
+            value: I64Blob,
+            optional_value: Option<I64Blob>,
+        }
+
+        fn setup_signed_counters(values: &[i32]) -> SqliteConnection {
+            let mut conn = setup_counters(&[]);
+            let rows: Vec<SignedCounter32> = values
+                .iter()
+                .enumerate()
+                .map(|(i, &v)| SignedCounter32 {
+                    id: i as i32 + 1,
+                    value: v.into(),
+                    optional_value: None,
+                })
+                .collect();
+            diesel::insert_into(counters::table)
+                .values(&rows)
+                .execute(&mut conn)
+                .unwrap();
+            conn
+        }
+
+        #[test]
+        fn test_widen_signed_column() {
+            let mut conn = setup_signed_counters(&[i32::MAX, -1, i32::MIN, 0]);
+
+            assert_eq!(
+                resize_signed_blob_column(&mut conn, "counters", "value", 8),
+                Ok(4)
+            );
+            let values: Vec<i64> = counters::table
+                .order(counters::value.asc())
+                .load::<SignedCounter64>(&mut conn)
+                .unwrap()
+                .iter()
+                .map(|r| r.value.get())
+                .collect();
+            assert_eq!(values, vec![i32::MIN as i64, -1, 0, i32::MAX as i64]);
+
+            assert_eq!(
+                resize_signed_blob_column(&mut conn, "counters", "value", 16),
+                Ok(4)
+            );
+            assert_eq!(
+                resize_signed_blob_column(&mut conn, "counters", "value", 4),
+                Ok(4)
+            );
+            let values: Vec<i32> = counters::table
+                .order(counters::id.asc())
+                .load::<SignedCounter32>(&mut conn)
+                .unwrap()
+                .iter()
+                .map(|r| r.value.get())
+                .collect();
+            assert_eq!(values, vec![i32::MAX, -1, i32::MIN, 0]);
+
+            let err = resize_signed_blob_column(&mut conn, "counters", "value", 1).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("Integer overflow: value does not fit in 1 bytes"),
+                "{err}"
+            );
+        }
+
+        #[test]
+        /// Pins what the unsigned resize does to a signed column: the
+        /// flipped sign bit is kept in place, so values change.
+        fn test_unsigned_resize_of_signed_column() {
+            let mut conn = setup_signed_counters(&[-1]);
+
+            resize_blob_column(&mut conn, "counters", "value", 8).unwrap();
+            let row: SignedCounter64 = counters::table.first(&mut conn).unwrap();
+            assert_eq!(row.value.get(), i64::MIN + i32::MAX as i64);
+        }
+
+        #[test]
+        fn test_resize_rejects_bad_width_and_quotes_names() {
+            let mut conn = setup_counters(&[1]);
+
+            for width in [0, 17] {
+                let err = resize_blob_column(&mut conn, "counters", "value", width).unwrap_err();
+                assert!(err.to_string().contains("expected 1 to 16 bytes"), "{err}");
+            }
+
+            // A hostile name is quoted, so it is just a missing column.
+            let err =
+                resize_blob_column(&mut conn, "counters", "value\" = X'00' --", 8).unwrap_err();
+            assert!(err.to_string().contains("no such column"), "{err}");
+        }
+    }
+
     #[cfg(test)]
     mod diesel_null_handling {
         use super::*;
@@ -1131,6 +2214,177 @@ mod tests {
         }
     }
 
+    /// Returns the bytes `value` is sent to a `DB` server as.
+    #[cfg(all(
+        feature = "backend-encoding-tests",
+        any(feature = "postgres", feature = "mysql")
+    ))]
+    fn backend_bytes<DB, T>(value: &T, lookup: &mut DB::MetadataLookup) -> Vec<u8>
+    where
+        for<'a> DB: Backend<BindCollector<'a> = RawBytesBindCollector<DB>> + HasSqlType<Binary>,
+        T: ToSql<Binary, DB>,
+    {
+        let mut collector = RawBytesBindCollector::<DB>::new();
+        collector
+            .push_bound_value::<Binary, T>(value, lookup)
+            .unwrap();
+        collector.binds.pop().unwrap().unwrap()
+    }
+
+    /// Checks, without a server, that the blobs are sent to another
+    /// backend as their SQLite encoding, so compare the same there.
+    /// Run it with `cargo test --features postgres,backend-encoding-tests`.
+    #[cfg(all(
+        feature = "backend-encoding-tests",
+        any(feature = "postgres", feature = "mysql")
+    ))]
+    macro_rules! test_backend_encoding {
+        ($name:ident, $backend:ty, $lookup:expr) => {
+            #[test]
+            fn $name() {
+                let unsigned = [0, 1, 255, 256, i64::MAX as u64, u64::MAX].map(U64Blob::from);
+                let encoded: Vec<Vec<u8>> = unsigned
+                    .iter()
+                    .map(|v| backend_bytes::<$backend, _>(v, $lookup))
+                    .collect();
+                let expected: Vec<Vec<u8>> = unsigned.iter().map(|v| v.to_bytes()).collect();
+                assert_eq!(encoded, expected);
+                assert!(encoded.is_sorted());
+
+                let signed = [i32::MIN, -256, -1, 0, 1, i32::MAX].map(I32Blob::from);
+                let encoded: Vec<Vec<u8>> = signed
+                    .iter()
+                    .map(|v| backend_bytes::<$backend, _>(v, $lookup))
+                    .collect();
+                let expected: Vec<Vec<u8>> = signed.iter().map(|v| v.to_bytes()).collect();
+                assert_eq!(encoded, expected);
+                assert!(encoded.is_sorted());
+            }
+        };
+    }
+
+    /// A Postgres type lookup for types with a static OID, as
+    /// `bytea` has.
+    #[cfg(all(feature = "backend-encoding-tests", feature = "postgres"))]
+    struct StaticOidsOnly;
+
+    #[cfg(all(feature = "backend-encoding-tests", feature = "postgres"))]
+    impl diesel::pg::PgMetadataLookup for StaticOidsOnly {
+        fn lookup_type(&mut self, type_name: &str, _: Option<&str>) -> diesel::pg::PgTypeMetadata {
+            panic!("unexpected lookup of {type_name}")
+        }
+    }
+
+    #[cfg(all(feature = "backend-encoding-tests", feature = "postgres"))]
+    test_backend_encoding!(test_postgres_encoding, diesel::pg::Pg, &mut StaticOidsOnly);
+
+    #[cfg(all(feature = "backend-encoding-tests", feature = "mysql"))]
+    test_backend_encoding!(test_mysql_encoding, diesel::mysql::Mysql, &mut ());
+
+    /// Round-trips, orders and filters blobs on another backend, given
+    /// a connection URL in `$env`. Ignored by default, as it needs a
+    /// server; run it with `cargo test --features ... -- --ignored`.
+    #[cfg(any(feature = "postgres", feature = "mysql"))]
+    macro_rules! test_backend_roundtrip {
+        ($name:ident, $connection:ty, $env:literal, $create:literal) => {
+            #[test]
+            #[ignore = "needs a database server"]
+            fn $name() {
+                table! {
+                    backend_blobs (id) {
+                        id -> Integer,
+                        value_u64 -> Binary,
+                        value_i32 -> Binary,
+                    }
+                }
+
+                #[derive(Debug, PartialEq, Queryable, Insertable)]
+                #[diesel(table_name = backend_blobs)]
+                struct Row {
+                    id: i32,
+                    value_u64: U64Blob,
+                    value_i32: I32Blob,
+                }
+
+                let url = std::env::var($env)
+                    .unwrap_or_else(|_| panic!("{} must be set to run this test", $env));
+                let mut conn = <$connection>::establish(&url).unwrap();
+                diesel::sql_query($create).execute(&mut conn).unwrap();
+
+                let rows = vec![
+                    Row {
+                        id: 1,
+                        value_u64: u64::MAX.into(),
+                        value_i32: i32::MIN.into(),
+                    },
+                    Row {
+                        id: 2,
+                        value_u64: 0.into(),
+                        value_i32: (-1).into(),
+                    },
+                    Row {
+                        id: 3,
+                        value_u64: (i64::MAX as u64 + 1).into(),
+                        value_i32: i32::MAX.into(),
+                    },
+                ];
+                diesel::insert_into(backend_blobs::table)
+                    .values(&rows)
+                    .execute(&mut conn)
+                    .unwrap();
+
+                let by_u64: Vec<Row> = backend_blobs::table
+                    .order(backend_blobs::value_u64.asc())
+                    .load(&mut conn)
+                    .unwrap();
+                assert_eq!(
+                    by_u64.iter().map(|r| r.id).collect::<Vec<_>>(),
+                    vec![2, 3, 1]
+                );
+                assert_eq!(by_u64[2], rows[0]);
+
+                let by_i32: Vec<i32> = backend_blobs::table
+                    .select(backend_blobs::id)
+                    .order(backend_blobs::value_i32.asc())
+                    .load(&mut conn)
+                    .unwrap();
+                assert_eq!(by_i32, vec![1, 2, 3]);
+
+                let negative: Vec<i32> = backend_blobs::table
+                    .select(backend_blobs::id)
+                    .filter(backend_blobs::value_i32.lt(I32Blob::from(0)))
+                    .order(backend_blobs::id.asc())
+                    .load(&mut conn)
+                    .unwrap();
+                assert_eq!(negative, vec![1, 2]);
+            }
+        };
+    }
+
+    #[cfg(feature = "postgres")]
+    test_backend_roundtrip!(
+        test_postgres_roundtrip,
+        diesel::pg::PgConnection,
+        "S2S_TEST_POSTGRES_URL",
+        "CREATE TEMPORARY TABLE backend_blobs (
+            id INTEGER PRIMARY KEY,
+            value_u64 BYTEA NOT NULL,
+            value_i32 BYTEA NOT NULL
+        )"
+    );
+
+    #[cfg(feature = "mysql")]
+    test_backend_roundtrip!(
+        test_mysql_roundtrip,
+        diesel::mysql::MysqlConnection,
+        "S2S_TEST_MYSQL_URL",
+        "CREATE TEMPORARY TABLE backend_blobs (
+            id INTEGER PRIMARY KEY,
+            value_u64 VARBINARY(16) NOT NULL,
+            value_i32 VARBINARY(16) NOT NULL
+        )"
+    );
+
     #[test]
     fn test_malicious_looking_blob() {
         table! {